@@ -1,6 +1,8 @@
-use std::sync::Arc;
-use tokio::sync::Mutex;
-use webserver::{serial::connect_arduino, AppState};
+use webserver::{
+    protocol::Command,
+    serial::{connect_arduinos, Boards, DiscoveryConfig, LinkConfig},
+    AppState,
+};
 
 #[cfg(feature = "dhat-heap")]
 #[global_allocator]
@@ -14,15 +16,17 @@ async fn main() {
     println!("Starting memory profiling with optimizations...");
 
     // Simulate typical operations
-    let arduino_port = connect_arduino().await;
-    let state = AppState {
-        arduino: Arc::new(Mutex::new(arduino_port)),
-        scan_cache: Arc::new(Mutex::new(None)),
-    };
+    let arduino = Boards::default();
+    for connection in
+        connect_arduinos(&DiscoveryConfig::default(), &LinkConfig::default(), None).await
+    {
+        arduino.attach(connection).await;
+    }
+    let state = AppState::new(arduino);
 
     // Simulate workload
     for _ in 0..100 {
-        let _lock = state.arduino.lock().await;
+        let _ = state.arduino.send(Command::Scan).await;
         tokio::time::sleep(tokio::time::Duration::from_millis(10)).await;
     }
 
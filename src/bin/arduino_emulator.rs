@@ -0,0 +1,129 @@
+//! Virtual Arduino that speaks the `<CMD(...)>` protocol over a pseudo-terminal.
+//!
+//! Usage: `arduino-emulator [layout.json] [--link <path>] [--leds <first>-<last>] [--legacy[=silent]]`
+//!
+//! `--legacy` emulates firmware that predates `IDENTIFY()` and rejects it;
+//! `--legacy=silent` firmware that ignores it.
+//!
+//! The emulator prints the path of its serial device; start the server with
+//! `ARDUINO_PORT=<path>` to use it instead of a real board. Modules can be
+//! plugged and unplugged at runtime by typing commands on stdin.
+
+#[cfg(unix)]
+fn main() {
+    use serialport::{SerialPort, TTYPort};
+    use std::io::{BufRead, Read, Write};
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+    use webserver::emulator::{Emulator, CONTROL_HELP};
+
+    let mut layout = None;
+    let mut link = None;
+    let mut leds = None;
+    let mut legacy = None;
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--link" => link = args.next(),
+            "--leds" => leds = args.next(),
+            "--legacy" => legacy = Some(false),
+            "--legacy=silent" => legacy = Some(true),
+            _ => layout = Some(arg),
+        }
+    }
+
+    let emulator = match &layout {
+        Some(path) => Emulator::from_layout_file(path).unwrap_or_else(|e| {
+            eprintln!("[Error] {}", e);
+            std::process::exit(1);
+        }),
+        None => Emulator::default(),
+    };
+    let emulator = match leds.as_deref().and_then(parse_led_range) {
+        Some(range) => emulator.with_leds(range),
+        None if leds.is_some() => {
+            eprintln!("[Error] --leds expects <first>-<last>, e.g. 0-14");
+            std::process::exit(1);
+        }
+        None => emulator,
+    };
+    let emulator = match legacy {
+        Some(true) => emulator.with_silent_legacy(),
+        Some(false) => emulator.with_identity(None),
+        None => emulator,
+    };
+    let emulator = Arc::new(Mutex::new(emulator));
+
+    let (mut master, mut slave) = TTYPort::pair().expect("Unable to create pseudo-terminal");
+    let device = slave.name().expect("Pseudo-terminal has no name");
+    // The slave end is opened exclusively; release it so the server can open the path.
+    slave
+        .set_exclusive(false)
+        .expect("Unable to release pseudo-terminal");
+    drop(slave);
+
+    if let Some(link) = &link {
+        let _ = std::fs::remove_file(link);
+        if let Err(e) = std::os::unix::fs::symlink(&device, link) {
+            eprintln!("[Error] while linking {} to {}: {}", link, device, e);
+        }
+    }
+
+    println!("Emulated Arduino on {}", link.as_deref().unwrap_or(&device));
+    println!(
+        "Modules: {}",
+        serde_json::to_string(emulator.lock().unwrap().modules()).unwrap_or_default()
+    );
+    println!("{}", CONTROL_HELP);
+
+    let console = Arc::clone(&emulator);
+    std::thread::spawn(move || {
+        for line in std::io::stdin().lock().lines().map_while(Result::ok) {
+            if line.trim().is_empty() {
+                continue;
+            }
+            match console.lock().unwrap().apply_control(&line) {
+                Ok(message) => println!("{}", message),
+                Err(message) => println!("[Error] {}", message),
+            }
+        }
+    });
+
+    master
+        .set_timeout(Duration::from_millis(100))
+        .expect("Unable to configure pseudo-terminal");
+    let mut buffer = [0u8; 256];
+    loop {
+        let replies = match master.read(&mut buffer) {
+            Ok(n) if n > 0 => emulator.lock().unwrap().feed(&buffer[..n]),
+            Ok(_) => Vec::new(),
+            Err(ref e) if e.kind() == std::io::ErrorKind::TimedOut => Vec::new(),
+            // Nobody has the device open right now.
+            Err(_) => {
+                std::thread::sleep(Duration::from_millis(200));
+                Vec::new()
+            }
+        };
+        // Events typed on the console go out between replies, like hot-plug
+        // notifications from the real firmware.
+        let events = emulator.lock().unwrap().take_events();
+        for line in replies.into_iter().chain(events) {
+            println!("-> {}", line);
+            let _ = master.write_all(format!("{}\r\n", line).as_bytes());
+        }
+    }
+}
+
+/// Parses an inclusive range such as `15-29`.
+#[cfg(unix)]
+fn parse_led_range(text: &str) -> Option<std::ops::Range<i32>> {
+    let (first, last) = text.split_once('-')?;
+    let first: i32 = first.trim().parse().ok()?;
+    let last: i32 = last.trim().parse().ok()?;
+    (first <= last).then_some(first..last + 1)
+}
+
+#[cfg(not(unix))]
+fn main() {
+    eprintln!("arduino-emulator needs pseudo-terminals and only runs on Unix-like systems");
+}
@@ -3,6 +3,7 @@
 //! This module contains all configurable constants used throughout the application.
 //! Modify these values to customize the server behavior.
 
+use std::ops::RangeInclusive;
 use std::time::Duration;
 
 /// Arduino manufacturer name used for device discovery.
@@ -17,24 +18,220 @@ pub const MANUFACTURER: &str = "Microsoft";
 #[cfg(target_os = "linux")]
 pub const MANUFACTURER: &str = "Arduino";
 
-/// Serial communication baud rate.
+/// Environment variable that overrides device discovery with explicit ports.
+///
+/// Set it to the path printed by the `arduino-emulator` binary (or any other
+/// serial device) to skip the discovery rules, e.g.
+/// `ARDUINO_PORT=/dev/pts/3 cargo run`. Several ports are separated by commas.
+pub const PORT_ENV_VAR: &str = "ARDUINO_PORT";
+
+/// Environment variable naming the JSON file with device discovery rules.
+///
+/// See `serial::discovery::DiscoveryRule` for the rule format and
+/// `examples/discovery.json` for an example.
+pub const DISCOVERY_ENV_VAR: &str = "ARDUINO_DISCOVERY";
+
+/// Discovery rules file used when [`DISCOVERY_ENV_VAR`] is not set.
+///
+/// If it does not exist either, boards are found by [`MANUFACTURER`] and the
+/// USB vendor ids of genuine Arduino boards.
+pub const DISCOVERY_FILE: &str = "discovery.json";
+
+/// Environment variable selecting the serial framing.
+///
+/// `ARDUINO_FRAMING=sequenced` tags every command with a sequence id that the
+/// firmware echoes back; unset (or any other value) keeps the legacy framing
+/// for firmware that does not echo ids.
+pub const FRAMING_ENV_VAR: &str = "ARDUINO_FRAMING";
+
+/// Environment variable enabling checksummed frames.
+///
+/// `ARDUINO_CHECKSUM=crc8` appends a CRC-8 suffix (`*XX`) to every command and
+/// requires one on every reply. Only enable it with firmware that supports it.
+pub const CHECKSUM_ENV_VAR: &str = "ARDUINO_CHECKSUM";
+
+/// Environment variable controlling the binary framing.
+///
+/// Binary frames are used with every board that announces the `binary`
+/// capability; `ARDUINO_BINARY=off` keeps the text protocol regardless.
+pub const BINARY_ENV_VAR: &str = "ARDUINO_BINARY";
+
+/// Most LED lines sent in one binary batch frame (at most 255).
+///
+/// Larger batches are split, so the firmware's receive buffer stays small.
+pub const LED_BATCH_SIZE: usize = 32;
+
+/// Environment variable overriding [`MAX_RETRIES`].
+pub const RETRIES_ENV_VAR: &str = "ARDUINO_RETRIES";
+
+/// How often a command is retransmitted after a timeout or a corrupted reply.
+///
+/// All commands set absolute state on the board, so sending one twice is
+/// harmless. Each attempt waits for the command's full deadline.
+pub const MAX_RETRIES: u32 = 2;
+
+/// Environment variable overriding [`HEARTBEAT_INTERVAL`], in milliseconds.
+///
+/// `ARDUINO_HEARTBEAT=0` switches the heartbeat off.
+pub const HEARTBEAT_ENV_VAR: &str = "ARDUINO_HEARTBEAT";
+
+/// Environment variable overriding [`HEARTBEAT_MISSES`].
+pub const HEARTBEAT_MISSES_ENV_VAR: &str = "ARDUINO_HEARTBEAT_MISSES";
+
+/// How long a board may be idle before the server sends `PING()`.
+///
+/// A board that browned out while its USB bridge stays enumerated would
+/// otherwise look connected until the next user action fails.
+pub const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Deadline for the answer to a heartbeat `PING()`.
+pub const HEARTBEAT_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// Missed heartbeats in a row after which the connection is dropped and
+/// reopened. Fewer misses mark it degraded.
+pub const HEARTBEAT_MISSES: u32 = 3;
+
+/// Preferred serial communication baud rate, tried first when a port is opened.
 ///
-/// This must match the baud rate configured in the Arduino sketch.
 /// Common values: 9600 (reliable), 57600 (fast), 115200 (fastest).
 pub const BAUD_RATE: u32 = 9600;
 
-/// Serial port read/write timeout duration.
+/// Baud rates tried in order until the board answers the handshake, unless
+/// the discovery rules or [`BAUD_ENV_VAR`] list others.
+pub const BAUD_RATES: &[u32] = &[BAUD_RATE, 115200, 57600, 38400, 19200];
+
+/// Environment variable overriding [`BAUD_RATES`] (comma separated); a single
+/// rate switches detection off.
+pub const BAUD_ENV_VAR: &str = "ARDUINO_BAUD";
+
+/// Default deadline for the Arduino to answer a command.
 ///
-/// Operations that take longer than this will return a timeout error.
+/// Commands whose reply does not arrive within this time fail with a timeout
+/// error. Individual commands may use a different deadline.
 pub const TIMEOUT: Duration = Duration::from_secs(1);
 
+/// Deadline for the reply to a `SCAN()` command.
+///
+/// Scanning polls every I2C slot on the table and takes noticeably longer
+/// than the other commands.
+pub const SCAN_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Number of `SCAN()` samples voted on per scan (see
+/// `inventory::ConsensusConfig`). Sampling stops early once the vote is
+/// decided, so two agreeing samples out of three are enough.
+pub const SCAN_SAMPLES: usize = 3;
+
+/// Pause between two `SCAN()` samples.
+pub const SCAN_SAMPLE_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Environment variable overriding [`SCAN_SAMPLES`].
+pub const SCAN_SAMPLES_ENV_VAR: &str = "ARDUINO_SCAN_SAMPLES";
+
+/// Environment variable setting how many samples must report a module for it
+/// to count: a number, or `majority` (the default).
+pub const SCAN_QUORUM_ENV_VAR: &str = "ARDUINO_SCAN_QUORUM";
+
+/// Environment variable switching on the background scan of the table, with
+/// the pause between two scans in milliseconds.
+///
+/// Off unless set to a positive number. While the poller runs,
+/// `GET /api/scan` answers from its last result instead of scanning on every
+/// request; ticks that find the serial queue busy are skipped.
+pub const SCAN_POLL_ENV_VAR: &str = "ARDUINO_SCAN_POLL";
+
+/// I2C address of the table slot labelled 0 (the household); slot `n` sits
+/// at `I2C_SLOT_BASE + n`.
+pub const I2C_SLOT_BASE: u8 = 0x1F;
+
+/// Environment variable naming the table calibration file.
+///
+/// See `layout::Calibration` for the format and `examples/calibration.json`
+/// for an example.
+pub const CALIBRATION_ENV_VAR: &str = "ARDUINO_CALIBRATION";
+
+/// Calibration file used when [`CALIBRATION_ENV_VAR`] is not set. Edits made
+/// through `/api/admin/calibration` are saved to it.
+pub const CALIBRATION_FILE: &str = "calibration.json";
+
+/// Environment variable holding the token of the `/api/admin` routes.
+///
+/// Admin requests must send `Authorization: Bearer <token>`. Without the
+/// variable the admin routes answer `401` to everyone.
+pub const ADMIN_TOKEN_ENV_VAR: &str = "ARDUINO_ADMIN_TOKEN";
+
+/// How long the board may take to answer `IDENTIFY()` during the handshake.
+///
+/// Boards reset when the port is opened and ignore input while the bootloader
+/// runs, so the handshake is retried up to [`HANDSHAKE_ATTEMPTS`] times.
+pub const HANDSHAKE_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// Number of `IDENTIFY()` attempts before a port is given up on.
+///
+/// Together with [`HANDSHAKE_TIMEOUT`] this covers the ~2 seconds an Arduino
+/// needs to boot after the port is opened.
+pub const HANDSHAKE_ATTEMPTS: u32 = 6;
+
+/// Serial protocol revisions this server can talk to.
+///
+/// Boards identifying with a revision outside this range are rejected during
+/// the handshake; firmware without `IDENTIFY()` counts as revision 1.
+pub const PROTOCOL_VERSIONS: RangeInclusive<u32> = 1..=1;
+
 /// HTTP server port number.
 ///
 /// The web server will listen on this port for incoming connections.
 pub const SERVER_PORT: u16 = 5000;
 
-/// Interval between Arduino reconnection attempts.
+/// Delay before the first reconnection attempt after a failure.
+///
+/// Each further failed attempt doubles the delay up to
+/// [`RECONNECT_MAX_INTERVAL`]. The background monitor also looks for newly
+/// plugged boards at this interval.
+pub const RECONNECT_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Upper bound of the reconnection backoff.
+pub const RECONNECT_MAX_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Random spread of each reconnection delay, as a fraction of it.
+///
+/// `0.2` retries anywhere between 80% and 120% of the nominal delay, so boards
+/// that failed together do not retry in lockstep.
+pub const RECONNECT_JITTER: f64 = 0.2;
+
+/// Number of unsolicited board events buffered for slow subscribers.
+///
+/// A subscriber that falls further behind skips the oldest events instead of
+/// holding up the serial task.
+pub const EVENT_CHANNEL_SIZE: usize = 64;
+
+/// Maximum number of commands waiting for the serial task.
+///
+/// Handlers wait for a free slot when the queue is full, which throttles
+/// clients that send faster than the board can answer.
+pub const COMMAND_QUEUE_SIZE: usize = 32;
+
+/// Most commands one WebSocket client can have waiting.
+///
+/// A newer `update` or `led` replaces a queued one for the same module or
+/// line; beyond that, commands are rejected with `QUEUE_FULL` until results
+/// come back.
+pub const WS_QUEUE_SIZE: usize = 32;
+
+/// Environment variable enabling the serial traffic capture.
+///
+/// `ARDUINO_CAPTURE=capture.jsonl` appends every frame sent and every line
+/// received, with timestamp, direction and originating request id, to that
+/// file. See `serial::capture` for the record format.
+pub const CAPTURE_ENV_VAR: &str = "ARDUINO_CAPTURE";
+
+/// Size at which the capture file is rotated.
+pub const CAPTURE_MAX_BYTES: u64 = 10 * 1024 * 1024;
+
+/// Number of rotated capture files kept next to the current one.
+pub const CAPTURE_FILES: usize = 5;
+
+/// Environment variable naming a capture to replay instead of opening boards.
 ///
-/// The background monitor task checks the connection status at this interval
-/// and attempts to reconnect if disconnected.
-pub const RECONNECT_INTERVAL: Duration = Duration::from_secs(5);
+/// `ARDUINO_REPLAY=capture.jsonl` answers every command from the recorded
+/// session, so a bug report can be reproduced without the hardware.
+pub const REPLAY_ENV_VAR: &str = "ARDUINO_REPLAY";
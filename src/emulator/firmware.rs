@@ -0,0 +1,367 @@
+use crate::protocol::firmware::{CAP_BINARY, CAP_CRC8, CAP_SEQUENCED};
+use crate::protocol::frame::{
+    append_checksum, split_sequence, verify_checksum, with_sequence, Checked,
+};
+use crate::protocol::{BinaryFrame, Command, Event, FirmwareInfo, Response};
+use serde::{Deserialize, Serialize};
+use std::ops::Range;
+use std::path::Path;
+
+/// Console commands understood by [`Emulator::apply_control`].
+pub const CONTROL_HELP: &str = "Commands: plug <eeprom> <i2c> <type> | unplug <eeprom> | button <eeprom> <n> | fault <message> | freeze | thaw | load <file> | clear | list";
+
+/// Number of LED lines on the exhibition table (`ledID` 0..=29).
+pub const LED_LINES: i32 = 30;
+
+/// A module plugged into the emulated table, serialized exactly like one
+/// entry of the firmware's `SCAN:` reply.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct EmulatedModule {
+    #[serde(rename = "EEPROM")]
+    pub eeprom: i32,
+    #[serde(rename = "I2C")]
+    pub i2c: i32,
+    #[serde(rename = "TYPE")]
+    pub module_type: i32,
+}
+
+/// Software model of the Arduino firmware.
+///
+/// It consumes the same `<CMD(args)>` and binary frames the server writes to
+/// the serial port and produces the lines the real board would print.
+pub struct Emulator {
+    modules: Vec<EmulatedModule>,
+    leds: Range<i32>,
+    identity: Option<FirmwareInfo>,
+    /// Without an identity, ignores `IDENTIFY()` instead of rejecting it.
+    silent_legacy: bool,
+    pending: Vec<u8>,
+    /// Whether the server sends checksums, so events need one as well.
+    checksums: bool,
+    events: Vec<String>,
+    /// Ignores every frame, like a browned-out board whose USB bridge is
+    /// still enumerated.
+    frozen: bool,
+}
+
+impl Default for Emulator {
+    fn default() -> Self {
+        Self::new(Vec::new())
+    }
+}
+
+impl Emulator {
+    pub fn new(modules: Vec<EmulatedModule>) -> Self {
+        Self {
+            modules,
+            leds: 0..LED_LINES,
+            identity: Some(FirmwareInfo {
+                name: "arduino-emulator".to_string(),
+                version: env!("CARGO_PKG_VERSION").to_string(),
+                protocol: 1,
+                capabilities: vec![
+                    CAP_SEQUENCED.to_string(),
+                    CAP_CRC8.to_string(),
+                    CAP_BINARY.to_string(),
+                ],
+                leds: Some([0, LED_LINES - 1]),
+                identified: true,
+            }),
+            silent_legacy: false,
+            pending: Vec::new(),
+            checksums: false,
+            events: Vec::new(),
+            frozen: false,
+        }
+    }
+
+    /// Changes the answer to `IDENTIFY()`; `None` emulates firmware that
+    /// predates the handshake and rejects the command.
+    pub fn with_identity(mut self, identity: Option<FirmwareInfo>) -> Self {
+        self.identity = identity;
+        self
+    }
+
+    /// Emulates firmware that predates the handshake and does not answer
+    /// `IDENTIFY()` at all, like boards that drop unknown commands.
+    pub fn with_silent_legacy(mut self) -> Self {
+        self.identity = None;
+        self.silent_legacy = true;
+        self
+    }
+
+    /// Restricts the LED lines this board drives, for emulating one of
+    /// several controllers on a larger table. `IDENTIFY()` reports them.
+    pub fn with_leds(mut self, leds: Range<i32>) -> Self {
+        if let Some(identity) = &mut self.identity {
+            identity.leds = Some([leds.start, leds.end - 1]);
+        }
+        self.leds = leds;
+        self
+    }
+
+    /// Loads a module layout, i.e. a JSON array in `SCAN:` format.
+    pub fn from_layout_file(path: impl AsRef<Path>) -> Result<Self, String> {
+        Ok(Self::new(load_layout(path)?))
+    }
+
+    pub fn modules(&self) -> &[EmulatedModule] {
+        &self.modules
+    }
+
+    /// Plugs a module in, replacing whatever sat on the same EEPROM id or I2C slot.
+    pub fn plug(&mut self, module: EmulatedModule) {
+        self.modules
+            .retain(|m| m.eeprom != module.eeprom && m.i2c != module.i2c);
+        self.modules.push(module);
+        self.modules.sort_by_key(|m| m.i2c);
+    }
+
+    /// Removes the module with the given EEPROM id, returning it if it was plugged.
+    pub fn unplug(&mut self, eeprom: i32) -> Option<EmulatedModule> {
+        let index = self.modules.iter().position(|m| m.eeprom == eeprom)?;
+        Some(self.modules.remove(index))
+    }
+
+    /// Feeds raw bytes received from the server and returns the replies for
+    /// every complete `<...>` or `0x00`-delimited binary frame among them.
+    /// Bytes outside a frame are ignored.
+    pub fn feed(&mut self, bytes: &[u8]) -> Vec<String> {
+        let mut replies = Vec::new();
+
+        for &byte in bytes {
+            match (self.pending.first(), byte) {
+                (None, 0) => self.pending = vec![0],
+                // Back-to-back delimiters: still between frames.
+                (Some(0), 0) if self.pending.len() == 1 => {}
+                (Some(0), 0) => {
+                    let encoded = std::mem::take(&mut self.pending);
+                    replies.extend(self.handle_binary(&encoded[1..]));
+                }
+                (Some(0), _) => self.pending.push(byte),
+                (_, b'<') => self.pending = vec![byte],
+                (Some(_), b'>') => {
+                    let command = String::from_utf8_lossy(&self.pending[1..]).to_string();
+                    self.pending.clear();
+                    replies.extend(self.handle_frame(&command));
+                }
+                (Some(_), _) => self.pending.push(byte),
+                (None, _) => {}
+            }
+        }
+        replies
+    }
+
+    /// Handles the COBS encoded bytes of a [`BinaryFrame`]. Replies always
+    /// carry the frame's sequence id and a checksum; undecodable frames are
+    /// dropped like corrupted text frames.
+    pub fn handle_binary(&mut self, encoded: &[u8]) -> Option<String> {
+        if self.frozen {
+            return None;
+        }
+        let frame = BinaryFrame::decode(encoded).ok()?;
+        self.checksums = true;
+        let reply = match frame {
+            BinaryFrame::Command { seq, text } => {
+                self.handle_command(&with_sequence(Some(seq), &text))
+            }
+            BinaryFrame::LedBatch { seq, leds } => {
+                let response = leds
+                    .iter()
+                    .map(|led| self.execute(&led.to_command()))
+                    .find(|response| matches!(response, Response::Error { .. }))
+                    .unwrap_or_else(|| Response::Ok(format!("{} LEDs", leds.len())));
+                with_sequence(Some(seq), &response.encode())
+            }
+        };
+        Some(append_checksum(&reply))
+    }
+
+    /// Handles the text between `<` and `>`, checking and echoing a `*XX`
+    /// checksum if the frame carries one. Corrupted frames are dropped without
+    /// a reply, so the server times out and retransmits.
+    pub fn handle_frame(&mut self, frame: &str) -> Option<String> {
+        if self.frozen {
+            return None;
+        }
+        let (body, checksum) = match verify_checksum(frame) {
+            Checked::Valid(body) => (body, true),
+            Checked::Missing => (frame, false),
+            Checked::Corrupted => return None,
+        };
+        self.checksums = checksum;
+        if self.silent_legacy && Command::parse(split_sequence(body).1) == Ok(Command::Identify) {
+            return None;
+        }
+        let reply = self.handle_command(body);
+        Some(if checksum {
+            append_checksum(&reply)
+        } else {
+            reply
+        })
+    }
+
+    /// Queues an unsolicited `EVENT` line, like firmware noticing a hot-plug.
+    pub fn emit(&mut self, event: Event) {
+        let line = event.encode();
+        self.events.push(if self.checksums {
+            append_checksum(&line)
+        } else {
+            line
+        });
+    }
+
+    /// Removes and returns the event lines queued since the last call.
+    pub fn take_events(&mut self) -> Vec<String> {
+        std::mem::take(&mut self.events)
+    }
+
+    /// Executes one command (the text between `<` and `>`) and returns the reply line.
+    ///
+    /// A `#<seq>` prefix on the command is echoed in front of the reply, like
+    /// firmware with sequenced framing does.
+    pub fn handle_command(&mut self, frame: &str) -> String {
+        let (seq, command) = split_sequence(frame);
+        let response = match Command::parse(command) {
+            Ok(command) => self.execute(&command),
+            Err(message) => Response::Error {
+                code: None,
+                message,
+            },
+        };
+        with_sequence(seq, &response.encode())
+    }
+
+    /// Executes a parsed command the way the firmware does.
+    pub fn execute(&mut self, command: &Command) -> Response {
+        let unknown_eeprom = |eeprom: i32| Response::Error {
+            code: None,
+            message: format!("unknown EEPROM {}", eeprom),
+        };
+
+        match *command {
+            Command::Update { eeprom, .. } if eeprom != 0 && !self.is_plugged(eeprom) => {
+                unknown_eeprom(eeprom)
+            }
+            Command::Update { .. } => Response::Ok("Updated".to_string()),
+            Command::Led { led_id, .. } if !self.leds.contains(&led_id) => Response::Error {
+                code: None,
+                message: format!("unknown LED {}", led_id),
+            },
+            Command::Led { .. } => Response::Ok("LED".to_string()),
+            Command::Stop {
+                eeprom: Some(eeprom),
+            } if !self.is_plugged(eeprom) => unknown_eeprom(eeprom),
+            Command::Stop { .. } => Response::Ok("Stopped".to_string()),
+            Command::Scan => {
+                Response::Scan(serde_json::to_value(&self.modules).unwrap_or_default())
+            }
+            Command::Identify => match &self.identity {
+                Some(identity) => Response::Identify(identity_json(identity)),
+                None => Response::Error {
+                    code: None,
+                    message: "unknown command IDENTIFY".to_string(),
+                },
+            },
+            Command::Ping => Response::Ok("PONG".to_string()),
+        }
+    }
+
+    /// Applies an operator command typed into the emulator console.
+    ///
+    /// Supported: see [`CONTROL_HELP`]. `freeze` stops answering frames until
+    /// `thaw`, for exercising the server's heartbeat.
+    pub fn apply_control(&mut self, line: &str) -> Result<String, String> {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let number = |s: &str| {
+            s.parse::<i32>()
+                .map_err(|_| format!("'{}' is not a number", s))
+        };
+
+        match words.as_slice() {
+            ["plug", eeprom, i2c, module_type] => {
+                let module = EmulatedModule {
+                    eeprom: number(eeprom)?,
+                    i2c: number(i2c)?,
+                    module_type: number(module_type)?,
+                };
+                let message = format!("Plugged {:?}", module);
+                self.emit(Event::ModulePlugged {
+                    eeprom: module.eeprom,
+                    i2c: module.i2c,
+                    module_type: module.module_type,
+                });
+                self.plug(module);
+                Ok(message)
+            }
+            ["unplug", eeprom] => match self.unplug(number(eeprom)?) {
+                Some(module) => {
+                    self.emit(Event::ModuleUnplugged {
+                        eeprom: module.eeprom,
+                    });
+                    Ok(format!("Unplugged {:?}", module))
+                }
+                None => Err(format!("No module with EEPROM {}", eeprom)),
+            },
+            ["button", eeprom, button] => {
+                let event = Event::ButtonPressed {
+                    eeprom: number(eeprom)?,
+                    button: number(button)?,
+                };
+                self.emit(event.clone());
+                Ok(format!("Sent {}", event))
+            }
+            ["fault", ..] if words.len() > 1 => {
+                let event = Event::Fault {
+                    code: None,
+                    message: words[1..].join(" "),
+                };
+                self.emit(event.clone());
+                Ok(format!("Sent {}", event))
+            }
+            ["load", path] => {
+                self.modules = load_layout(path)?;
+                Ok(format!(
+                    "Loaded {} modules from {}",
+                    self.modules.len(),
+                    path
+                ))
+            }
+            ["freeze"] => {
+                self.frozen = true;
+                Ok("Ignoring all frames until 'thaw'".to_string())
+            }
+            ["thaw"] => {
+                self.frozen = false;
+                Ok("Answering frames again".to_string())
+            }
+            ["clear"] => {
+                self.modules.clear();
+                Ok("All modules unplugged".to_string())
+            }
+            ["list"] => Ok(serde_json::to_string_pretty(&self.modules).unwrap_or_default()),
+            _ => Err(CONTROL_HELP.to_string()),
+        }
+    }
+
+    fn is_plugged(&self, eeprom: i32) -> bool {
+        self.modules.iter().any(|m| m.eeprom == eeprom)
+    }
+}
+
+pub fn load_layout(path: impl AsRef<Path>) -> Result<Vec<EmulatedModule>, String> {
+    let path = path.as_ref();
+    let content = std::fs::read_to_string(path)
+        .map_err(|e| format!("Cannot read layout {}: {}", path.display(), e))?;
+    serde_json::from_str(&content).map_err(|e| format!("Invalid layout {}: {}", path.display(), e))
+}
+
+/// The `ID:` payload as real firmware sends it, without the server-side
+/// `identified` flag.
+fn identity_json(identity: &FirmwareInfo) -> serde_json::Value {
+    let mut payload = serde_json::to_value(identity).unwrap_or_default();
+    if let Some(fields) = payload.as_object_mut() {
+        fields.remove("identified");
+    }
+    payload
+}
@@ -0,0 +1,6 @@
+pub mod firmware;
+
+#[cfg(test)]
+mod tests;
+
+pub use firmware::{load_layout, EmulatedModule, Emulator, CONTROL_HELP};
@@ -0,0 +1,253 @@
+//! Tests for the Arduino firmware emulator
+
+#[cfg(test)]
+mod firmware_tests {
+    use crate::emulator::{EmulatedModule, Emulator};
+    use crate::protocol::frame::append_checksum;
+    use crate::protocol::{Event, FirmwareInfo, Response};
+    use crate::utils::format_response;
+
+    fn module(eeprom: i32, i2c: i32, module_type: i32) -> EmulatedModule {
+        EmulatedModule {
+            eeprom,
+            i2c,
+            module_type,
+        }
+    }
+
+    #[test]
+    fn test_scan_reply_is_parseable() {
+        let mut emulator = Emulator::new(vec![module(1, 32, 2)]);
+        let reply = emulator.handle_command("SCAN()");
+        assert_eq!(reply, r#"SCAN: [{"EEPROM":1,"I2C":32,"TYPE":2}]"#);
+
+        let data = format_response(&reply).unwrap();
+        assert_eq!(data[0]["TYPE"], 2);
+    }
+
+    #[test]
+    fn test_update_known_and_unknown_eeprom() {
+        let mut emulator = Emulator::new(vec![module(1, 32, 2)]);
+        assert_eq!(
+            emulator.handle_command("UPDATE(1, 100, 50, 30, 1)"),
+            "OK: Updated"
+        );
+        assert_eq!(
+            emulator.handle_command("UPDATE(9, 100, 50, 30, 1)"),
+            "ERROR: unknown EEPROM 9"
+        );
+    }
+
+    #[test]
+    fn test_led_range() {
+        let mut emulator = Emulator::default();
+        assert_eq!(
+            emulator.handle_command("LED(29, 1, 255, 0, 0, 2)"),
+            "OK: LED"
+        );
+        assert_eq!(
+            emulator.handle_command("LED(30, 1, 255, 0, 0, 2)"),
+            "ERROR: unknown LED 30"
+        );
+    }
+
+    #[test]
+    fn test_stop_with_and_without_eeprom() {
+        let mut emulator = Emulator::new(vec![module(4, 33, 1)]);
+        assert_eq!(emulator.handle_command("STOP()"), "OK: Stopped");
+        assert_eq!(emulator.handle_command("STOP(4)"), "OK: Stopped");
+        assert!(emulator.handle_command("STOP(5)").starts_with("ERROR"));
+    }
+
+    #[test]
+    fn test_malformed_and_unknown_commands() {
+        let mut emulator = Emulator::default();
+        assert!(emulator.handle_command("SCAN").starts_with("ERROR"));
+        assert!(emulator.handle_command("LED(1, 2)").starts_with("ERROR"));
+        assert_eq!(
+            emulator.handle_command("FOO()"),
+            "ERROR: unknown command FOO"
+        );
+    }
+
+    #[test]
+    fn test_identify() {
+        let mut emulator = Emulator::default();
+        let reply = emulator.handle_command("IDENTIFY()");
+        let Response::Identify(payload) = Response::parse(&reply) else {
+            panic!("unexpected reply {}", reply);
+        };
+        assert!(payload.get("identified").is_none());
+        let info = FirmwareInfo::from_value(payload).unwrap();
+        assert_eq!(info.name, "arduino-emulator");
+        assert!(info.supports("sequenced") && info.supports("crc8"));
+
+        let mut legacy = Emulator::default().with_identity(None);
+        assert_eq!(
+            legacy.handle_command("IDENTIFY()"),
+            "ERROR: unknown command IDENTIFY"
+        );
+
+        let mut silent = Emulator::default().with_silent_legacy();
+        assert_eq!(silent.handle_frame("IDENTIFY()"), None);
+        assert_eq!(silent.handle_frame("#3 IDENTIFY()"), None);
+        assert_eq!(silent.handle_frame("SCAN()").as_deref(), Some("SCAN: []"));
+    }
+
+    #[test]
+    fn test_console_emits_events() {
+        let mut emulator = Emulator::default();
+        emulator.apply_control("plug 5 40 7").unwrap();
+        emulator.apply_control("button 5 2").unwrap();
+        emulator.apply_control("unplug 5").unwrap();
+        emulator.apply_control("fault bus stuck").unwrap();
+        assert_eq!(
+            emulator.take_events(),
+            vec![
+                r#"EVENT PLUG: {"EEPROM":5,"I2C":40,"TYPE":7}"#,
+                r#"EVENT BUTTON: {"EEPROM":5,"BUTTON":2}"#,
+                r#"EVENT UNPLUG: {"EEPROM":5}"#,
+                "EVENT FAULT: bus stuck",
+            ]
+        );
+        assert!(emulator.take_events().is_empty());
+    }
+
+    #[test]
+    fn test_events_follow_checksum_mode() {
+        let mut emulator = Emulator::default();
+        emulator.handle_frame(&append_checksum("#1 STOP()"));
+        emulator.emit(Event::ModuleUnplugged { eeprom: 2 });
+        assert_eq!(
+            emulator.take_events(),
+            vec![append_checksum(r#"EVENT UNPLUG: {"EEPROM":2}"#)]
+        );
+    }
+
+    #[test]
+    fn test_sequence_id_is_echoed() {
+        let mut emulator = Emulator::default();
+        assert_eq!(emulator.handle_command("#42 STOP()"), "#42 OK: Stopped");
+        assert_eq!(
+            emulator.handle_command("#43 FOO()"),
+            "#43 ERROR: unknown command FOO"
+        );
+    }
+
+    #[test]
+    fn test_checksum_is_verified_and_echoed() {
+        let mut emulator = Emulator::default();
+        let frame = append_checksum("#7 STOP()");
+        assert_eq!(
+            emulator.handle_frame(&frame),
+            Some(append_checksum("#7 OK: Stopped"))
+        );
+        assert_eq!(emulator.handle_frame("#7 STOP()*00"), None);
+        assert_eq!(
+            emulator.handle_frame("STOP()"),
+            Some("OK: Stopped".to_string())
+        );
+    }
+
+    #[test]
+    fn test_led_subset() {
+        let mut emulator = Emulator::default().with_leds(15..30);
+        assert_eq!(
+            emulator.handle_command("LED(3, 1, 0, 0, 255, 1)"),
+            "ERROR: unknown LED 3"
+        );
+        assert_eq!(
+            emulator.handle_command("LED(15, 1, 0, 0, 255, 1)"),
+            "OK: LED"
+        );
+    }
+
+    #[test]
+    fn test_feed_extracts_frames() {
+        let mut emulator = Emulator::default();
+        assert!(emulator.feed(b"noise<STO").is_empty());
+        let replies = emulator.feed(b"P()>\n<SCAN()>\n");
+        assert_eq!(replies, vec!["OK: Stopped", "SCAN: []"]);
+    }
+
+    #[test]
+    fn test_plug_replaces_slot() {
+        let mut emulator = Emulator::new(vec![module(1, 32, 2)]);
+        emulator.plug(module(2, 32, 3));
+        assert_eq!(emulator.modules(), &[module(2, 32, 3)]);
+        assert_eq!(emulator.unplug(2), Some(module(2, 32, 3)));
+        assert!(emulator.modules().is_empty());
+    }
+
+    #[test]
+    fn test_ping_and_freeze() {
+        let mut emulator = Emulator::default();
+        assert_eq!(emulator.handle_frame("PING()").as_deref(), Some("OK: PONG"));
+
+        emulator.apply_control("freeze").unwrap();
+        assert_eq!(emulator.handle_frame("PING()"), None);
+        assert!(emulator.feed(b"<STOP()>").is_empty());
+
+        emulator.apply_control("thaw").unwrap();
+        assert_eq!(emulator.handle_frame("PING()").as_deref(), Some("OK: PONG"));
+    }
+
+    #[test]
+    fn test_control_commands() {
+        let mut emulator = Emulator::default();
+        assert!(emulator.apply_control("plug 5 40 7").is_ok());
+        assert_eq!(emulator.modules(), &[module(5, 40, 7)]);
+        assert!(emulator.apply_control("unplug 6").is_err());
+        assert!(emulator.apply_control("unplug 5").is_ok());
+        assert!(emulator.apply_control("plug x 1 1").is_err());
+        assert!(emulator.apply_control("dance").is_err());
+    }
+
+    #[test]
+    fn test_binary_frames() {
+        use crate::protocol::{BinaryFrame, LedSetting};
+        let led = |led_id| LedSetting {
+            led_id,
+            forward: false,
+            color: (1, 2, 3),
+            pulse_frequenz: 4,
+        };
+        let mut emulator = Emulator::default();
+
+        let mut bytes = BinaryFrame::Command {
+            seq: 9,
+            text: "STOP()".to_string(),
+        }
+        .encode();
+        bytes.extend(
+            BinaryFrame::LedBatch {
+                seq: 10,
+                leds: vec![led(0), led(29)],
+            }
+            .encode(),
+        );
+        // Split mid-frame, like reads from the serial port.
+        let (first, second) = bytes.split_at(5);
+        let mut replies = emulator.feed(first);
+        replies.extend(emulator.feed(second));
+        assert_eq!(
+            replies,
+            vec![
+                append_checksum("#9 OK: Stopped"),
+                append_checksum("#10 OK: 2 LEDs")
+            ]
+        );
+
+        let unknown = BinaryFrame::LedBatch {
+            seq: 11,
+            leds: vec![led(1), led(30)],
+        };
+        assert_eq!(
+            emulator.feed(&unknown.encode()),
+            vec![append_checksum("#11 ERROR: unknown LED 30")]
+        );
+
+        // A text frame still works after binary ones.
+        assert_eq!(emulator.feed(b"<PING()>\n"), vec!["OK: PONG"]);
+    }
+}
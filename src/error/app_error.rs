@@ -0,0 +1,154 @@
+use crate::error::request_id::current_request_id;
+use crate::models::ErrorResponse;
+use axum::{
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde_json::json;
+use std::fmt;
+use std::time::Duration;
+
+/// Every error the server reports to a client.
+///
+/// Each variant has a stable machine-readable [`code`](AppError::code) and an
+/// HTTP [`status`](AppError::status); the JSON body is built in one place by
+/// the [`IntoResponse`] impl.
+#[derive(Debug, Clone, PartialEq)]
+pub enum AppError {
+    /// No board is attached (or the serial task has stopped).
+    NotConnected,
+    /// The board did not answer within the command's deadline.
+    Timeout(Duration),
+    /// Writing to or reading from the transport failed.
+    Io(String),
+    /// A reply failed its checksum on every attempt.
+    Checksum { line: String },
+    /// A reply carried a sequence id that has not been issued yet.
+    SequenceMismatch { expected: u16, received: u16 },
+    /// The firmware answered with `ERROR`; the connection is still healthy.
+    Device { code: Option<i32>, message: String },
+    /// The board failed the handshake: unsupported protocol revision or a
+    /// missing feature.
+    IncompatibleFirmware(String),
+    /// A color string that is not `#RRGGBB`.
+    InvalidColor(String),
+    /// The request body could not be read or deserialized.
+    InvalidRequest(String),
+    /// A file the server keeps, e.g. the calibration, could not be written.
+    Storage(String),
+    /// A WebSocket client has this many commands waiting already.
+    QueueFull(usize),
+    /// An admin request without the admin token, or while admin routes are
+    /// disabled.
+    Unauthorized(String),
+}
+
+impl AppError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            AppError::NotConnected => "ARDUINO_NOT_CONNECTED",
+            AppError::Timeout(_) => "SERIAL_TIMEOUT",
+            AppError::Io(_) => "SERIAL_IO",
+            AppError::Checksum { .. } => "CHECKSUM_MISMATCH",
+            AppError::SequenceMismatch { .. } => "SEQUENCE_MISMATCH",
+            AppError::Device { .. } => "DEVICE_ERROR",
+            AppError::IncompatibleFirmware(_) => "INCOMPATIBLE_FIRMWARE",
+            AppError::InvalidColor(_) => "INVALID_COLOR",
+            AppError::InvalidRequest(_) => "INVALID_REQUEST",
+            AppError::Storage(_) => "STORAGE_ERROR",
+            AppError::Unauthorized(_) => "UNAUTHORIZED",
+            AppError::QueueFull(_) => "QUEUE_FULL",
+        }
+    }
+
+    pub fn status(&self) -> StatusCode {
+        match self {
+            AppError::NotConnected => StatusCode::SERVICE_UNAVAILABLE,
+            AppError::Timeout(_) => StatusCode::GATEWAY_TIMEOUT,
+            AppError::Io(_)
+            | AppError::Checksum { .. }
+            | AppError::SequenceMismatch { .. }
+            | AppError::IncompatibleFirmware(_) => StatusCode::BAD_GATEWAY,
+            AppError::Device { .. } => StatusCode::UNPROCESSABLE_ENTITY,
+            AppError::InvalidColor(_) | AppError::InvalidRequest(_) => StatusCode::BAD_REQUEST,
+            AppError::Storage(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            AppError::Unauthorized(_) => StatusCode::UNAUTHORIZED,
+            AppError::QueueFull(_) => StatusCode::TOO_MANY_REQUESTS,
+        }
+    }
+
+    /// Structured context for the `details` field of the response body.
+    pub fn details(&self) -> Option<serde_json::Value> {
+        match self {
+            AppError::Timeout(deadline) => {
+                Some(json!({ "timeout_ms": deadline.as_millis() as u64 }))
+            }
+            AppError::Checksum { line } => Some(json!({ "reply": line })),
+            AppError::SequenceMismatch { expected, received } => {
+                Some(json!({ "expected_seq": expected, "received_seq": received }))
+            }
+            AppError::Device {
+                code: Some(code), ..
+            } => Some(json!({ "firmware_code": code })),
+            _ => None,
+        }
+    }
+
+    /// Whether the serial link itself is broken and has to be reopened.
+    pub fn is_transport_failure(&self) -> bool {
+        matches!(self, AppError::Timeout(_) | AppError::Io(_))
+    }
+}
+
+impl fmt::Display for AppError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AppError::NotConnected => write!(f, "Arduino not connected - no port available"),
+            AppError::Timeout(deadline) => write!(
+                f,
+                "No response from Arduino within {} ms",
+                deadline.as_millis()
+            ),
+            AppError::Checksum { .. } => write!(f, "Reply from Arduino failed its checksum"),
+            AppError::QueueFull(queued) => write!(
+                f,
+                "{} commands are already waiting; wait for their results",
+                queued
+            ),
+            AppError::SequenceMismatch { expected, received } => write!(
+                f,
+                "Reply for command #{} arrived while waiting for #{}",
+                received, expected
+            ),
+            AppError::Io(message)
+            | AppError::Device { message, .. }
+            | AppError::IncompatibleFirmware(message)
+            | AppError::InvalidColor(message)
+            | AppError::InvalidRequest(message)
+            | AppError::Storage(message)
+            | AppError::Unauthorized(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl std::error::Error for AppError {}
+
+impl AppError {
+    /// The error body sent to clients, tagged with the current request id.
+    pub fn body(&self) -> ErrorResponse {
+        ErrorResponse {
+            status: "error".to_string(),
+            code: self.code().to_string(),
+            message: self.to_string(),
+            details: self.details(),
+            request_id: current_request_id(),
+        }
+    }
+}
+
+impl IntoResponse for AppError {
+    fn into_response(self) -> Response {
+        (self.status(), Json(self.body())).into_response()
+    }
+}
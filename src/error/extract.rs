@@ -0,0 +1,27 @@
+use crate::error::AppError;
+use axum::{
+    async_trait,
+    extract::{rejection::JsonRejection, FromRequest, Request},
+    Json,
+};
+use serde::de::DeserializeOwned;
+
+/// Drop-in replacement for [`axum::Json`] whose rejection is an [`AppError`],
+/// so malformed bodies get the same JSON error format as everything else.
+pub struct AppJson<T>(pub T);
+
+#[async_trait]
+impl<T, S> FromRequest<S> for AppJson<T>
+where
+    T: DeserializeOwned,
+    S: Send + Sync,
+{
+    type Rejection = AppError;
+
+    async fn from_request(req: Request, state: &S) -> Result<Self, Self::Rejection> {
+        Json::<T>::from_request(req, state)
+            .await
+            .map(|Json(value)| AppJson(value))
+            .map_err(|rejection: JsonRejection| AppError::InvalidRequest(rejection.body_text()))
+    }
+}
@@ -0,0 +1,10 @@
+pub mod app_error;
+pub mod extract;
+pub mod request_id;
+
+#[cfg(test)]
+mod tests;
+
+pub use app_error::AppError;
+pub use extract::AppJson;
+pub use request_id::{current_request_id, request_id, with_request_id, REQUEST_ID_HEADER};
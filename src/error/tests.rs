@@ -0,0 +1,83 @@
+//! Tests for the application error type
+
+#[cfg(test)]
+mod app_error_tests {
+    use crate::error::AppError;
+    use axum::{http::StatusCode, response::IntoResponse};
+    use std::time::Duration;
+
+    #[test]
+    fn test_status_mapping() {
+        assert_eq!(
+            AppError::NotConnected.status(),
+            StatusCode::SERVICE_UNAVAILABLE
+        );
+        assert_eq!(
+            AppError::Timeout(Duration::from_secs(1)).status(),
+            StatusCode::GATEWAY_TIMEOUT
+        );
+        assert_eq!(
+            AppError::Io("broken pipe".to_string()).status(),
+            StatusCode::BAD_GATEWAY
+        );
+        assert_eq!(
+            AppError::Device {
+                code: None,
+                message: "unknown EEPROM 9".to_string()
+            }
+            .status(),
+            StatusCode::UNPROCESSABLE_ENTITY
+        );
+        assert_eq!(
+            AppError::IncompatibleFirmware("protocol 2".to_string()).status(),
+            StatusCode::BAD_GATEWAY
+        );
+        assert_eq!(
+            AppError::InvalidColor("bad".to_string()).status(),
+            StatusCode::BAD_REQUEST
+        );
+        assert_eq!(
+            AppError::Storage("disk full".to_string()).status(),
+            StatusCode::INTERNAL_SERVER_ERROR
+        );
+        assert_eq!(
+            AppError::Unauthorized("no token".to_string()).status(),
+            StatusCode::UNAUTHORIZED
+        );
+        assert_eq!(
+            AppError::QueueFull(32).status(),
+            StatusCode::TOO_MANY_REQUESTS
+        );
+    }
+
+    #[test]
+    fn test_only_link_failures_drop_the_port() {
+        assert!(AppError::Timeout(Duration::from_secs(1)).is_transport_failure());
+        assert!(AppError::Io("broken pipe".to_string()).is_transport_failure());
+        assert!(!AppError::NotConnected.is_transport_failure());
+        assert!(!AppError::Device {
+            code: Some(1),
+            message: "busy".to_string()
+        }
+        .is_transport_failure());
+    }
+
+    #[test]
+    fn test_details() {
+        assert_eq!(AppError::NotConnected.details(), None);
+        assert_eq!(
+            AppError::Device {
+                code: Some(12),
+                message: "bus fault".to_string()
+            }
+            .details(),
+            Some(serde_json::json!({"firmware_code": 12}))
+        );
+    }
+
+    #[test]
+    fn test_into_response_without_request_id() {
+        let response = AppError::NotConnected.into_response();
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+    }
+}
@@ -0,0 +1,57 @@
+use crate::config::ADMIN_TOKEN_ENV_VAR;
+use crate::error::AppError;
+use crate::models::AppState;
+use axum::{
+    extract::{Request, State},
+    http::header::AUTHORIZATION,
+    middleware::Next,
+    response::Response,
+};
+use std::sync::Arc;
+
+/// Reads [`ADMIN_TOKEN_ENV_VAR`]; `None` leaves the admin routes off.
+pub fn admin_token_from_env() -> Option<Arc<str>> {
+    let token = std::env::var(ADMIN_TOKEN_ENV_VAR).ok()?;
+    if token.trim().is_empty() {
+        println!("[Error] {} is empty; admin routes off", ADMIN_TOKEN_ENV_VAR);
+        return None;
+    }
+    Some(token.trim().into())
+}
+
+/// Middleware for the `/api/admin` routes: lets a request through only if it
+/// carries `Authorization: Bearer` with `state.admin_token`.
+pub async fn require_admin(
+    State(state): State<AppState>,
+    request: Request,
+    next: Next,
+) -> Result<Response, AppError> {
+    let Some(expected) = &state.admin_token else {
+        return Err(AppError::Unauthorized(format!(
+            "Admin routes are disabled; set {} to enable them",
+            ADMIN_TOKEN_ENV_VAR
+        )));
+    };
+    let token = request
+        .headers()
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+    match token {
+        Some(token) if same_token(token.trim(), expected) => Ok(next.run(request).await),
+        _ => Err(AppError::Unauthorized(
+            "Missing or wrong admin token".to_string(),
+        )),
+    }
+}
+
+/// Compares without stopping at the first difference, so the time taken
+/// does not tell how much of a guess was right.
+fn same_token(given: &str, expected: &str) -> bool {
+    given.len() == expected.len()
+        && given
+            .bytes()
+            .zip(expected.bytes())
+            .fold(0, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
@@ -0,0 +1,14 @@
+use crate::models::{AppState, SuccessResponse};
+use axum::{extract::State, Json};
+
+/// Lists the connected boards and the modules and LED lines each one owns.
+pub async fn boards(State(state): State<AppState>) -> Json<SuccessResponse> {
+    Json(SuccessResponse {
+        status: "success".to_string(),
+        sent: None,
+        arduino_response: None,
+        message: None,
+        parameters: None,
+        data: serde_json::to_value(state.arduino.statuses()).ok(),
+    })
+}
@@ -0,0 +1,81 @@
+use crate::error::{AppError, AppJson};
+use crate::layout::{Calibration, LayoutStore};
+use crate::models::{AppState, LineCalibrationRequest, SlotCalibrationRequest, SuccessResponse};
+use axum::extract::{Path, State};
+use axum::Json;
+
+/// The table's calibration: what differs from the default slot addresses and
+/// LED ids.
+pub async fn calibration(State(state): State<AppState>) -> Json<SuccessResponse> {
+    Json(respond(&state.layout, None))
+}
+
+/// Replaces the whole calibration.
+pub async fn replace_calibration(
+    State(state): State<AppState>,
+    AppJson(calibration): AppJson<Calibration>,
+) -> Result<Json<SuccessResponse>, AppError> {
+    state.layout.calibrate(calibration).await?;
+    Ok(Json(saved(&state.layout)))
+}
+
+/// Moves the slot labelled `label` to another I2C address, in place of the
+/// one the calibration gave it before.
+pub async fn calibrate_slot(
+    State(state): State<AppState>,
+    Path(label): Path<String>,
+    AppJson(payload): AppJson<SlotCalibrationRequest>,
+) -> Result<Json<SuccessResponse>, AppError> {
+    let label: u8 = label
+        .parse()
+        .map_err(|_| AppError::InvalidRequest(format!("Unknown slot {}", label)))?;
+    state
+        .layout
+        .edit(|calibration| {
+            calibration.slots.retain(|_, slot| *slot != label);
+            calibration.slots.insert(payload.i2c, label);
+        })
+        .await?;
+    Ok(Json(saved(&state.layout)))
+}
+
+/// Gives the line with frontend key `key` another LED id.
+pub async fn calibrate_line(
+    State(state): State<AppState>,
+    Path(key): Path<String>,
+    AppJson(payload): AppJson<LineCalibrationRequest>,
+) -> Result<Json<SuccessResponse>, AppError> {
+    state
+        .layout
+        .edit(|calibration| {
+            calibration.lines.insert(key, payload.led_id);
+        })
+        .await?;
+    Ok(Json(saved(&state.layout)))
+}
+
+/// Drops the calibration, back to the default table.
+pub async fn reset_calibration(
+    State(state): State<AppState>,
+) -> Result<Json<SuccessResponse>, AppError> {
+    state.layout.calibrate(Calibration::default()).await?;
+    Ok(Json(saved(&state.layout)))
+}
+
+fn saved(store: &LayoutStore) -> SuccessResponse {
+    let message = store
+        .path()
+        .map(|path| format!("Saved to {}", path.display()));
+    respond(store, message)
+}
+
+fn respond(store: &LayoutStore, message: Option<String>) -> SuccessResponse {
+    SuccessResponse {
+        status: "success".to_string(),
+        sent: None,
+        arduino_response: None,
+        message,
+        parameters: None,
+        data: serde_json::to_value(store.calibration()).ok(),
+    }
+}
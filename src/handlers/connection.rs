@@ -0,0 +1,17 @@
+use crate::models::{AppState, SuccessResponse};
+use axum::{extract::State, Json};
+
+/// Reports the connection state, reconnect counters and last error of every
+/// board, plus the combined state of all of them.
+pub async fn connection(State(state): State<AppState>) -> Json<SuccessResponse> {
+    let status = state.arduino.connection();
+
+    Json(SuccessResponse {
+        status: "success".to_string(),
+        sent: None,
+        arduino_response: None,
+        message: None,
+        parameters: None,
+        data: serde_json::to_value(status).ok(),
+    })
+}
@@ -0,0 +1,21 @@
+use crate::live::stream;
+use crate::models::AppState;
+use axum::extract::State;
+use axum::response::sse::{Event, KeepAlive, Sse};
+use futures::stream::{Stream, StreamExt};
+use std::convert::Infallible;
+
+/// Streams every [`crate::live::LiveEvent`] as Server-Sent Events, named
+/// after its kind (`module`, `connection`, `command`, `firmware`) with the
+/// event as JSON data.
+pub async fn events(
+    State(state): State<AppState>,
+) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
+    let events = stream(&state).map(|event| {
+        Ok(Event::default()
+            .event(event.name())
+            .json_data(&event)
+            .unwrap_or_default())
+    });
+    Sse::new(events).keep_alive(KeepAlive::default())
+}
@@ -0,0 +1,27 @@
+use crate::models::{AppState, SuccessResponse};
+use axum::{extract::State, Json};
+
+/// Reports what each board identified itself as during the handshake.
+pub async fn firmware(State(state): State<AppState>) -> Json<SuccessResponse> {
+    let boards: Vec<serde_json::Value> = state
+        .arduino
+        .statuses()
+        .into_iter()
+        .map(|board| {
+            serde_json::json!({
+                "port": board.port,
+                "connected": board.connected,
+                "firmware": board.firmware,
+            })
+        })
+        .collect();
+
+    Json(SuccessResponse {
+        status: "success".to_string(),
+        sent: None,
+        arduino_response: None,
+        message: None,
+        parameters: None,
+        data: Some(serde_json::Value::Array(boards)),
+    })
+}
@@ -0,0 +1,87 @@
+use crate::layout::{Layout, LedLine, Slot};
+use crate::models::{AppState, ModuleView, ScanResponse, SuccessResponse};
+use axum::{extract::State, Json};
+use serde_json::{json, Value};
+
+/// Describes the table: every slot with its position, neighbours and LED
+/// lines, and the module the last scan found in it.
+///
+/// Reflects the calibration. Never scans; slots stay empty until a scan has
+/// run.
+pub async fn layout(State(state): State<AppState>) -> Json<ScanResponse> {
+    let layout = &state.layout.layout();
+    let snapshot = state.scan_cache.get();
+    let modules = snapshot
+        .as_ref()
+        .map(|snapshot| snapshot.modules.clone())
+        .unwrap_or_default();
+
+    let slots: Vec<Value> = layout
+        .slots()
+        .iter()
+        .map(|slot| {
+            let module = modules.iter().find(|module| module.i2c == slot.i2c);
+            let module = module.map(|module| ModuleView::new(module, layout));
+            describe_slot(layout, slot, module)
+        })
+        .collect();
+    let lines: Vec<Value> = layout
+        .lines()
+        .iter()
+        .map(|line| describe_line(layout, line))
+        .collect();
+
+    let outside: Vec<String> = modules
+        .iter()
+        .filter(|module| layout.slot_at_i2c(module.i2c).is_none())
+        .map(|module| format!("{} (I2C {})", module.eeprom, module.i2c))
+        .collect();
+
+    Json(ScanResponse {
+        response: SuccessResponse {
+            status: "success".to_string(),
+            sent: None,
+            arduino_response: None,
+            message: if outside.is_empty() {
+                None
+            } else {
+                Some(format!(
+                    "Modules outside the layout: {}",
+                    outside.join(", ")
+                ))
+            },
+            parameters: None,
+            data: Some(json!({ "slots": slots, "lines": lines })),
+        },
+        consensus: None,
+        scanned_at_ms: snapshot.map(|snapshot| snapshot.scanned_at_ms),
+    })
+}
+
+fn describe_slot(layout: &Layout, slot: &Slot, module: Option<ModuleView>) -> Value {
+    let mut value = serde_json::to_value(slot).unwrap_or_default();
+    if let Some(hex) = slot.hex() {
+        let (row, col) = hex.to_offset();
+        value["row"] = json!(row);
+        value["col"] = json!(col);
+    }
+    let lines: Vec<u8> = layout
+        .lines_of(slot.label)
+        .iter()
+        .map(|line| line.id)
+        .collect();
+    value["neighbours"] = json!(layout.neighbours(slot.label));
+    value["lines"] = json!(lines);
+    value["module"] = json!(module);
+    value
+}
+
+fn describe_line(layout: &Layout, line: &LedLine) -> Value {
+    json!({
+        "id": line.id,
+        "key": line.key,
+        "from": line.from,
+        "to": line.to,
+        "slots": layout.line_ends(line),
+    })
+}
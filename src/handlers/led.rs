@@ -1,82 +1,105 @@
-use crate::models::{AppState, ErrorResponse, LedRequest, SuccessResponse};
-use crate::serial::send_data;
-use crate::utils::{hex_to_rgb, make_led_string};
-use axum::{extract::State, http::StatusCode, Json};
+use crate::error::{AppError, AppJson};
+use crate::models::{AppState, LedBatchRequest, LedRequest, SuccessResponse};
+use crate::protocol::{Command, LedSetting};
+use crate::utils::hex_to_rgb;
+use axum::{extract::State, Json};
 
 pub async fn led(
     State(state): State<AppState>,
-    Json(payload): Json<LedRequest>,
-) -> Result<Json<SuccessResponse>, (StatusCode, Json<ErrorResponse>)> {
-    let mut arduino = state.arduino.lock().await;
+    AppJson(payload): AppJson<LedRequest>,
+) -> Result<Json<SuccessResponse>, AppError> {
+    apply_led(&state, payload).await.map(Json)
+}
 
-    if arduino.is_none() {
-        return Err((
-            StatusCode::SERVICE_UNAVAILABLE,
-            Json(ErrorResponse {
-                status: "error".to_string(),
-                message: "Arduino not connected - no port available".to_string(),
-            }),
-        ));
+/// Sends `LED` for `payload`; shared by `POST /api/led` and the WebSocket.
+pub async fn apply_led(state: &AppState, payload: LedRequest) -> Result<SuccessResponse, AppError> {
+    if !state.arduino.is_connected() {
+        return Err(AppError::NotConnected);
     }
 
     println!("{:?}", payload);
 
-    let rgb_color = match hex_to_rgb(&payload.color) {
-        Ok(color) => color,
-        Err(e) => {
-            return Err((
-                StatusCode::BAD_REQUEST,
-                Json(ErrorResponse {
-                    status: "error".to_string(),
-                    message: e,
-                }),
-            ))
-        }
+    let rgb_color = hex_to_rgb(&payload.color)?;
+
+    let command = Command::Led {
+        led_id: payload.led_id,
+        forward: payload.forward,
+        color: rgb_color,
+        pulse_frequenz: payload.pulse_frequenz,
     };
 
-    let data_string = make_led_string(
-        payload.led_id,
-        rgb_color,
-        payload.forward,
-        payload.pulse_frequenz,
-    );
+    let result = state.arduino.send(command.clone()).await;
+    state.live.command(&command, &result);
+    result?;
+    Ok(SuccessResponse {
+        status: "success".to_string(),
+        sent: None,
+        arduino_response: None,
+        message: Some("LED parameters received".to_string()),
+        parameters: Some(serde_json::json!({
+            "ledID": payload.led_id,
+            "color": rgb_color,
+            "forward": payload.forward,
+            "pulseFrequenz": payload.pulse_frequenz,
+        })),
+        data: None,
+    })
+}
+
+/// Sets several LED lines with one request, sent as binary batch frames to
+/// boards that support them.
+pub async fn leds(
+    State(state): State<AppState>,
+    AppJson(payload): AppJson<LedBatchRequest>,
+) -> Result<Json<SuccessResponse>, AppError> {
+    apply_leds(&state, payload).await.map(Json)
+}
+
+/// Sets the LED lines of `payload`; shared by `POST /api/leds` and the
+/// WebSocket.
+pub async fn apply_leds(
+    state: &AppState,
+    payload: LedBatchRequest,
+) -> Result<SuccessResponse, AppError> {
+    if !state.arduino.is_connected() {
+        return Err(AppError::NotConnected);
+    }
+    if payload.leds.is_empty() {
+        return Err(AppError::InvalidRequest("No LEDs given".to_string()));
+    }
+
+    let leds = payload
+        .leds
+        .iter()
+        .map(|led| {
+            let command = Command::Led {
+                led_id: led.led_id,
+                forward: led.forward,
+                color: hex_to_rgb(&led.color)?,
+                pulse_frequenz: led.pulse_frequenz,
+            };
+            LedSetting::from_command(&command).ok_or_else(|| {
+                AppError::InvalidRequest(format!(
+                    "LED {} out of range (ledID 0-255, pulseFrequenz 0-65535)",
+                    led.led_id
+                ))
+            })
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    let count = leds.len();
 
-    let port = arduino.as_mut().unwrap();
-    match send_data(port, &data_string) {
-        Ok(response) if !response.to_lowercase().starts_with("error") => {
-            Ok(Json(SuccessResponse {
-                status: "success".to_string(),
-                sent: None,
-                arduino_response: None,
-                message: Some("LED parameters received".to_string()),
-                parameters: Some(serde_json::json!({
-                    "ledID": payload.led_id,
-                    "color": rgb_color,
-                    "forward": payload.forward,
-                    "pulseFrequenz": payload.pulse_frequenz,
-                })),
-                data: None,
-            }))
-        }
-        Ok(_) => {
-            *arduino = None;
-            Err((
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(ErrorResponse {
-                    status: "error".to_string(),
-                    message: "No response from Arduino".to_string(),
-                }),
-            ))
-        }
-        Err(e) => {
-            *arduino = None;
-            Err((
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(ErrorResponse {
-                    status: "error".to_string(),
-                    message: e,
-                }),
-            ))
-        }
+    let commands: Vec<Command> = leds.iter().map(|led| led.to_command()).collect();
+    let result = state.arduino.send_leds(leds).await;
+    for command in &commands {
+        state.live.command(command, &result);
     }
+    result?;
+    Ok(SuccessResponse {
+        status: "success".to_string(),
+        sent: None,
+        arduino_response: None,
+        message: Some(format!("{} LEDs set", count)),
+        parameters: None,
+        data: None,
+    })
 }
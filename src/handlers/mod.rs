@@ -1,9 +1,33 @@
+pub mod admin;
+pub mod boards;
+pub mod calibration;
+pub mod connection;
+pub mod events;
+pub mod firmware;
+pub mod layout;
 pub mod led;
+pub mod modules;
+pub mod ports;
 pub mod scan;
+pub mod stats;
 pub mod stop;
 pub mod update;
+pub mod ws;
 
-pub use led::led;
+pub use admin::{admin_token_from_env, require_admin};
+pub use boards::boards;
+pub use calibration::{
+    calibrate_line, calibrate_slot, calibration, replace_calibration, reset_calibration,
+};
+pub use connection::connection;
+pub use events::events;
+pub use firmware::firmware;
+pub use layout::layout;
+pub use led::{led, leds};
+pub use modules::modules;
+pub use ports::ports;
 pub use scan::scan;
+pub use stats::serial_stats;
 pub use stop::stop;
 pub use update::update;
+pub use ws::ws;
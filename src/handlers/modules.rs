@@ -0,0 +1,44 @@
+use crate::error::AppError;
+use crate::inventory::rescan;
+use crate::models::{AppState, ModuleView, ModulesResponse};
+use axum::{extract::State, Json};
+
+/// Lists the modules of the last scan with their type and slot spelled out,
+/// scanning first if nothing has been scanned yet.
+pub async fn modules(State(state): State<AppState>) -> Result<Json<ModulesResponse>, AppError> {
+    let snapshot = match state.scan_cache.get() {
+        Some(snapshot) => Some(snapshot),
+        None => {
+            if !state.arduino.is_connected() {
+                return Err(AppError::NotConnected);
+            }
+            rescan(&state).await?;
+            state.scan_cache.get()
+        }
+    };
+    let modules = snapshot
+        .as_ref()
+        .map(|snapshot| snapshot.modules.clone())
+        .unwrap_or_default();
+
+    let layout = state.layout.layout();
+    let unknown: Vec<String> = modules
+        .iter()
+        .filter(|module| !module.module_type.is_known())
+        .map(|module| format!("{} ({})", module.eeprom, module.module_type.code()))
+        .collect();
+
+    Ok(Json(ModulesResponse {
+        status: "success".to_string(),
+        message: if unknown.is_empty() {
+            None
+        } else {
+            Some(format!("Unknown module types: {}", unknown.join(", ")))
+        },
+        data: modules
+            .iter()
+            .map(|module| ModuleView::new(module, &layout))
+            .collect(),
+        scanned_at_ms: snapshot.map(|snapshot| snapshot.scanned_at_ms),
+    }))
+}
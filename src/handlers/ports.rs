@@ -0,0 +1,23 @@
+use crate::models::{AppState, SuccessResponse};
+use axum::{extract::State, Json};
+
+/// Lists every serial port the system reports, which discovery rule matched
+/// it and whether a board on it is connected.
+pub async fn ports(State(state): State<AppState>) -> Json<SuccessResponse> {
+    let discovery = state.discovery.clone();
+    let mut ports = tokio::task::spawn_blocking(move || discovery.enumerate())
+        .await
+        .unwrap_or_default();
+    for port in &mut ports {
+        port.connected = state.arduino.is_port_connected(&port.port);
+    }
+
+    Json(SuccessResponse {
+        status: "success".to_string(),
+        sent: None,
+        arduino_response: None,
+        message: None,
+        parameters: None,
+        data: serde_json::to_value(ports).ok(),
+    })
+}
@@ -1,130 +1,80 @@
-use crate::models::{AppState, ErrorResponse, SuccessResponse};
-use crate::serial::send_data;
-use crate::utils::format_response;
-use axum::{extract::State, http::StatusCode, Json};
-use std::time::Duration;
+use crate::error::AppError;
+use crate::inventory::rescan;
+use crate::models::{AppState, ScanResponse, SuccessResponse};
+use crate::protocol::Response;
+use axum::{extract::State, Json};
 
-pub async fn scan(
-    State(state): State<AppState>,
-) -> Result<Json<SuccessResponse>, (StatusCode, Json<ErrorResponse>)> {
-    let mut arduino = state.arduino.lock().await;
+/// Answers with the modules on the table.
+///
+/// While the topology poller runs this is its last result from
+/// `scan_cache`. Otherwise the table is scanned several times and the modules
+/// most samples agree on are returned (see [`crate::inventory::Tally`]);
+/// without a consensus the last good scan is returned from the cache.
+pub async fn scan(State(state): State<AppState>) -> Result<Json<ScanResponse>, AppError> {
+    apply_scan(&state).await.map(Json)
+}
 
-    if arduino.is_none() {
-        return Err((
-            StatusCode::SERVICE_UNAVAILABLE,
-            Json(ErrorResponse {
-                status: "error".to_string(),
-                message: "Arduino not connected - no port available".to_string(),
-            }),
-        ));
+/// The body of `GET /api/scan`, shared with the WebSocket.
+pub async fn apply_scan(state: &AppState) -> Result<ScanResponse, AppError> {
+    if !state.arduino.is_connected() {
+        return Err(AppError::NotConnected);
     }
 
-    let port = arduino.as_mut().unwrap();
-
-    let response1 = match send_data(port, "SCAN()") {
-        Ok(r) if !r.to_lowercase().starts_with("error") => r,
-        Ok(_) | Err(_) => {
-            *arduino = None;
-            return Err((
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(ErrorResponse {
-                    status: "error".to_string(),
-                    message: "No response from Arduino on first scan".to_string(),
-                }),
-            ));
-        }
-    };
-
-    let formatted1 = format_response(&response1).ok();
-    tokio::time::sleep(Duration::from_millis(100)).await;
-
-    let response2 = match send_data(port, "SCAN()") {
-        Ok(r) if !r.to_lowercase().starts_with("error") => r,
-        Ok(_) | Err(_) => {
-            *arduino = None;
-            return Err((
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(ErrorResponse {
-                    status: "error".to_string(),
-                    message: "No response from Arduino on second scan".to_string(),
-                }),
-            ));
+    if state.scan_poll.is_some() {
+        if let Some(snapshot) = state.scan_cache.get() {
+            return Ok(ScanResponse {
+                response: SuccessResponse {
+                    status: "success".to_string(),
+                    sent: None,
+                    arduino_response: None,
+                    message: None,
+                    parameters: None,
+                    data: serde_json::to_value(&snapshot.modules).ok(),
+                },
+                consensus: snapshot.diagnostics,
+                scanned_at_ms: Some(snapshot.scanned_at_ms),
+            });
         }
-    };
-
-    let formatted2 = format_response(&response2).ok();
-
-    if formatted1 == formatted2 {
-        if let Some(data) = &formatted1 {
-            *state.scan_cache.lock().await = Some(data.clone());
-        }
-
-        return Ok(Json(SuccessResponse {
-            status: "success".to_string(),
-            sent: None,
-            arduino_response: Some(response1.clone()),
-            message: None,
-            parameters: None,
-            data: formatted1,
-        }));
     }
 
-    let response3 = match send_data(port, "SCAN()") {
-        Ok(r) if !r.to_lowercase().starts_with("error") => r,
-        Ok(_) | Err(_) => {
-            *arduino = None;
-            return Err((
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(ErrorResponse {
-                    status: "error".to_string(),
-                    message: "No response from Arduino on third scan".to_string(),
-                }),
-            ));
-        }
-    };
-
-    let formatted3 = format_response(&response3).ok();
-
-    if formatted1 == formatted3 {
-        if let Some(data) = &formatted1 {
-            *state.scan_cache.lock().await = Some(data.clone());
-        }
-
-        Ok(Json(SuccessResponse {
-            status: "success".to_string(),
-            sent: None,
-            arduino_response: Some(response1),
-            message: None,
-            parameters: None,
-            data: formatted1,
-        }))
-    } else if formatted2 == formatted3 {
-        if let Some(data) = &formatted2 {
-            *state.scan_cache.lock().await = Some(data.clone());
-        }
+    let tally = rescan(state).await?;
+    let diagnostics = tally.diagnostics();
+    let snapshot = state.scan_cache.get();
+    let data = serde_json::to_value(
+        snapshot
+            .as_ref()
+            .map(|snapshot| snapshot.modules.clone())
+            .unwrap_or_default(),
+    )
+    .ok();
 
-        Ok(Json(SuccessResponse {
-            status: "success".to_string(),
-            sent: None,
-            arduino_response: Some(response2),
-            message: None,
-            parameters: None,
-            data: formatted2,
-        }))
+    let (arduino_response, message) = if tally.modules().is_some() {
+        (
+            data.clone().map(|data| Response::Scan(data).to_string()),
+            None,
+        )
     } else {
-        let cached_data = state.scan_cache.lock().await.clone();
+        (
+            Some(format!(
+                "No consensus among {} samples",
+                diagnostics.samples
+            )),
+            snapshot
+                .as_ref()
+                .map(|_| "Using cached scan data after inconsistent Arduino responses".to_string()),
+        )
+    };
 
-        Ok(Json(SuccessResponse {
+    Ok(ScanResponse {
+        response: SuccessResponse {
             status: "success".to_string(),
             sent: None,
-            arduino_response: Some("No consistent pair found".to_string()),
-            message: if cached_data.is_some() {
-                Some("Using cached scan data after inconsistent Arduino responses".to_string())
-            } else {
-                None
-            },
+            arduino_response,
+            message,
             parameters: None,
-            data: cached_data.or_else(|| Some(serde_json::json!([]))),
-        }))
-    }
+            data,
+        },
+        consensus: Some(diagnostics),
+        scanned_at_ms: snapshot.map(|snapshot| snapshot.scanned_at_ms),
+    })
 }
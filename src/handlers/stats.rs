@@ -0,0 +1,21 @@
+use crate::models::{AppState, SuccessResponse};
+use axum::{extract::State, Json};
+
+/// Reports the serial link settings and frame counters of every board.
+pub async fn serial_stats(State(state): State<AppState>) -> Json<SuccessResponse> {
+    let stats: Vec<serde_json::Value> = state
+        .arduino
+        .statuses()
+        .into_iter()
+        .map(|board| serde_json::json!({ "port": board.port, "link": board.link }))
+        .collect();
+
+    Json(SuccessResponse {
+        status: "success".to_string(),
+        sent: None,
+        arduino_response: None,
+        message: None,
+        parameters: None,
+        data: Some(serde_json::Value::Array(stats)),
+    })
+}
@@ -1,59 +1,38 @@
-use crate::models::{AppState, ErrorResponse, StopRequest, SuccessResponse};
-use crate::serial::send_data;
-use axum::{extract::State, http::StatusCode, Json};
+use crate::error::{AppError, AppJson};
+use crate::models::{AppState, StopRequest, SuccessResponse};
+use crate::protocol::Command;
+use axum::{extract::State, Json};
 
 pub async fn stop(
     State(state): State<AppState>,
-    Json(payload): Json<Option<StopRequest>>,
-) -> Result<Json<SuccessResponse>, (StatusCode, Json<ErrorResponse>)> {
-    let mut arduino = state.arduino.lock().await;
+    AppJson(payload): AppJson<Option<StopRequest>>,
+) -> Result<Json<SuccessResponse>, AppError> {
+    apply_stop(&state, payload).await.map(Json)
+}
 
-    if arduino.is_none() {
-        return Err((
-            StatusCode::SERVICE_UNAVAILABLE,
-            Json(ErrorResponse {
-                status: "error".to_string(),
-                message: "Arduino not connected - no port available".to_string(),
-            }),
-        ));
+/// Sends `STOP`, to every board without an EEPROM id; shared by
+/// `POST /api/stop` and the WebSocket.
+pub async fn apply_stop(
+    state: &AppState,
+    payload: Option<StopRequest>,
+) -> Result<SuccessResponse, AppError> {
+    if !state.arduino.is_connected() {
+        return Err(AppError::NotConnected);
     }
 
-    let command = match payload {
-        Some(req) if req.eeprom.is_some() => format!("STOP({})", req.eeprom.unwrap()),
-        _ => "STOP()".to_string(),
+    let command = Command::Stop {
+        eeprom: payload.and_then(|req| req.eeprom),
     };
 
-    let port = arduino.as_mut().unwrap();
-    match send_data(port, &command) {
-        Ok(response) if !response.to_lowercase().starts_with("error") => {
-            Ok(Json(SuccessResponse {
-                status: "success".to_string(),
-                sent: None,
-                arduino_response: Some(response),
-                message: None,
-                parameters: None,
-                data: None,
-            }))
-        }
-        Ok(_) => {
-            *arduino = None;
-            Err((
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(ErrorResponse {
-                    status: "error".to_string(),
-                    message: "No response from Arduino".to_string(),
-                }),
-            ))
-        }
-        Err(e) => {
-            *arduino = None;
-            Err((
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(ErrorResponse {
-                    status: "error".to_string(),
-                    message: e,
-                }),
-            ))
-        }
-    }
+    let result = state.arduino.send(command.clone()).await;
+    state.live.command(&command, &result);
+    let response = result?;
+    Ok(SuccessResponse {
+        status: "success".to_string(),
+        sent: None,
+        arduino_response: Some(response.to_string()),
+        message: None,
+        parameters: None,
+        data: None,
+    })
 }
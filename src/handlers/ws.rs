@@ -0,0 +1,167 @@
+use crate::config::WS_QUEUE_SIZE;
+use crate::error::{current_request_id, with_request_id, AppError};
+use crate::handlers::led::{apply_led, apply_leds};
+use crate::handlers::scan::apply_scan;
+use crate::handlers::stop::apply_stop;
+use crate::handlers::update::apply_update;
+use crate::live::stream;
+use crate::models::{AppState, WsCommand, WsMessage, WsOutcome, WsRequest};
+use axum::extract::ws::{Message, WebSocket, WebSocketUpgrade};
+use axum::extract::State;
+use axum::response::Response;
+use futures::stream::SplitSink;
+use futures::{FutureExt, SinkExt, StreamExt};
+use std::collections::VecDeque;
+
+/// Control channel for interactive clients: takes the commands of the REST
+/// endpoints as JSON messages, answers each with a result carrying its `id`,
+/// and pushes every live event (see `GET /api/events`).
+///
+/// Commands run one at a time in arrival order. A queued `update` for a
+/// module, or `led` for a line, is answered as superseded as soon as a newer
+/// one for it arrives, so a dragged slider does not build up a backlog on the
+/// board. At most [`WS_QUEUE_SIZE`] commands wait; more are rejected.
+///
+/// The session runs as part of the upgrade request, so error results and
+/// captured traffic carry its request id.
+pub async fn ws(ws: WebSocketUpgrade, State(state): State<AppState>) -> Response {
+    let request_id = current_request_id();
+    ws.on_upgrade(move |socket| with_request_id(request_id, session(socket, state)))
+}
+
+type Sender = SplitSink<WebSocket, Message>;
+
+async fn session(socket: WebSocket, state: AppState) {
+    let (mut sender, mut receiver) = socket.split();
+    let mut events = Box::pin(stream(&state));
+    let mut pending: VecDeque<WsRequest> = VecDeque::new();
+
+    loop {
+        // Take in whatever arrived while the last command ran.
+        while let Some(message) = receiver.next().now_or_never() {
+            if !accept(message, &mut pending, &mut sender).await {
+                return;
+            }
+        }
+        while let Some(Some(event)) = events.next().now_or_never() {
+            if send_event(&mut sender, event).await.is_err() {
+                return;
+            }
+        }
+
+        if let Some(request) = pending.pop_front() {
+            let result = WsMessage::Result {
+                id: request.id,
+                outcome: execute(&state, request.command).await,
+            };
+            if send(&mut sender, &result).await.is_err() {
+                return;
+            }
+            continue;
+        }
+
+        tokio::select! {
+            message = receiver.next() => {
+                if !accept(message, &mut pending, &mut sender).await {
+                    return;
+                }
+            }
+            Some(event) = events.next() => {
+                if send_event(&mut sender, event).await.is_err() {
+                    return;
+                }
+            }
+        }
+    }
+}
+
+/// Queues a client message, answering malformed ones, the command it
+/// supersedes and ones beyond a full queue right away. Returns `false` once
+/// the connection is closed.
+async fn accept(
+    message: Option<Result<Message, axum::Error>>,
+    pending: &mut VecDeque<WsRequest>,
+    sender: &mut Sender,
+) -> bool {
+    let text = match message {
+        Some(Ok(Message::Text(text))) => text,
+        Some(Ok(Message::Binary(_))) => {
+            let error = AppError::InvalidRequest("Binary messages are not supported".to_string());
+            return reject(sender, None, error).await;
+        }
+        Some(Ok(Message::Ping(_) | Message::Pong(_))) => return true,
+        Some(Ok(Message::Close(_))) | Some(Err(_)) | None => return false,
+    };
+
+    let value: serde_json::Value = match serde_json::from_str(&text) {
+        Ok(value) => value,
+        Err(e) => {
+            let error = AppError::InvalidRequest(format!("Invalid JSON: {}", e));
+            return reject(sender, None, error).await;
+        }
+    };
+    let id = value.get("id").cloned();
+    match serde_json::from_value::<WsRequest>(value) {
+        Ok(request) => {
+            let target = request.command.target();
+            let older = target.and_then(|target| {
+                pending
+                    .iter()
+                    .position(|queued| queued.command.target() == Some(target))
+            });
+            if let Some(older) = older.and_then(|index| pending.remove(index)) {
+                pending.push_back(request);
+                let result = WsMessage::Result {
+                    id: older.id,
+                    outcome: WsOutcome::Superseded {
+                        status: "superseded".to_string(),
+                    },
+                };
+                return send(sender, &result).await.is_ok();
+            }
+            if pending.len() >= WS_QUEUE_SIZE {
+                return reject(sender, request.id, AppError::QueueFull(pending.len())).await;
+            }
+            pending.push_back(request);
+            true
+        }
+        Err(e) => {
+            let error = AppError::InvalidRequest(format!("Invalid command: {}", e));
+            reject(sender, id, error).await
+        }
+    }
+}
+
+async fn reject(sender: &mut Sender, id: Option<serde_json::Value>, error: AppError) -> bool {
+    let result = WsMessage::Result {
+        id,
+        outcome: WsOutcome::Error(error.body()),
+    };
+    send(sender, &result).await.is_ok()
+}
+
+async fn execute(state: &AppState, command: WsCommand) -> WsOutcome {
+    let result = match command {
+        WsCommand::Update(payload) => apply_update(state, payload).await.map(WsOutcome::Success),
+        WsCommand::Led(payload) => apply_led(state, payload).await.map(WsOutcome::Success),
+        WsCommand::Leds(payload) => apply_leds(state, payload).await.map(WsOutcome::Success),
+        WsCommand::Stop(payload) => apply_stop(state, Some(payload))
+            .await
+            .map(WsOutcome::Success),
+        WsCommand::Scan => apply_scan(state).await.map(WsOutcome::Scan),
+    };
+    result.unwrap_or_else(|error| WsOutcome::Error(error.body()))
+}
+
+async fn send_event(sender: &mut Sender, event: crate::live::LiveEvent) -> Result<(), axum::Error> {
+    let message = WsMessage::Event {
+        event: event.name(),
+        data: event,
+    };
+    send(sender, &message).await
+}
+
+async fn send(sender: &mut Sender, message: &WsMessage) -> Result<(), axum::Error> {
+    let text = serde_json::to_string(message).unwrap_or_default();
+    sender.send(Message::Text(text)).await
+}
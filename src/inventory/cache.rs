@@ -0,0 +1,54 @@
+use super::{diff, ModuleChange, ModuleInfo, ScanDiagnostics};
+use std::sync::{Arc, RwLock};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// The modules of one scan that reached a consensus.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Snapshot {
+    pub modules: Vec<ModuleInfo>,
+    /// Milliseconds since the Unix epoch at which the scan finished.
+    pub scanned_at_ms: u64,
+    /// How the samples of the scan agreed, if it was voted on.
+    pub diagnostics: Option<ScanDiagnostics>,
+}
+
+/// The current inventory of the table, shared by the background poller and
+/// the handlers.
+///
+/// Only scans that reached a consensus are stored, so a failed or disputed
+/// scan keeps the last known modules.
+#[derive(Clone, Debug, Default)]
+pub struct ScanCache {
+    inner: Arc<RwLock<Option<Snapshot>>>,
+}
+
+impl ScanCache {
+    pub fn get(&self) -> Option<Snapshot> {
+        self.inner.read().unwrap().clone()
+    }
+
+    /// Replaces the inventory with `modules` and returns how the table
+    /// changed. The first scan reports every module as added.
+    pub fn update(
+        &self,
+        modules: Vec<ModuleInfo>,
+        diagnostics: Option<ScanDiagnostics>,
+    ) -> Vec<ModuleChange> {
+        let scanned_at_ms = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_millis() as u64)
+            .unwrap_or_default();
+        let mut inner = self.inner.write().unwrap();
+        let previous = inner.as_ref().map(|snapshot| snapshot.modules.as_slice());
+        let changes = diff(previous.unwrap_or_default(), &modules);
+        for change in &changes {
+            println!("[Topology] {}", change);
+        }
+        *inner = Some(Snapshot {
+            modules,
+            scanned_at_ms,
+            diagnostics,
+        });
+        changes
+    }
+}
@@ -0,0 +1,293 @@
+use super::ModuleInfo;
+use crate::config::{
+    SCAN_QUORUM_ENV_VAR, SCAN_SAMPLES, SCAN_SAMPLES_ENV_VAR, SCAN_SAMPLE_INTERVAL,
+};
+use crate::error::AppError;
+use crate::protocol::Response;
+use crate::serial::{Boards, ScanFailure};
+use serde::Serialize;
+use serde_json::Value;
+use std::time::Duration;
+
+/// How many samples have to report a module for it to count.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Voting {
+    /// More than half of the samples.
+    Majority,
+    /// At least this many samples (at most all of them).
+    Quorum(usize),
+}
+
+/// How a scan is sampled and voted on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ConsensusConfig {
+    /// Most `SCAN()` commands sent per scan.
+    pub samples: usize,
+    pub voting: Voting,
+    /// Pause between two samples.
+    pub interval: Duration,
+}
+
+impl Default for ConsensusConfig {
+    fn default() -> Self {
+        Self {
+            samples: SCAN_SAMPLES,
+            voting: Voting::Majority,
+            interval: SCAN_SAMPLE_INTERVAL,
+        }
+    }
+}
+
+impl ConsensusConfig {
+    /// Reads [`SCAN_SAMPLES_ENV_VAR`] and [`SCAN_QUORUM_ENV_VAR`], keeping the
+    /// defaults for missing or invalid values.
+    pub fn from_env() -> Self {
+        let mut config = Self::default();
+        if let Some(samples) = std::env::var(SCAN_SAMPLES_ENV_VAR)
+            .ok()
+            .and_then(|value| value.parse::<usize>().ok())
+            .filter(|samples| *samples > 0)
+        {
+            config.samples = samples;
+        }
+        match std::env::var(SCAN_QUORUM_ENV_VAR).as_deref() {
+            Ok("majority") | Err(_) => {}
+            Ok(value) => match value.parse::<usize>() {
+                Ok(quorum) if quorum > 0 => config.voting = Voting::Quorum(quorum),
+                _ => println!(
+                    "[Error] {}={} is neither a number nor 'majority'",
+                    SCAN_QUORUM_ENV_VAR, value
+                ),
+            },
+        }
+        config
+    }
+
+    /// Votes a module needs to be part of the result.
+    pub fn required_votes(&self) -> usize {
+        match self.voting {
+            Voting::Majority => self.samples / 2 + 1,
+            Voting::Quorum(quorum) => quorum.clamp(1, self.samples.max(1)),
+        }
+    }
+}
+
+/// A module entry reported by at least one sample, with the number of
+/// samples that reported exactly this entry.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ModuleVote {
+    pub module: ModuleInfo,
+    pub votes: usize,
+    pub accepted: bool,
+}
+
+/// A board that missed samples of a scan over several boards.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FailedBoard {
+    pub port: String,
+    /// Samples the board did not answer with a module list.
+    pub samples: usize,
+    /// Why it failed the last of them.
+    pub error: String,
+}
+
+/// How a scan came about, reported as `consensus` by `GET /api/scan`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ScanDiagnostics {
+    /// `SCAN()` samples taken.
+    pub samples: usize,
+    /// Samples whose reply was not a module list.
+    pub invalid_samples: usize,
+    /// Samples identical to the result.
+    pub agreeing_samples: usize,
+    pub required_votes: usize,
+    /// Entries not reported by every valid sample, e.g. a module with a
+    /// loose contact or one that was just plugged in.
+    pub disputed: Vec<ModuleVote>,
+    /// Boards that failed samples; the others' modules were still counted.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub failed_boards: Vec<FailedBoard>,
+}
+
+/// Per-module vote over several `SCAN()` samples.
+///
+/// Modules are identified by their `EEPROM` id. Each id is voted on
+/// separately, so a flaky module only drops itself from the result instead
+/// of invalidating the whole scan. If samples disagree about an id's other
+/// fields (e.g. its I2C slot), the entry with the most votes wins, the
+/// earliest seen on a tie.
+#[derive(Debug, Clone)]
+pub struct Tally {
+    config: ConsensusConfig,
+    samples: Vec<Option<Vec<ModuleInfo>>>,
+    /// Distinct entries in order of first appearance, with their votes.
+    entries: Vec<(ModuleInfo, usize)>,
+    failed_boards: Vec<FailedBoard>,
+}
+
+impl Tally {
+    pub fn new(config: ConsensusConfig) -> Self {
+        Self {
+            config,
+            samples: Vec::new(),
+            entries: Vec::new(),
+            failed_boards: Vec::new(),
+        }
+    }
+
+    /// Counts one sample; anything but a list of well-formed module entries
+    /// counts as invalid.
+    pub fn add(&mut self, payload: Option<&Value>) {
+        let modules = payload.and_then(ModuleInfo::parse_scan);
+        for module in modules.iter().flatten() {
+            match self.entries.iter_mut().find(|(entry, _)| entry == module) {
+                Some((_, votes)) => *votes += 1,
+                None => self.entries.push((*module, 1)),
+            }
+        }
+        self.samples.push(modules);
+    }
+
+    /// Notes the boards that did not answer the last sample; it holds the
+    /// modules of the others.
+    pub fn add_failures(&mut self, failures: &[ScanFailure]) {
+        for failure in failures {
+            match self
+                .failed_boards
+                .iter_mut()
+                .find(|board| board.port == failure.port)
+            {
+                Some(board) => {
+                    board.samples += 1;
+                    board.error = failure.error.clone();
+                }
+                None => self.failed_boards.push(FailedBoard {
+                    port: failure.port.clone(),
+                    samples: 1,
+                    error: failure.error.clone(),
+                }),
+            }
+        }
+    }
+
+    /// Whether more samples could still change the result.
+    pub fn is_decided(&self) -> bool {
+        let remaining = self.config.samples.saturating_sub(self.samples.len());
+        let required = self.config.required_votes();
+        if remaining == 0 {
+            return true;
+        }
+        // A module no sample has reported yet could still reach the quorum.
+        if remaining >= required {
+            return false;
+        }
+        self.entries
+            .iter()
+            .enumerate()
+            .all(|(index, (entry, votes))| {
+                let reachable = votes + remaining >= required;
+                if !reachable {
+                    return true;
+                }
+                // Reachable entries must already have won their id for good.
+                *votes >= required
+                    && self
+                        .rivals(index, entry)
+                        .all(|(_, rival)| rival + remaining < *votes)
+            })
+    }
+
+    /// The modules that won their vote, or `None` if there is no consensus:
+    /// no module got enough votes and too few samples reported an empty table.
+    pub fn modules(&self) -> Option<Vec<ModuleInfo>> {
+        let modules: Vec<ModuleInfo> = (0..self.entries.len())
+            .filter(|&index| self.is_accepted(index))
+            .map(|index| self.entries[index].0)
+            .collect();
+        let empty_samples = self
+            .samples
+            .iter()
+            .flatten()
+            .filter(|sample| sample.is_empty())
+            .count();
+        if modules.is_empty() && empty_samples < self.config.required_votes() {
+            return None;
+        }
+        Some(modules)
+    }
+
+    pub fn diagnostics(&self) -> ScanDiagnostics {
+        let result = self.modules();
+        let valid = self.samples.iter().flatten().count();
+        ScanDiagnostics {
+            samples: self.samples.len(),
+            invalid_samples: self.samples.len() - valid,
+            agreeing_samples: self
+                .samples
+                .iter()
+                .flatten()
+                .filter(|sample| result.as_ref() == Some(*sample))
+                .count(),
+            required_votes: self.config.required_votes(),
+            disputed: self
+                .entries
+                .iter()
+                .enumerate()
+                .filter(|(_, (_, votes))| *votes < valid)
+                .map(|(index, (module, votes))| ModuleVote {
+                    module: *module,
+                    votes: *votes,
+                    accepted: self.is_accepted(index),
+                })
+                .collect(),
+            failed_boards: self.failed_boards.clone(),
+        }
+    }
+
+    fn is_accepted(&self, index: usize) -> bool {
+        let (entry, votes) = &self.entries[index];
+        *votes >= self.config.required_votes()
+            && self.rivals(index, entry).all(|(rival_index, rival)| {
+                rival < *votes || (rival == *votes && rival_index > index)
+            })
+    }
+
+    /// Other entries for the same module, with their index and votes.
+    fn rivals<'a>(
+        &'a self,
+        index: usize,
+        entry: &'a ModuleInfo,
+    ) -> impl Iterator<Item = (usize, usize)> + 'a {
+        self.entries
+            .iter()
+            .enumerate()
+            .filter(move |(other, (candidate, _))| {
+                *other != index && candidate.eeprom == entry.eeprom
+            })
+            .map(|(other, (_, votes))| (other, *votes))
+    }
+}
+
+/// Scans `arduino` up to `config.samples` times and votes on the result.
+///
+/// Stops as soon as further samples cannot change the outcome. Firmware
+/// errors and communication failures end the scan with that error, unless
+/// other boards answered the sample; see [`Boards::scan`].
+pub async fn scan_consensus(arduino: &Boards, config: ConsensusConfig) -> Result<Tally, AppError> {
+    let mut tally = Tally::new(config);
+    for sample in 0..config.samples {
+        if sample > 0 {
+            tokio::time::sleep(config.interval).await;
+        }
+        let (response, failures) = arduino.scan().await?;
+        tally.add_failures(&failures);
+        match response {
+            Response::Scan(payload) => tally.add(Some(&payload)),
+            _ => tally.add(None),
+        }
+        if tally.is_decided() {
+            break;
+        }
+    }
+    Ok(tally)
+}
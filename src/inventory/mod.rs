@@ -0,0 +1,16 @@
+pub mod cache;
+pub mod consensus;
+pub mod module;
+pub mod poller;
+pub mod topology;
+
+#[cfg(test)]
+mod tests;
+
+pub use cache::{ScanCache, Snapshot};
+pub use consensus::{
+    scan_consensus, ConsensusConfig, FailedBoard, ModuleVote, ScanDiagnostics, Tally, Voting,
+};
+pub use module::{ModuleInfo, ModuleType};
+pub use poller::{poll_interval_from_env, poll_topology, rescan};
+pub use topology::{diff, ModuleChange};
@@ -0,0 +1,106 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::fmt;
+
+/// What a module on the table is, from the `TYPE` field of a scan entry.
+///
+/// Serializes as the firmware's numeric code, so scan entries keep their
+/// wire format.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(from = "u8", into = "u8")]
+pub enum ModuleType {
+    Solar,
+    Wind,
+    Battery,
+    Hydrogen,
+    ChargingStation,
+    Factory,
+    Household,
+    /// A code this server does not know, e.g. from newer firmware.
+    Unknown(u8),
+}
+
+impl ModuleType {
+    /// The firmware's `TYPE` code.
+    pub fn code(self) -> u8 {
+        match self {
+            ModuleType::Solar => 1,
+            ModuleType::Wind => 2,
+            ModuleType::Battery => 3,
+            ModuleType::Hydrogen => 4,
+            ModuleType::ChargingStation => 5,
+            ModuleType::Factory => 6,
+            ModuleType::Household => 7,
+            ModuleType::Unknown(code) => code,
+        }
+    }
+
+    /// Name used by the API, `"unknown"` for unknown codes.
+    pub fn name(self) -> &'static str {
+        match self {
+            ModuleType::Solar => "solar",
+            ModuleType::Wind => "wind",
+            ModuleType::Battery => "battery",
+            ModuleType::Hydrogen => "hydrogen",
+            ModuleType::ChargingStation => "charging_station",
+            ModuleType::Factory => "factory",
+            ModuleType::Household => "household",
+            ModuleType::Unknown(_) => "unknown",
+        }
+    }
+
+    pub fn is_known(self) -> bool {
+        !matches!(self, ModuleType::Unknown(_))
+    }
+}
+
+impl From<u8> for ModuleType {
+    fn from(code: u8) -> Self {
+        match code {
+            1 => ModuleType::Solar,
+            2 => ModuleType::Wind,
+            3 => ModuleType::Battery,
+            4 => ModuleType::Hydrogen,
+            5 => ModuleType::ChargingStation,
+            6 => ModuleType::Factory,
+            7 => ModuleType::Household,
+            code => ModuleType::Unknown(code),
+        }
+    }
+}
+
+impl From<ModuleType> for u8 {
+    fn from(module_type: ModuleType) -> Self {
+        module_type.code()
+    }
+}
+
+impl fmt::Display for ModuleType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ModuleType::Unknown(code) => write!(f, "unknown ({})", code),
+            known => f.write_str(known.name()),
+        }
+    }
+}
+
+/// One entry of a `SCAN()` result: `{"EEPROM":1,"I2C":32,"TYPE":1}`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct ModuleInfo {
+    /// Id stored in the module's EEPROM, unique on the table.
+    #[serde(rename = "EEPROM")]
+    pub eeprom: u32,
+    /// I2C address of the slot the module sits in.
+    #[serde(rename = "I2C")]
+    pub i2c: u8,
+    #[serde(rename = "TYPE")]
+    pub module_type: ModuleType,
+}
+
+impl ModuleInfo {
+    /// Parses a `SCAN:` payload, or `None` if it is not a list of well-formed
+    /// entries.
+    pub fn parse_scan(payload: &Value) -> Option<Vec<ModuleInfo>> {
+        Vec::<ModuleInfo>::deserialize(payload).ok()
+    }
+}
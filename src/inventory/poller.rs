@@ -0,0 +1,53 @@
+use super::{scan_consensus, Tally};
+use crate::config::SCAN_POLL_ENV_VAR;
+use crate::error::AppError;
+use crate::models::AppState;
+use std::time::Duration;
+
+/// Reads [`SCAN_POLL_ENV_VAR`]; `None` leaves the background scan off.
+pub fn poll_interval_from_env() -> Option<Duration> {
+    let value = std::env::var(SCAN_POLL_ENV_VAR).ok()?;
+    match value.parse::<u64>() {
+        Ok(0) => None,
+        Ok(millis) => Some(Duration::from_millis(millis)),
+        Err(_) => {
+            println!(
+                "[Error] {}={} is not a number of milliseconds; background scan off",
+                SCAN_POLL_ENV_VAR, value
+            );
+            None
+        }
+    }
+}
+
+/// Takes a consensus scan and, if it reached one, makes it the inventory in
+/// `state.scan_cache` and announces the changes on `state.live`.
+pub async fn rescan(state: &AppState) -> Result<Tally, AppError> {
+    let tally = scan_consensus(&state.arduino, state.scan_consensus).await?;
+    if let Some(modules) = tally.modules() {
+        let changes = state.scan_cache.update(modules, Some(tally.diagnostics()));
+        state.live.modules(&changes);
+    }
+    Ok(tally)
+}
+
+/// Background task that rescans the table every `interval` while a board is
+/// connected, keeping `state.scan_cache` current.
+///
+/// A tick is skipped while commands are waiting for a board, so polling
+/// never delays slider or heartbeat traffic.
+pub async fn poll_topology(state: AppState, interval: Duration) {
+    loop {
+        if state.arduino.is_connected() && !state.arduino.is_busy() {
+            match rescan(&state).await {
+                Ok(tally) if tally.modules().is_none() => println!(
+                    "[Topology] No consensus among {} samples, keeping the last inventory",
+                    tally.diagnostics().samples
+                ),
+                Ok(_) => {}
+                Err(error) => println!("[Topology] Scan failed: {}", error),
+            }
+        }
+        tokio::time::sleep(interval).await;
+    }
+}
@@ -0,0 +1,376 @@
+#[cfg(test)]
+mod consensus_tests {
+    use crate::inventory::{ConsensusConfig, ModuleInfo, ModuleType, Tally, Voting};
+    use crate::serial::ScanFailure;
+    use serde_json::{json, Value};
+    use std::time::Duration;
+
+    fn config(samples: usize, voting: Voting) -> ConsensusConfig {
+        ConsensusConfig {
+            samples,
+            voting,
+            interval: Duration::ZERO,
+        }
+    }
+
+    fn module(eeprom: u32, i2c: u8) -> ModuleInfo {
+        ModuleInfo {
+            eeprom,
+            i2c,
+            module_type: ModuleType::Solar,
+        }
+    }
+
+    fn tally(config: ConsensusConfig, samples: &[Value]) -> Tally {
+        let mut tally = Tally::new(config);
+        for sample in samples {
+            tally.add(Some(sample));
+        }
+        tally
+    }
+
+    #[test]
+    fn test_required_votes() {
+        assert_eq!(config(3, Voting::Majority).required_votes(), 2);
+        assert_eq!(config(4, Voting::Majority).required_votes(), 3);
+        assert_eq!(config(5, Voting::Majority).required_votes(), 3);
+        assert_eq!(config(1, Voting::Majority).required_votes(), 1);
+        assert_eq!(config(5, Voting::Quorum(4)).required_votes(), 4);
+        assert_eq!(config(3, Voting::Quorum(9)).required_votes(), 3);
+        assert_eq!(config(3, Voting::Quorum(0)).required_votes(), 1);
+    }
+
+    #[test]
+    fn test_two_agreeing_samples_decide_early() {
+        let sample = json!([module(1, 10), module(2, 11)]);
+        let mut tally = Tally::new(config(3, Voting::Majority));
+        tally.add(Some(&sample));
+        assert!(!tally.is_decided());
+        tally.add(Some(&sample));
+        assert!(tally.is_decided());
+        assert_eq!(tally.modules(), Some(vec![module(1, 10), module(2, 11)]));
+
+        let diagnostics = tally.diagnostics();
+        assert_eq!(diagnostics.samples, 2);
+        assert_eq!(diagnostics.agreeing_samples, 2);
+        assert!(diagnostics.disputed.is_empty());
+    }
+
+    #[test]
+    fn test_flaky_module_only_drops_itself() {
+        // No two samples are identical, but modules 1 and 2 are in all of them.
+        let tally = tally(
+            config(3, Voting::Majority),
+            &[
+                json!([module(1, 10), module(2, 11), module(3, 12)]),
+                json!([module(1, 10), module(2, 11)]),
+                json!([module(1, 10), module(2, 11), module(4, 13)]),
+            ],
+        );
+        assert!(tally.is_decided());
+        assert_eq!(tally.modules(), Some(vec![module(1, 10), module(2, 11)]));
+
+        let diagnostics = tally.diagnostics();
+        assert_eq!(diagnostics.agreeing_samples, 1);
+        assert_eq!(diagnostics.disputed.len(), 2);
+        assert!(diagnostics.disputed.iter().all(|vote| vote.votes == 1));
+        assert!(diagnostics.disputed.iter().all(|vote| !vote.accepted));
+    }
+
+    #[test]
+    fn test_disputed_slot_resolves_by_majority() {
+        let tally = tally(
+            config(3, Voting::Majority),
+            &[
+                json!([module(1, 10)]),
+                json!([module(1, 20)]),
+                json!([module(1, 20)]),
+            ],
+        );
+        assert_eq!(tally.modules(), Some(vec![module(1, 20)]));
+
+        let disputed = tally.diagnostics().disputed;
+        assert_eq!(disputed.len(), 2);
+        assert_eq!((disputed[0].votes, disputed[0].accepted), (1, false));
+        assert_eq!((disputed[1].votes, disputed[1].accepted), (2, true));
+    }
+
+    #[test]
+    fn test_undecided_slot_keeps_sampling() {
+        let mut tally = Tally::new(config(5, Voting::Majority));
+        tally.add(Some(&json!([module(1, 10)])));
+        tally.add(Some(&json!([module(1, 20)])));
+        tally.add(Some(&json!([module(1, 10)])));
+        tally.add(Some(&json!([module(1, 20)])));
+        assert!(!tally.is_decided());
+        tally.add(Some(&json!([module(1, 20)])));
+        assert!(tally.is_decided());
+        assert_eq!(tally.modules(), Some(vec![module(1, 20)]));
+    }
+
+    #[test]
+    fn test_disjoint_samples_have_no_consensus() {
+        let tally = tally(
+            config(3, Voting::Majority),
+            &[
+                json!([module(1, 10)]),
+                json!([module(2, 11)]),
+                json!([module(3, 12)]),
+            ],
+        );
+        assert_eq!(tally.modules(), None);
+        assert_eq!(tally.diagnostics().agreeing_samples, 0);
+    }
+
+    #[test]
+    fn test_empty_table_is_a_consensus() {
+        let tally = tally(config(3, Voting::Majority), &[json!([]), json!([])]);
+        assert!(tally.is_decided());
+        assert_eq!(tally.modules(), Some(Vec::new()));
+        assert_eq!(tally.diagnostics().agreeing_samples, 2);
+    }
+
+    #[test]
+    fn test_invalid_samples_do_not_vote() {
+        let mut tally = Tally::new(config(3, Voting::Majority));
+        tally.add(None);
+        assert!(!tally.is_decided());
+        tally.add(Some(&json!({"error": "bus"})));
+        // One sample left can no longer give anything a majority.
+        assert!(tally.is_decided());
+        assert_eq!(tally.modules(), None);
+
+        let diagnostics = tally.diagnostics();
+        assert_eq!(diagnostics.samples, 2);
+        assert_eq!(diagnostics.invalid_samples, 2);
+    }
+
+    #[test]
+    fn test_failed_boards_are_reported() {
+        let sample = json!([module(1, 10)]);
+        let failure = |error: &str| ScanFailure {
+            port: "right".to_string(),
+            error: error.to_string(),
+        };
+        let mut tally = Tally::new(config(3, Voting::Majority));
+        tally.add_failures(&[failure("No response")]);
+        tally.add(Some(&sample));
+        tally.add_failures(&[failure("busy")]);
+        tally.add(Some(&sample));
+        assert_eq!(tally.modules(), Some(vec![module(1, 10)]));
+
+        let diagnostics = tally.diagnostics();
+        assert_eq!(diagnostics.failed_boards.len(), 1);
+        assert_eq!(diagnostics.failed_boards[0].samples, 2);
+        assert_eq!(diagnostics.failed_boards[0].error, "busy");
+        assert!(
+            serde_json::to_value(Tally::new(config(3, Voting::Majority)).diagnostics())
+                .unwrap()
+                .get("failed_boards")
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn test_quorum_voting() {
+        let samples = [
+            json!([module(1, 10), module(2, 11)]),
+            json!([module(1, 10), module(2, 11)]),
+            json!([module(1, 10)]),
+            json!([module(1, 10), module(2, 11)]),
+        ];
+        let strict = tally(config(4, Voting::Quorum(4)), &samples);
+        assert_eq!(strict.modules(), Some(vec![module(1, 10)]));
+
+        // Any later sample could still add a module, so all are taken.
+        let mut lenient = tally(config(4, Voting::Quorum(1)), &samples[..3]);
+        assert!(!lenient.is_decided());
+        lenient.add(Some(&samples[3]));
+        assert!(lenient.is_decided());
+        assert_eq!(lenient.modules(), Some(vec![module(1, 10), module(2, 11)]));
+    }
+}
+
+#[cfg(test)]
+mod module_tests {
+    use crate::inventory::{ModuleInfo, ModuleType};
+    use serde_json::json;
+
+    #[test]
+    fn test_module_type_codes() {
+        for code in 1..=7 {
+            let module_type = ModuleType::from(code);
+            assert!(module_type.is_known());
+            assert_eq!(module_type.code(), code);
+        }
+        assert_eq!(ModuleType::from(4), ModuleType::Hydrogen);
+        assert_eq!(ModuleType::from(5).name(), "charging_station");
+        assert_eq!(ModuleType::from(0), ModuleType::Unknown(0));
+        assert_eq!(ModuleType::from(9).name(), "unknown");
+        assert_eq!(ModuleType::Unknown(9).to_string(), "unknown (9)");
+        assert_eq!(ModuleType::Factory.to_string(), "factory");
+    }
+
+    #[test]
+    fn test_parse_scan() {
+        let modules =
+            ModuleInfo::parse_scan(&json!([{"EEPROM": 12, "I2C": 43, "TYPE": 6}])).unwrap();
+        assert_eq!(
+            modules,
+            vec![ModuleInfo {
+                eeprom: 12,
+                i2c: 43,
+                module_type: ModuleType::Factory,
+            }]
+        );
+        assert_eq!(
+            ModuleInfo::parse_scan(&json!([{"EEPROM": 1, "I2C": 32, "TYPE": 42}])).unwrap()[0]
+                .module_type,
+            ModuleType::Unknown(42)
+        );
+        assert_eq!(ModuleInfo::parse_scan(&json!([])), Some(Vec::new()));
+    }
+
+    #[test]
+    fn test_parse_scan_rejects_malformed_entries() {
+        assert_eq!(ModuleInfo::parse_scan(&json!({"EEPROM": 1})), None);
+        assert_eq!(
+            ModuleInfo::parse_scan(&json!([{"EEPROM": 1, "I2C": 32}])),
+            None
+        );
+        assert_eq!(
+            ModuleInfo::parse_scan(&json!([{"EEPROM": 1, "I2C": 300, "TYPE": 1}])),
+            None
+        );
+        assert_eq!(
+            ModuleInfo::parse_scan(&json!([{"EEPROM": "1", "I2C": 32, "TYPE": 1}])),
+            None
+        );
+    }
+
+    #[test]
+    fn test_module_keeps_wire_format() {
+        let module = ModuleInfo {
+            eeprom: 3,
+            i2c: 34,
+            module_type: ModuleType::Unknown(11),
+        };
+        assert_eq!(
+            serde_json::to_value(module).unwrap(),
+            json!({"EEPROM": 3, "I2C": 34, "TYPE": 11})
+        );
+    }
+}
+
+#[cfg(test)]
+mod topology_tests {
+    use crate::inventory::{diff, ModuleChange, ModuleInfo, ModuleType, ScanCache};
+
+    fn module(eeprom: u32, i2c: u8, module_type: ModuleType) -> ModuleInfo {
+        ModuleInfo {
+            eeprom,
+            i2c,
+            module_type,
+        }
+    }
+
+    #[test]
+    fn test_diff_unchanged() {
+        let modules = [
+            module(1, 32, ModuleType::Solar),
+            module(2, 33, ModuleType::Wind),
+        ];
+        assert!(diff(&modules, &modules).is_empty());
+        // Order does not matter.
+        assert!(diff(&modules, &[modules[1], modules[0]]).is_empty());
+    }
+
+    #[test]
+    fn test_diff_added_and_removed() {
+        let solar = module(1, 32, ModuleType::Solar);
+        let wind = module(2, 33, ModuleType::Wind);
+        let battery = module(3, 34, ModuleType::Battery);
+        assert_eq!(
+            diff(&[solar, wind], &[solar, battery]),
+            vec![
+                ModuleChange::Removed { module: wind },
+                ModuleChange::Added { module: battery },
+            ]
+        );
+        assert_eq!(
+            diff(&[], &[solar]),
+            vec![ModuleChange::Added { module: solar }]
+        );
+    }
+
+    #[test]
+    fn test_diff_moved_and_type_changed() {
+        let before = module(5, 36, ModuleType::Factory);
+        let moved = module(5, 40, ModuleType::Factory);
+        assert_eq!(
+            diff(&[before], &[moved]),
+            vec![ModuleChange::Moved {
+                module: moved,
+                from_i2c: 36,
+            }]
+        );
+
+        let reprogrammed = module(5, 40, ModuleType::Unknown(12));
+        assert_eq!(
+            diff(&[before], &[reprogrammed]),
+            vec![
+                ModuleChange::Moved {
+                    module: reprogrammed,
+                    from_i2c: 36,
+                },
+                ModuleChange::TypeChanged {
+                    module: reprogrammed,
+                    from: ModuleType::Factory,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_change_serialization() {
+        let change = ModuleChange::Moved {
+            module: module(5, 40, ModuleType::Factory),
+            from_i2c: 36,
+        };
+        assert_eq!(
+            serde_json::to_value(change).unwrap(),
+            serde_json::json!({
+                "change": "moved",
+                "module": {"EEPROM": 5, "I2C": 40, "TYPE": 6},
+                "from_i2c": 36,
+            })
+        );
+        assert_eq!(
+            change.to_string(),
+            "factory module 5 moved from I2C 36 to 40"
+        );
+    }
+
+    #[test]
+    fn test_scan_cache_update() {
+        let cache = ScanCache::default();
+        assert!(cache.get().is_none());
+
+        let solar = module(1, 32, ModuleType::Solar);
+        assert_eq!(
+            cache.update(vec![solar], None),
+            vec![ModuleChange::Added { module: solar }]
+        );
+        let first = cache.get().unwrap();
+        assert_eq!(first.modules, vec![solar]);
+        assert!(first.scanned_at_ms > 0);
+
+        assert!(cache.update(vec![solar], None).is_empty());
+        assert!(cache.get().unwrap().scanned_at_ms >= first.scanned_at_ms);
+        assert_eq!(
+            cache.update(Vec::new(), None),
+            vec![ModuleChange::Removed { module: solar }]
+        );
+        assert_eq!(cache.get().unwrap().modules, Vec::new());
+    }
+}
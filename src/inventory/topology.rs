@@ -0,0 +1,92 @@
+use super::{ModuleInfo, ModuleType};
+use serde::Serialize;
+use std::fmt;
+
+/// How the table changed between two scans. Modules are matched by their
+/// `EEPROM` id.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(tag = "change", rename_all = "snake_case")]
+pub enum ModuleChange {
+    Added {
+        module: ModuleInfo,
+    },
+    Removed {
+        module: ModuleInfo,
+    },
+    /// The module sits in another I2C slot now.
+    Moved {
+        module: ModuleInfo,
+        from_i2c: u8,
+    },
+    /// The module reports another `TYPE`, e.g. after being reprogrammed.
+    TypeChanged {
+        module: ModuleInfo,
+        from: ModuleType,
+    },
+}
+
+impl fmt::Display for ModuleChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ModuleChange::Added { module } => write!(
+                f,
+                "{} module {} added at I2C {}",
+                module.module_type, module.eeprom, module.i2c
+            ),
+            ModuleChange::Removed { module } => write!(
+                f,
+                "{} module {} removed from I2C {}",
+                module.module_type, module.eeprom, module.i2c
+            ),
+            ModuleChange::Moved { module, from_i2c } => write!(
+                f,
+                "{} module {} moved from I2C {} to {}",
+                module.module_type, module.eeprom, from_i2c, module.i2c
+            ),
+            ModuleChange::TypeChanged { module, from } => write!(
+                f,
+                "module {} changed type from {} to {}",
+                module.eeprom, from, module.module_type
+            ),
+        }
+    }
+}
+
+/// Changes from `old` to `new`: removals first, then moves and type changes
+/// in the order of `new`, then additions.
+pub fn diff(old: &[ModuleInfo], new: &[ModuleInfo]) -> Vec<ModuleChange> {
+    let find = |modules: &[ModuleInfo], eeprom: u32| {
+        modules
+            .iter()
+            .find(|module| module.eeprom == eeprom)
+            .copied()
+    };
+
+    let mut changes: Vec<ModuleChange> = old
+        .iter()
+        .filter(|module| find(new, module.eeprom).is_none())
+        .map(|module| ModuleChange::Removed { module: *module })
+        .collect();
+    let mut added = Vec::new();
+    for module in new {
+        match find(old, module.eeprom) {
+            None => added.push(ModuleChange::Added { module: *module }),
+            Some(before) => {
+                if before.i2c != module.i2c {
+                    changes.push(ModuleChange::Moved {
+                        module: *module,
+                        from_i2c: before.i2c,
+                    });
+                }
+                if before.module_type != module.module_type {
+                    changes.push(ModuleChange::TypeChanged {
+                        module: *module,
+                        from: before.module_type,
+                    });
+                }
+            }
+        }
+    }
+    changes.extend(added);
+    changes
+}
@@ -0,0 +1,229 @@
+use crate::config::{CALIBRATION_ENV_VAR, CALIBRATION_FILE};
+use crate::error::AppError;
+use crate::layout::Layout;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+
+/// How a physical table differs from [`Layout::table`].
+///
+/// Stored as JSON, with slots keyed by I2C address and lines by their
+/// frontend key:
+///
+/// ```json
+/// {
+///   "slots": { "40": 1, "33": 2 },
+///   "lines": { "line_2_0_1_-1": 12 }
+/// }
+/// ```
+///
+/// `slots` gives the label of the slot a module answering at an I2C address
+/// sits in, `lines` the LED id of a line. Anything not listed keeps its
+/// default. Modules are placed by I2C address alone, so with several boards
+/// the addresses must differ across boards too.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Calibration {
+    #[serde(default)]
+    pub slots: BTreeMap<u8, u8>,
+    #[serde(default)]
+    pub lines: BTreeMap<String, u8>,
+}
+
+impl Calibration {
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, String> {
+        let path = path.as_ref();
+        let content = std::fs::read_to_string(path)
+            .map_err(|e| format!("Cannot read calibration {}: {}", path.display(), e))?;
+        serde_json::from_str(&content)
+            .map_err(|e| format!("Invalid calibration {}: {}", path.display(), e))
+    }
+
+    /// Writes the file next to `path` first and renames it, so a crash never
+    /// leaves half a calibration behind.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), AppError> {
+        let path = path.as_ref();
+        let storage = |e: std::io::Error| {
+            AppError::Storage(format!("Cannot save calibration {}: {}", path.display(), e))
+        };
+        let mut content = serde_json::to_string_pretty(self).unwrap_or_default();
+        content.push('\n');
+        let temporary = path.with_extension("json.tmp");
+        std::fs::write(&temporary, content).map_err(storage)?;
+        std::fs::rename(&temporary, path).map_err(storage)
+    }
+
+    /// `base` with this calibration applied.
+    ///
+    /// Fails if it names a slot or line `base` does not have, puts two I2C
+    /// addresses in one slot, or leaves two slots at one I2C address or two
+    /// lines with one LED id.
+    pub fn apply(&self, base: &Layout) -> Result<Layout, String> {
+        let mut slots = base.slots().to_vec();
+        let mut labels = HashMap::new();
+        for (&i2c, &label) in &self.slots {
+            if let Some(other) = labels.insert(label, i2c) {
+                return Err(format!("I2C {} and {} are both slot {}", other, i2c, label));
+            }
+            let slot = slots
+                .iter_mut()
+                .find(|slot| slot.label == label)
+                .ok_or_else(|| format!("Unknown slot {}", label))?;
+            slot.i2c = i2c;
+        }
+        let mut lines = base.lines().to_vec();
+        for (key, &id) in &self.lines {
+            let line = lines
+                .iter_mut()
+                .find(|line| &line.key == key)
+                .ok_or_else(|| format!("Unknown line {}", key))?;
+            line.id = id;
+        }
+
+        let mut addresses = HashMap::new();
+        for slot in &slots {
+            if let Some(other) = addresses.insert(slot.i2c, slot.label) {
+                return Err(format!(
+                    "Slots {} and {} are both at I2C {}",
+                    other, slot.label, slot.i2c
+                ));
+            }
+        }
+        let mut ids = HashMap::new();
+        for line in &lines {
+            if let Some(other) = ids.insert(line.id, &line.key) {
+                return Err(format!(
+                    "Lines {} and {} both have LED {}",
+                    other, line.key, line.id
+                ));
+            }
+        }
+        lines.sort_by_key(|line| line.id);
+        Ok(Layout::new(slots, lines))
+    }
+}
+
+struct Calibrated {
+    calibration: Calibration,
+    layout: Arc<Layout>,
+}
+
+/// The calibrated layout of the table, shared by the handlers.
+///
+/// Changes are saved to the calibration file, if there is one, before they
+/// take effect.
+#[derive(Clone)]
+pub struct LayoutStore {
+    path: Option<PathBuf>,
+    inner: Arc<RwLock<Calibrated>>,
+    /// Held for the whole of an edit, so concurrent edits do not overwrite
+    /// each other; readers only wait for the final swap.
+    edits: Arc<tokio::sync::Mutex<()>>,
+}
+
+impl Default for LayoutStore {
+    /// The uncalibrated table, kept in memory only.
+    fn default() -> Self {
+        Self {
+            path: None,
+            inner: Arc::new(RwLock::new(Calibrated {
+                calibration: Calibration::default(),
+                layout: Arc::new(Layout::table()),
+            })),
+            edits: Arc::default(),
+        }
+    }
+}
+
+impl LayoutStore {
+    /// Loads the file named by [`CALIBRATION_ENV_VAR`], or [`CALIBRATION_FILE`].
+    ///
+    /// A missing or invalid file leaves the table uncalibrated; the next edit
+    /// creates or replaces it.
+    pub fn from_env() -> Self {
+        let path = std::env::var(CALIBRATION_ENV_VAR).unwrap_or_else(|_| CALIBRATION_FILE.into());
+        let store = Self {
+            path: Some(PathBuf::from(&path)),
+            ..Self::default()
+        };
+        if !Path::new(&path).exists() {
+            return store;
+        }
+        let loaded = Calibration::load(&path).and_then(|calibration| {
+            let layout = calibration.apply(&Layout::table())?;
+            Ok((calibration, layout))
+        });
+        match loaded {
+            Ok((calibration, layout)) => {
+                println!(
+                    "Loaded calibration of {} slots and {} lines from {}",
+                    calibration.slots.len(),
+                    calibration.lines.len(),
+                    path
+                );
+                *store.inner.write().unwrap() = Calibrated {
+                    calibration,
+                    layout: Arc::new(layout),
+                };
+            }
+            Err(e) => println!("[Error] {}; using the default table layout", e),
+        }
+        store
+    }
+
+    /// A store saving to `path`, starting out uncalibrated.
+    pub fn with_path(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: Some(path.into()),
+            ..Self::default()
+        }
+    }
+
+    /// Where changes are saved, `None` if they are kept in memory only.
+    pub fn path(&self) -> Option<&Path> {
+        self.path.as_deref()
+    }
+
+    pub fn layout(&self) -> Arc<Layout> {
+        self.inner.read().unwrap().layout.clone()
+    }
+
+    pub fn calibration(&self) -> Calibration {
+        self.inner.read().unwrap().calibration.clone()
+    }
+
+    /// Replaces the calibration, saving it first.
+    pub async fn calibrate(&self, calibration: Calibration) -> Result<Arc<Layout>, AppError> {
+        self.edit(|current| *current = calibration).await
+    }
+
+    /// Changes the current calibration in place, saving the result first.
+    /// Nothing changes if the result is invalid or cannot be saved.
+    ///
+    /// The file is written on the blocking pool; handlers keep reading the
+    /// old layout until it is saved.
+    pub async fn edit(
+        &self,
+        change: impl FnOnce(&mut Calibration),
+    ) -> Result<Arc<Layout>, AppError> {
+        let _edit = self.edits.lock().await;
+        let mut calibration = self.calibration();
+        change(&mut calibration);
+        let layout = calibration
+            .apply(&Layout::table())
+            .map_err(AppError::InvalidRequest)?;
+        if let Some(path) = self.path.clone() {
+            let saved = calibration.clone();
+            tokio::task::spawn_blocking(move || saved.save(path))
+                .await
+                .map_err(|e| AppError::Storage(format!("Cannot save calibration: {}", e)))??;
+        }
+        let layout = Arc::new(layout);
+        *self.inner.write().unwrap() = Calibrated {
+            calibration,
+            layout: layout.clone(),
+        };
+        Ok(layout)
+    }
+}
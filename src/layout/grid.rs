@@ -0,0 +1,64 @@
+use serde::Serialize;
+
+/// Position of a hexagon on the table in axial coordinates.
+///
+/// The hexagons are flat-topped: `q` runs left to right, `r` down along the
+/// columns. The frontend draws them on an offset grid (`row`, `col`) whose
+/// odd columns are shifted up by half a hexagon; see [`HexCoord::from_offset`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize)]
+pub struct HexCoord {
+    pub q: i32,
+    pub r: i32,
+}
+
+/// Axial steps to the six neighbours of a hexagon, clockwise from the one
+/// below-right.
+pub const DIRECTIONS: [HexCoord; 6] = [
+    HexCoord { q: 1, r: 0 },
+    HexCoord { q: 0, r: 1 },
+    HexCoord { q: -1, r: 1 },
+    HexCoord { q: -1, r: 0 },
+    HexCoord { q: 0, r: -1 },
+    HexCoord { q: 1, r: -1 },
+];
+
+impl HexCoord {
+    pub fn new(q: i32, r: i32) -> Self {
+        Self { q, r }
+    }
+
+    /// Converts the frontend's `row`/`col` grid position.
+    pub fn from_offset(row: i32, col: i32) -> Self {
+        Self {
+            q: col,
+            r: row - (col + (col & 1)) / 2,
+        }
+    }
+
+    /// The frontend's `(row, col)` for this position.
+    pub fn to_offset(self) -> (i32, i32) {
+        (self.r + (self.q + (self.q & 1)) / 2, self.q)
+    }
+
+    pub fn neighbours(self) -> [HexCoord; 6] {
+        DIRECTIONS.map(|step| HexCoord::new(self.q + step.q, self.r + step.r))
+    }
+
+    pub fn is_adjacent(self, other: HexCoord) -> bool {
+        self.distance(other) == 1
+    }
+
+    /// Number of steps between two hexagons.
+    pub fn distance(self, other: HexCoord) -> u32 {
+        let dq = self.q - other.q;
+        let dr = self.r - other.r;
+        ((dq.abs() + dr.abs() + (dq + dr).abs()) / 2) as u32
+    }
+}
+
+/// One square of the household area, on the frontend's square grid.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+pub struct SquareCell {
+    pub row: i32,
+    pub col: i32,
+}
@@ -0,0 +1,10 @@
+pub mod calibration;
+pub mod grid;
+pub mod table;
+
+#[cfg(test)]
+mod tests;
+
+pub use calibration::{Calibration, LayoutStore};
+pub use grid::{HexCoord, SquareCell, DIRECTIONS};
+pub use table::{Layout, LedLine, Slot, SlotShape};
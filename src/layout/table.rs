@@ -0,0 +1,248 @@
+use crate::config::I2C_SLOT_BASE;
+use crate::layout::grid::{HexCoord, SquareCell};
+use serde::Serialize;
+
+/// `(row, col)` on one of the frontend's grids.
+type Offset = (i32, i32);
+
+/// Hexagon slots as `(label, row, col)` on the frontend's offset grid.
+const HEXAGONS: [(u8, i32, i32); 15] = [
+    (1, 2, 0),
+    (2, 2, 2),
+    (3, 2, 4),
+    (4, 2, 1),
+    (5, 2, 3),
+    (6, 1, 0),
+    (7, 1, 2),
+    (8, 1, 4),
+    (9, 1, 1),
+    (10, 1, 3),
+    (11, 0, 0),
+    (12, 0, 2),
+    (13, 0, 4),
+    (14, 0, 1),
+    (15, 0, 3),
+];
+
+/// Size of the square grid the household area is cut from.
+const SQUARE_ROWS: i32 = 9;
+const SQUARE_COLS: i32 = 4;
+
+/// Squares of that grid that are not part of the household area.
+const MISSING_SQUARES: [Offset; 16] = [
+    (0, 1),
+    (2, 1),
+    (8, 1),
+    (2, 2),
+    (3, 2),
+    (5, 2),
+    (6, 2),
+    (7, 2),
+    (8, 2),
+    (0, 3),
+    (3, 3),
+    (4, 3),
+    (5, 3),
+    (6, 3),
+    (7, 3),
+    (8, 3),
+];
+
+/// LED lines as `(id, key, from, to)`, both ends as `(row, col)`. The key is
+/// the frontend's id for the line. Lines 0, 26, 27 and 29 run from a hexagon
+/// off the edge of the grid; the frontend names the far end of 0 and 26 one
+/// row off, as it steps rows without the column shift.
+const LINES: [(u8, &str, Offset, Offset); 30] = [
+    (0, "line_2_0_1_-1", (2, 0), (2, -1)),
+    (1, "line_2_1_2_0", (2, 1), (2, 0)),
+    (2, "line_2_2_2_1", (2, 2), (2, 1)),
+    (3, "line_2_2_1_2", (2, 2), (1, 2)),
+    (4, "line_2_3_1_2", (2, 3), (1, 2)),
+    (5, "line_2_4_2_3", (2, 4), (2, 3)),
+    (6, "line_2_4_1_4", (2, 4), (1, 4)),
+    (7, "line_2_3_1_4", (2, 3), (1, 4)),
+    (8, "line_2_3_1_3", (2, 3), (1, 3)),
+    (9, "line_1_4_1_3", (1, 4), (1, 3)),
+    (10, "line_1_4_0_4", (1, 4), (0, 4)),
+    (11, "line_1_3_0_4", (1, 3), (0, 4)),
+    (12, "line_1_3_0_3", (1, 3), (0, 3)),
+    (13, "line_0_3_0_2", (0, 3), (0, 2)),
+    (14, "line_1_3_0_2", (1, 3), (0, 2)),
+    (15, "line_1_3_1_2", (1, 3), (1, 2)),
+    (16, "line_1_2_0_2", (1, 2), (0, 2)),
+    (17, "line_1_1_0_2", (1, 1), (0, 2)),
+    (18, "line_1_2_1_1", (1, 2), (1, 1)),
+    (19, "line_2_1_1_2", (2, 1), (1, 2)),
+    (20, "line_2_1_1_1", (2, 1), (1, 1)),
+    (21, "line_1_1_1_0", (1, 1), (1, 0)),
+    (22, "line_1_0_0_0", (1, 0), (0, 0)),
+    (23, "line_1_1_0_0", (1, 1), (0, 0)),
+    (24, "line_1_1_0_1", (1, 1), (0, 1)),
+    (25, "line_0_1_0_0", (0, 1), (0, 0)),
+    (26, "line_0_0_-1_-1", (0, 0), (0, -1)),
+    (27, "line_0_1_-1_0", (0, 1), (-1, 0)),
+    (28, "line_0_2_0_1", (0, 2), (0, 1)),
+    (29, "line_0_2_-1_2", (0, 2), (-1, 2)),
+];
+
+/// Where a slot sits on the table.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "shape", rename_all = "snake_case")]
+pub enum SlotShape {
+    Hexagon {
+        hex: HexCoord,
+    },
+    /// The household area, drawn as a block of squares.
+    Square {
+        cells: Vec<SquareCell>,
+    },
+}
+
+/// A place on the table a module can be plugged into.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Slot {
+    /// Number printed on the table, as shown by the frontend.
+    pub label: u8,
+    /// I2C address a module in this slot answers `SCAN()` with.
+    pub i2c: u8,
+    #[serde(flatten)]
+    pub shape: SlotShape,
+}
+
+impl Slot {
+    pub fn hex(&self) -> Option<HexCoord> {
+        match self.shape {
+            SlotShape::Hexagon { hex } => Some(hex),
+            SlotShape::Square { .. } => None,
+        }
+    }
+}
+
+/// An LED strip between two hexagons, or from a hexagon to the edge.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct LedLine {
+    /// The `led_id` of `POST /api/led`.
+    pub id: u8,
+    /// The frontend's id for the line, e.g. `line_0_2_0_1`.
+    pub key: String,
+    pub from: HexCoord,
+    pub to: HexCoord,
+}
+
+/// The slots of the table and the LED lines between them.
+///
+/// Two slots are neighbours if an LED line connects them; a few hexagons
+/// that touch have no line between them and are not.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Layout {
+    slots: Vec<Slot>,
+    lines: Vec<LedLine>,
+}
+
+impl Default for Layout {
+    fn default() -> Self {
+        Self::table()
+    }
+}
+
+impl Layout {
+    pub fn new(slots: Vec<Slot>, lines: Vec<LedLine>) -> Self {
+        Self { slots, lines }
+    }
+
+    /// The table as the frontend draws it: the household area (slot 0) and
+    /// hexagons 1 to 15, each at I2C address [`I2C_SLOT_BASE`] plus its label.
+    pub fn table() -> Self {
+        let cells = (0..SQUARE_ROWS)
+            .flat_map(|row| (0..SQUARE_COLS).map(move |col| (row, col)))
+            .filter(|cell| !MISSING_SQUARES.contains(cell))
+            .map(|(row, col)| SquareCell { row, col })
+            .collect();
+        let household = Slot {
+            label: 0,
+            i2c: I2C_SLOT_BASE,
+            shape: SlotShape::Square { cells },
+        };
+        let hexagons = HEXAGONS.iter().map(|&(label, row, col)| Slot {
+            label,
+            i2c: I2C_SLOT_BASE + label,
+            shape: SlotShape::Hexagon {
+                hex: HexCoord::from_offset(row, col),
+            },
+        });
+        let lines = LINES
+            .iter()
+            .map(
+                |&(id, key, (from_row, from_col), (to_row, to_col))| LedLine {
+                    id,
+                    key: key.to_string(),
+                    from: HexCoord::from_offset(from_row, from_col),
+                    to: HexCoord::from_offset(to_row, to_col),
+                },
+            )
+            .collect();
+        Self::new(std::iter::once(household).chain(hexagons).collect(), lines)
+    }
+
+    pub fn slots(&self) -> &[Slot] {
+        &self.slots
+    }
+
+    pub fn lines(&self) -> &[LedLine] {
+        &self.lines
+    }
+
+    pub fn slot(&self, label: u8) -> Option<&Slot> {
+        self.slots.iter().find(|slot| slot.label == label)
+    }
+
+    /// The slot a module reported at `i2c` sits in.
+    pub fn slot_at_i2c(&self, i2c: u8) -> Option<&Slot> {
+        self.slots.iter().find(|slot| slot.i2c == i2c)
+    }
+
+    pub fn slot_at(&self, hex: HexCoord) -> Option<&Slot> {
+        self.slots.iter().find(|slot| slot.hex() == Some(hex))
+    }
+
+    pub fn line(&self, id: u8) -> Option<&LedLine> {
+        self.lines.iter().find(|line| line.id == id)
+    }
+
+    /// Labels of the slots at both ends of `line`, `None` off the grid.
+    pub fn line_ends(&self, line: &LedLine) -> [Option<u8>; 2] {
+        [line.from, line.to].map(|hex| self.slot_at(hex).map(|slot| slot.label))
+    }
+
+    /// Lines with an end in the slot labelled `label`.
+    pub fn lines_of(&self, label: u8) -> Vec<&LedLine> {
+        self.lines
+            .iter()
+            .filter(|line| self.line_ends(line).contains(&Some(label)))
+            .collect()
+    }
+
+    /// The line connecting two slots, if there is one.
+    pub fn line_between(&self, a: u8, b: u8) -> Option<&LedLine> {
+        self.lines.iter().find(|line| {
+            let ends = self.line_ends(line);
+            ends == [Some(a), Some(b)] || ends == [Some(b), Some(a)]
+        })
+    }
+
+    /// Labels of the slots connected to `label` by a line, ascending.
+    pub fn neighbours(&self, label: u8) -> Vec<u8> {
+        let mut neighbours: Vec<u8> = self
+            .lines_of(label)
+            .into_iter()
+            .filter_map(|line| match self.line_ends(line) {
+                [Some(a), Some(b)] if a == label => Some(b),
+                [Some(a), Some(_)] => Some(a),
+                _ => None,
+            })
+            .collect();
+        neighbours.sort_unstable();
+        neighbours.dedup();
+        neighbours
+    }
+}
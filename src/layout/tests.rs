@@ -0,0 +1,285 @@
+#[cfg(test)]
+mod grid_tests {
+    use crate::layout::HexCoord;
+
+    #[test]
+    fn test_offset_round_trip() {
+        for row in -2..4 {
+            for col in -2..6 {
+                let hex = HexCoord::from_offset(row, col);
+                assert_eq!(hex.to_offset(), (row, col));
+            }
+        }
+    }
+
+    #[test]
+    fn test_odd_columns_shift_up() {
+        // Column 1 is drawn half a hexagon above column 0, so (0, 1) touches
+        // (-1, 0) and (0, 0) of it but not (1, 0).
+        let hex = HexCoord::from_offset(0, 1);
+        assert!(hex.is_adjacent(HexCoord::from_offset(0, 0)));
+        assert!(hex.is_adjacent(HexCoord::from_offset(-1, 0)));
+        assert!(!hex.is_adjacent(HexCoord::from_offset(1, 0)));
+        assert_eq!(hex.distance(HexCoord::from_offset(2, 0)), 3);
+    }
+
+    #[test]
+    fn test_neighbours_are_one_step_away() {
+        let hex = HexCoord::new(2, -1);
+        let neighbours = hex.neighbours();
+        assert!(neighbours.iter().all(|n| hex.distance(*n) == 1));
+        assert!(!neighbours.contains(&hex));
+    }
+}
+
+#[cfg(test)]
+mod table_tests {
+    use crate::layout::{HexCoord, Layout, SlotShape};
+
+    #[test]
+    fn test_slots_follow_i2c_addresses() {
+        let layout = Layout::table();
+        assert_eq!(layout.slots().len(), 16);
+        assert_eq!(layout.slot_at_i2c(31).unwrap().label, 0);
+        assert_eq!(layout.slot_at_i2c(32).unwrap().label, 1);
+        assert_eq!(layout.slot_at_i2c(46).unwrap().label, 15);
+        assert!(layout.slot_at_i2c(47).is_none());
+
+        assert_eq!(
+            layout.slot(1).unwrap().hex(),
+            Some(HexCoord::from_offset(2, 0))
+        );
+        match &layout.slot(0).unwrap().shape {
+            SlotShape::Square { cells } => assert_eq!(cells.len(), 20),
+            other => panic!("household is not square: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_lines_match_frontend_ids() {
+        let layout = Layout::table();
+        let ids: Vec<u8> = layout.lines().iter().map(|line| line.id).collect();
+        assert_eq!(ids, (0..30).collect::<Vec<u8>>());
+        assert_eq!(layout.line(28).unwrap().key, "line_0_2_0_1");
+        assert_eq!(layout.line(0).unwrap().key, "line_2_0_1_-1");
+        assert_eq!(layout.line(26).unwrap().key, "line_0_0_-1_-1");
+        assert_eq!(layout.line(0).unwrap().to, HexCoord::from_offset(2, -1));
+    }
+
+    #[test]
+    fn test_lines_connect_touching_hexagons() {
+        let layout = Layout::table();
+        let mut edge = Vec::new();
+        for line in layout.lines() {
+            assert!(line.from.is_adjacent(line.to), "line {}", line.id);
+            match layout.line_ends(line) {
+                [Some(_), Some(_)] => {}
+                [Some(_), None] => edge.push(line.id),
+                ends => panic!("line {} has ends {:?}", line.id, ends),
+            }
+        }
+        assert_eq!(edge, vec![0, 26, 27, 29]);
+    }
+
+    #[test]
+    fn test_neighbours_follow_lines() {
+        let layout = Layout::table();
+        // Hexagons 6 and 1 touch, but no LED line runs between them.
+        assert!(layout
+            .slot(6)
+            .unwrap()
+            .hex()
+            .unwrap()
+            .is_adjacent(layout.slot(1).unwrap().hex().unwrap()));
+        assert_eq!(layout.neighbours(6), vec![9, 11]);
+        assert_eq!(layout.neighbours(9), vec![4, 6, 7, 11, 12, 14]);
+        assert!(layout.neighbours(0).is_empty());
+
+        assert_eq!(layout.line_between(9, 11).unwrap().id, 23);
+        assert_eq!(layout.line_between(11, 9).unwrap().id, 23);
+        assert!(layout.line_between(6, 1).is_none());
+
+        let lines: Vec<u8> = layout.lines_of(1).iter().map(|line| line.id).collect();
+        assert_eq!(lines, vec![0, 1]);
+    }
+}
+
+#[cfg(test)]
+mod calibration_tests {
+    use crate::error::AppError;
+    use crate::layout::{Calibration, Layout, LayoutStore};
+    use std::path::PathBuf;
+
+    fn calibration_path(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("calibration-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir.join("calibration.json")
+    }
+
+    #[test]
+    fn test_parses_file_format() {
+        let calibration: Calibration =
+            serde_json::from_str(r#"{"slots": {"33": 1, "32": 2}, "lines": {"line_2_1_2_0": 5}}"#)
+                .unwrap();
+        assert_eq!(calibration.slots.get(&33), Some(&1));
+        assert_eq!(calibration.lines.get("line_2_1_2_0"), Some(&5));
+
+        assert_eq!(
+            serde_json::from_str::<Calibration>("{}").unwrap(),
+            Calibration::default()
+        );
+        assert!(serde_json::from_str::<Calibration>(r#"{"slot": {}}"#).is_err());
+    }
+
+    #[test]
+    fn test_example_file_is_valid() {
+        let calibration = Calibration::load("examples/calibration.json").unwrap();
+        let layout = calibration.apply(&Layout::table()).unwrap();
+        assert_eq!(layout.slot_at_i2c(40).unwrap().label, 6);
+        assert_eq!(layout.line(23).unwrap().key, "line_1_0_0_0");
+    }
+
+    #[test]
+    fn test_apply_swaps_slots_and_lines() {
+        let calibration = Calibration {
+            slots: [(33, 1), (32, 2)].into(),
+            lines: [
+                ("line_2_1_2_0".to_string(), 5),
+                ("line_2_4_2_3".to_string(), 1),
+            ]
+            .into(),
+        };
+        let layout = calibration.apply(&Layout::table()).unwrap();
+
+        assert_eq!(layout.slot_at_i2c(33).unwrap().label, 1);
+        assert_eq!(layout.slot_at_i2c(32).unwrap().label, 2);
+        assert_eq!(layout.line(5).unwrap().key, "line_2_1_2_0");
+        assert_eq!(layout.line(1).unwrap().key, "line_2_4_2_3");
+        // Neighbours follow the physical lines, whatever their LED ids.
+        assert_eq!(layout.neighbours(1), Layout::table().neighbours(1));
+        let ids: Vec<u8> = layout.lines().iter().map(|line| line.id).collect();
+        assert_eq!(ids, (0..30).collect::<Vec<u8>>());
+    }
+
+    #[test]
+    fn test_apply_rejects_conflicts() {
+        let table = Layout::table();
+        let unknown_slot = Calibration {
+            slots: [(60, 16)].into(),
+            ..Calibration::default()
+        };
+        assert_eq!(unknown_slot.apply(&table).unwrap_err(), "Unknown slot 16");
+
+        let unknown_line = Calibration {
+            lines: [("line_9_9_9_9".to_string(), 40)].into(),
+            ..Calibration::default()
+        };
+        assert_eq!(
+            unknown_line.apply(&table).unwrap_err(),
+            "Unknown line line_9_9_9_9"
+        );
+
+        let shared_address = Calibration {
+            slots: [(33, 1)].into(),
+            ..Calibration::default()
+        };
+        assert_eq!(
+            shared_address.apply(&table).unwrap_err(),
+            "Slots 1 and 2 are both at I2C 33"
+        );
+
+        let shared_slot = Calibration {
+            slots: [(60, 1), (61, 1)].into(),
+            ..Calibration::default()
+        };
+        assert_eq!(
+            shared_slot.apply(&table).unwrap_err(),
+            "I2C 60 and 61 are both slot 1"
+        );
+
+        let shared_id = Calibration {
+            lines: [("line_2_1_2_0".to_string(), 2)].into(),
+            ..Calibration::default()
+        };
+        assert!(shared_id
+            .apply(&table)
+            .unwrap_err()
+            .ends_with("both have LED 2"));
+    }
+
+    #[tokio::test]
+    async fn test_store_saves_edits() {
+        let path = calibration_path("saves");
+        let store = LayoutStore::with_path(&path);
+
+        let layout = store
+            .edit(|calibration| {
+                calibration.slots.insert(60, 1);
+            })
+            .await
+            .unwrap();
+        assert_eq!(layout.slot_at_i2c(60).unwrap().label, 1);
+        assert_eq!(store.layout().slot(1).unwrap().i2c, 60);
+
+        let saved = Calibration::load(&path).unwrap();
+        assert_eq!(saved, store.calibration());
+        assert_eq!(saved.apply(&Layout::table()).unwrap(), *layout);
+    }
+
+    #[tokio::test]
+    async fn test_store_keeps_concurrent_edits() {
+        let path = calibration_path("concurrent");
+        let store = LayoutStore::with_path(&path);
+
+        let (first, second) = tokio::join!(
+            store.edit(|calibration| {
+                calibration.slots.insert(60, 1);
+            }),
+            store.edit(|calibration| {
+                calibration.slots.insert(61, 2);
+            }),
+        );
+        first.unwrap();
+        second.unwrap();
+
+        let calibration = store.calibration();
+        assert_eq!(calibration.slots.get(&60), Some(&1));
+        assert_eq!(calibration.slots.get(&61), Some(&2));
+        assert_eq!(Calibration::load(&path).unwrap(), calibration);
+    }
+
+    #[tokio::test]
+    async fn test_store_keeps_calibration_on_invalid_edit() {
+        let path = calibration_path("invalid");
+        let store = LayoutStore::with_path(&path);
+        store
+            .edit(|calibration| {
+                calibration.slots.insert(60, 1);
+            })
+            .await
+            .unwrap();
+
+        let error = store
+            .edit(|calibration| {
+                calibration.slots.insert(34, 2);
+            })
+            .await
+            .unwrap_err();
+        assert!(matches!(error, AppError::InvalidRequest(_)));
+        assert_eq!(store.calibration().slots.len(), 1);
+        assert_eq!(Calibration::load(&path).unwrap().slots.len(), 1);
+        assert!(!path.with_extension("json.tmp").exists());
+    }
+
+    #[tokio::test]
+    async fn test_store_reports_unwritable_file() {
+        let path = calibration_path("unwritable")
+            .join("missing")
+            .join("calibration.json");
+        let store = LayoutStore::with_path(&path);
+
+        let error = store.calibrate(Calibration::default()).await.unwrap_err();
+        assert_eq!(error.code(), "STORAGE_ERROR");
+    }
+}
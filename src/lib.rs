@@ -1,10 +1,17 @@
 pub mod config;
+pub mod emulator;
+pub mod error;
 pub mod handlers;
+pub mod inventory;
+pub mod layout;
+pub mod live;
 pub mod models;
+pub mod protocol;
 pub mod serial;
 pub mod utils;
 
 pub use config::*;
+pub use error::AppError;
 pub use models::{
     AppState, ErrorResponse, LedRequest, StopRequest, SuccessResponse, UpdateRequest,
 };
@@ -0,0 +1,130 @@
+use crate::config::EVENT_CHANNEL_SIZE;
+use crate::error::AppError;
+use crate::inventory::ModuleChange;
+use crate::models::AppState;
+use crate::protocol::{Command, Response};
+use crate::serial::{BoardConnection, BoardEvent};
+use futures::stream::{self, BoxStream, Stream, StreamExt};
+use serde::Serialize;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::sync::broadcast;
+use tokio::sync::broadcast::error::RecvError;
+
+/// A command a client had applied, with the board's verdict.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct CommandOutcome {
+    /// Milliseconds since the Unix epoch at which the answer arrived.
+    pub timestamp_ms: u64,
+    /// The command as sent, e.g. `UPDATE(1, 100, 50, 30, 1)`.
+    pub command: String,
+    pub ok: bool,
+    /// The board's answer, if it accepted the command.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub response: Option<String>,
+    /// `code` of the error, as in error responses.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error_code: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Something every client watching the table should learn about.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(untagged)]
+pub enum LiveEvent {
+    /// A module was plugged in, unplugged, moved or retyped.
+    Module(ModuleChange),
+    /// A board's connection state changed.
+    Connection(BoardConnection),
+    Command(CommandOutcome),
+    /// An unsolicited `EVENT` line from the firmware.
+    Firmware(BoardEvent),
+}
+
+impl LiveEvent {
+    /// Kind of the event, used as the SSE event name.
+    pub fn name(&self) -> &'static str {
+        match self {
+            LiveEvent::Module(_) => "module",
+            LiveEvent::Connection(_) => "connection",
+            LiveEvent::Command(_) => "command",
+            LiveEvent::Firmware(_) => "firmware",
+        }
+    }
+}
+
+/// Broadcast channel for the events the server itself produces: topology
+/// changes and command outcomes. Board events are merged in by [`stream`].
+#[derive(Clone, Debug)]
+pub struct LiveFeed {
+    tx: broadcast::Sender<LiveEvent>,
+}
+
+impl Default for LiveFeed {
+    fn default() -> Self {
+        let (tx, _) = broadcast::channel(EVENT_CHANNEL_SIZE);
+        Self { tx }
+    }
+}
+
+impl LiveFeed {
+    pub fn subscribe(&self) -> broadcast::Receiver<LiveEvent> {
+        self.tx.subscribe()
+    }
+
+    pub fn publish(&self, event: LiveEvent) {
+        let _ = self.tx.send(event);
+    }
+
+    pub fn modules(&self, changes: &[ModuleChange]) {
+        for change in changes {
+            self.publish(LiveEvent::Module(*change));
+        }
+    }
+
+    /// Publishes what became of `command`.
+    pub fn command(&self, command: &Command, result: &Result<Response, AppError>) {
+        let timestamp_ms = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_millis() as u64)
+            .unwrap_or_default();
+        self.publish(LiveEvent::Command(CommandOutcome {
+            timestamp_ms,
+            command: command.encode(),
+            ok: result.is_ok(),
+            response: result.as_ref().ok().map(Response::to_string),
+            error_code: result.as_ref().err().map(|e| e.code().to_string()),
+            error: result.as_ref().err().map(AppError::to_string),
+        }));
+    }
+}
+
+/// Every [`LiveEvent`] from now on: the server's own feed merged with the
+/// connection changes and firmware events of all boards.
+///
+/// A subscriber too slow to keep up skips the oldest events.
+pub fn stream(state: &AppState) -> impl Stream<Item = LiveEvent> {
+    stream::select_all([
+        receive(state.live.subscribe(), |event| event),
+        receive(state.arduino.subscribe_connections(), LiveEvent::Connection),
+        receive(state.arduino.subscribe(), LiveEvent::Firmware),
+    ])
+}
+
+fn receive<T: Clone + Send + 'static>(
+    rx: broadcast::Receiver<T>,
+    wrap: fn(T) -> LiveEvent,
+) -> BoxStream<'static, LiveEvent> {
+    stream::unfold(rx, move |mut rx| async move {
+        loop {
+            match rx.recv().await {
+                Ok(item) => return Some((wrap(item), rx)),
+                Err(RecvError::Lagged(missed)) => {
+                    println!("[Live] Subscriber fell behind, skipped {} events", missed)
+                }
+                Err(RecvError::Closed) => return None,
+            }
+        }
+    })
+    .boxed()
+}
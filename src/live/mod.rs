@@ -0,0 +1,6 @@
+pub mod feed;
+
+#[cfg(test)]
+mod tests;
+
+pub use feed::{stream, CommandOutcome, LiveEvent, LiveFeed};
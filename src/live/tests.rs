@@ -0,0 +1,95 @@
+#[cfg(test)]
+mod live_tests {
+    use crate::error::AppError;
+    use crate::inventory::{ModuleChange, ModuleInfo, ModuleType};
+    use crate::live::{stream, LiveEvent, LiveFeed};
+    use crate::models::AppState;
+    use crate::protocol::{Command, Response};
+    use crate::serial::{Boards, ConnectionState, MockTransport, SerialHandle};
+    use futures::StreamExt;
+    use std::time::Duration;
+
+    fn outcome(feed: &LiveFeed, result: Result<Response, AppError>) -> serde_json::Value {
+        let mut rx = feed.subscribe();
+        feed.command(&Command::Stop { eeprom: Some(3) }, &result);
+        let event = rx.try_recv().unwrap();
+        assert_eq!(event.name(), "command");
+        serde_json::to_value(event).unwrap()
+    }
+
+    #[test]
+    fn test_command_outcome() {
+        let feed = LiveFeed::default();
+
+        let accepted = outcome(&feed, Ok(Response::Ok("Stopped".to_string())));
+        assert_eq!(accepted["command"], "STOP(3)");
+        assert_eq!(accepted["ok"], true);
+        assert_eq!(accepted["response"], "OK: Stopped");
+        assert!(accepted.get("error").is_none());
+
+        let rejected = outcome(
+            &feed,
+            Err(AppError::Device {
+                code: Some(3),
+                message: "I2C bus busy".to_string(),
+            }),
+        );
+        assert_eq!(rejected["ok"], false);
+        assert_eq!(rejected["error_code"], "DEVICE_ERROR");
+        assert_eq!(rejected["error"], "I2C bus busy");
+        assert!(rejected.get("response").is_none());
+    }
+
+    #[test]
+    fn test_module_changes_are_published_in_order() {
+        let feed = LiveFeed::default();
+        let mut rx = feed.subscribe();
+        let module = ModuleInfo {
+            eeprom: 1,
+            i2c: 32,
+            module_type: ModuleType::Solar,
+        };
+        feed.modules(&[
+            ModuleChange::Removed { module },
+            ModuleChange::Added { module },
+        ]);
+
+        assert_eq!(
+            rx.try_recv().unwrap(),
+            LiveEvent::Module(ModuleChange::Removed { module })
+        );
+        let added = serde_json::to_value(rx.try_recv().unwrap()).unwrap();
+        assert_eq!(added["change"], "added");
+        assert_eq!(added["module"]["EEPROM"], 1);
+    }
+
+    #[tokio::test]
+    async fn test_stream_merges_feed_and_board_changes() {
+        let mock = MockTransport::new();
+        let handle = SerialHandle::spawn(Some(Box::new(mock.clone())));
+        let state = AppState::new(Boards::single("mock", handle.clone()));
+        let mut events = Box::pin(stream(&state));
+
+        handle.disconnect().await;
+        let event = tokio::time::timeout(Duration::from_secs(1), events.next())
+            .await
+            .unwrap();
+        match event {
+            Some(LiveEvent::Connection(change)) => {
+                assert_eq!(change.port, "mock");
+                assert_eq!(change.health.state, ConnectionState::Searching);
+            }
+            other => panic!("expected a connection change, got {:?}", other),
+        }
+
+        state.live.command(
+            &Command::Stop { eeprom: None },
+            &Err(AppError::NotConnected),
+        );
+        let event = tokio::time::timeout(Duration::from_secs(1), events.next())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(event.name(), "command");
+    }
+}
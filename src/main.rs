@@ -1,41 +1,93 @@
-mod config;
-mod handlers;
-mod models;
-mod serial;
-mod utils;
-
 use axum::{
-    routing::{get, post},
+    middleware,
+    routing::{get, post, put},
     Router,
 };
 use std::{net::SocketAddr, sync::Arc};
-use tokio::sync::Mutex;
 use tower_http::{cors::CorsLayer, services::ServeDir};
 
-use config::SERVER_PORT;
-use handlers::{led, scan, stop, update};
-use models::AppState;
-use serial::{connect_arduino, monitor_arduino_connection};
+use webserver::config::{REPLAY_ENV_VAR, SERVER_PORT};
+use webserver::error::request_id;
+use webserver::handlers::{
+    admin_token_from_env, boards, calibrate_line, calibrate_slot, calibration, connection, events,
+    firmware, layout, led, leds, modules, ports, replace_calibration, require_admin,
+    reset_calibration, scan, serial_stats, stop, update, ws,
+};
+use webserver::inventory::{poll_interval_from_env, poll_topology, ConsensusConfig};
+use webserver::layout::LayoutStore;
+use webserver::models::AppState;
+use webserver::serial::{
+    monitor_arduino_connection, reconnect, replay_arduinos, Backoff, Boards, CaptureLog,
+    DiscoveryConfig, LinkConfig,
+};
 
 #[tokio::main]
 async fn main() {
-    let arduino_port = connect_arduino().await;
+    let link = LinkConfig::from_env();
+    let discovery = DiscoveryConfig::from_env();
+    let capture = CaptureLog::from_env();
+    let replay = std::env::var(REPLAY_ENV_VAR).ok();
     let state = AppState {
-        arduino: Arc::new(Mutex::new(arduino_port)),
-        scan_cache: Arc::new(Mutex::new(None)),
+        discovery: Arc::new(discovery),
+        capture,
+        scan_consensus: ConsensusConfig::from_env(),
+        scan_poll: poll_interval_from_env(),
+        layout: LayoutStore::from_env(),
+        admin_token: admin_token_from_env(),
+        ..AppState::new(Boards::new(link))
     };
 
-    let monitor_state = state.clone();
-    tokio::spawn(async move {
-        monitor_arduino_connection(monitor_state).await;
-    });
+    // A replay stands in for the hardware, so no real boards are looked for.
+    if let Some(path) = &replay {
+        for replayed in replay_arduinos(path, &link).await {
+            state.arduino.attach(replayed).await;
+        }
+    } else {
+        reconnect(&state, &Backoff::default()).await;
+        let monitor_state = state.clone();
+        tokio::spawn(async move {
+            monitor_arduino_connection(monitor_state).await;
+        });
+    }
+
+    if let Some(interval) = state.scan_poll {
+        let poller_state = state.clone();
+        tokio::spawn(async move {
+            poll_topology(poller_state, interval).await;
+        });
+    }
+
+    // Admin routes are only for the frontend served below, so they get no
+    // CORS headers and need the admin token.
+    let admin = Router::new()
+        .route(
+            "/api/admin/calibration",
+            get(calibration)
+                .put(replace_calibration)
+                .delete(reset_calibration),
+        )
+        .route("/api/admin/calibration/slots/:label", put(calibrate_slot))
+        .route("/api/admin/calibration/lines/:key", put(calibrate_line))
+        .route_layer(middleware::from_fn_with_state(state.clone(), require_admin));
 
     let app = Router::new()
         .route("/api/update", post(update))
         .route("/api/stop", post(stop))
         .route("/api/led", post(led))
+        .route("/api/leds", post(leds))
         .route("/api/scan", get(scan))
+        .route("/api/modules", get(modules))
+        .route("/api/layout", get(layout))
+        .route("/api/events", get(events))
+        .route("/api/ws", get(ws))
+        .route("/api/serial/stats", get(serial_stats))
+        .route("/api/boards", get(boards))
+        .route("/api/ports", get(ports))
+        .route("/api/firmware", get(firmware))
+        .route("/api/connection", get(connection))
         .layer(CorsLayer::permissive())
+        .merge(admin)
+        .layer(middleware::from_fn(request_id))
         .with_state(state)
         .nest_service("/", ServeDir::new("src/frontend/build"));
     let addr = SocketAddr::from(([0, 0, 0, 0], SERVER_PORT));
@@ -3,6 +3,7 @@ pub mod responses;
 pub mod state;
 
 #[cfg(test)]
+#[allow(clippy::module_inception, clippy::bool_assert_comparison)]
 mod tests;
 
 pub use requests::*;
@@ -28,3 +28,55 @@ pub struct LedRequest {
     #[serde(rename = "pulseFrequenz")]
     pub pulse_frequenz: i32,
 }
+
+#[derive(Deserialize, Debug)]
+pub struct LedBatchRequest {
+    pub leds: Vec<LedRequest>,
+}
+
+/// Body of `PUT /api/admin/calibration/slots/{label}`.
+#[derive(Deserialize, Debug)]
+pub struct SlotCalibrationRequest {
+    pub i2c: u8,
+}
+
+/// Body of `PUT /api/admin/calibration/lines/{key}`.
+#[derive(Deserialize, Debug)]
+pub struct LineCalibrationRequest {
+    #[serde(rename = "ledID")]
+    pub led_id: u8,
+}
+
+/// A command sent over `GET /api/ws`, with the same fields as the matching
+/// REST request.
+#[derive(Deserialize, Debug)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum WsCommand {
+    Update(UpdateRequest),
+    Led(LedRequest),
+    Leds(LedBatchRequest),
+    Stop(StopRequest),
+    Scan,
+}
+
+impl WsCommand {
+    /// The module or LED line a command sets, if a newer command for it makes
+    /// this one pointless.
+    pub fn target(&self) -> Option<(&'static str, i32)> {
+        match self {
+            WsCommand::Update(update) => Some(("update", update.eeprom)),
+            WsCommand::Led(led) => Some(("led", led.led_id)),
+            _ => None,
+        }
+    }
+}
+
+/// One WebSocket message from a client: `{"id": 7, "type": "update", ...}`.
+#[derive(Deserialize, Debug)]
+pub struct WsRequest {
+    /// Echoed in the result so the client can match it to the command.
+    #[serde(default)]
+    pub id: Option<serde_json::Value>,
+    #[serde(flatten)]
+    pub command: WsCommand,
+}
@@ -1,3 +1,6 @@
+use crate::inventory::{ModuleInfo, ScanDiagnostics};
+use crate::layout::Layout;
+use crate::live::LiveEvent;
 use serde::Serialize;
 
 #[derive(Serialize)]
@@ -15,8 +18,100 @@ pub struct SuccessResponse {
     pub data: Option<serde_json::Value>,
 }
 
+/// A [`SuccessResponse`] whose `data` comes from a scan.
+#[derive(Serialize)]
+pub struct ScanResponse {
+    #[serde(flatten)]
+    pub response: SuccessResponse,
+    /// How the `SCAN()` samples behind `data` agreed.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub consensus: Option<ScanDiagnostics>,
+    /// When the scan behind `data` finished, in milliseconds since the Unix
+    /// epoch.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub scanned_at_ms: Option<u64>,
+}
+
+/// A module with its type spelled out, as listed by `GET /api/modules`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ModuleView {
+    /// The module's own id, which commands address it by.
+    pub eeprom: u32,
+    pub i2c: u8,
+    /// Label of the slot the module sits in on the calibrated table, `None`
+    /// if it is in none.
+    pub slot: Option<u8>,
+    /// [`ModuleType::name`](crate::inventory::ModuleType::name).
+    #[serde(rename = "type")]
+    pub module_type: &'static str,
+    /// The firmware's `TYPE` code.
+    pub type_code: u8,
+}
+
+impl ModuleView {
+    pub fn new(module: &ModuleInfo, layout: &Layout) -> Self {
+        Self {
+            eeprom: module.eeprom,
+            i2c: module.i2c,
+            slot: layout.slot_at_i2c(module.i2c).map(|slot| slot.label),
+            module_type: module.module_type.name(),
+            type_code: module.module_type.code(),
+        }
+    }
+}
+
+/// Body of `GET /api/modules`.
+#[derive(Serialize)]
+pub struct ModulesResponse {
+    pub status: String,
+    /// Lists modules of unknown type, if any.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+    pub data: Vec<ModuleView>,
+    /// When the scan behind `data` finished, in milliseconds since the Unix
+    /// epoch.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub scanned_at_ms: Option<u64>,
+}
+
 #[derive(Serialize)]
 pub struct ErrorResponse {
     pub status: String,
+    pub code: String,
     pub message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub details: Option<serde_json::Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<String>,
+}
+
+/// What became of a WebSocket command: the body the REST endpoint would have
+/// answered with, or `{"status": "superseded"}`.
+#[derive(Serialize)]
+#[serde(untagged)]
+pub enum WsOutcome {
+    Success(SuccessResponse),
+    Scan(ScanResponse),
+    Error(ErrorResponse),
+    /// A newer command for the same module or LED line arrived before this
+    /// one was sent, so it was skipped.
+    Superseded {
+        status: String,
+    },
+}
+
+/// A message the server sends over `GET /api/ws`.
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum WsMessage {
+    Result {
+        id: Option<serde_json::Value>,
+        #[serde(flatten)]
+        outcome: WsOutcome,
+    },
+    /// A [`LiveEvent`], as streamed by `GET /api/events`.
+    Event {
+        event: &'static str,
+        data: LiveEvent,
+    },
 }
@@ -1,8 +1,46 @@
+use crate::inventory::{ConsensusConfig, ScanCache};
+use crate::layout::LayoutStore;
+use crate::live::LiveFeed;
+use crate::serial::{Boards, CaptureLog, DiscoveryConfig};
 use std::sync::Arc;
-use tokio::sync::Mutex;
+use std::time::Duration;
 
 #[derive(Clone)]
 pub struct AppState {
-    pub arduino: Arc<Mutex<Option<Box<dyn serialport::SerialPort>>>>,
-    pub scan_cache: Arc<Mutex<Option<serde_json::Value>>>,
+    pub arduino: Boards,
+    /// Modules of the last scan that reached a consensus.
+    pub scan_cache: ScanCache,
+    pub discovery: Arc<DiscoveryConfig>,
+    /// Where reconnected boards record their traffic, if capturing.
+    pub capture: Option<CaptureLog>,
+    /// How `GET /api/scan` samples and votes.
+    pub scan_consensus: ConsensusConfig,
+    /// Interval of the background topology poller, `None` if it is off.
+    /// `GET /api/scan` only answers from `scan_cache` while it runs.
+    pub scan_poll: Option<Duration>,
+    /// Topology changes and command outcomes for `GET /api/events`.
+    pub live: LiveFeed,
+    /// Slots and LED lines of the table, as calibrated.
+    pub layout: LayoutStore,
+    /// Token required by the `/api/admin` routes, `None` if they are off.
+    pub admin_token: Option<Arc<str>>,
+}
+
+impl AppState {
+    /// State around `arduino` with an empty scan cache, the default
+    /// discovery rules and scan consensus, without capture or topology
+    /// polling, on the uncalibrated table layout, with the admin routes off.
+    pub fn new(arduino: Boards) -> Self {
+        Self {
+            arduino,
+            scan_cache: ScanCache::default(),
+            discovery: Arc::new(DiscoveryConfig::default()),
+            capture: None,
+            scan_consensus: ConsensusConfig::default(),
+            scan_poll: None,
+            live: LiveFeed::default(),
+            layout: LayoutStore::default(),
+            admin_token: None,
+        }
+    }
 }
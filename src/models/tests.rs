@@ -129,16 +129,39 @@ mod tests {
         assert_eq!(json["data"]["temperature"], 25.5);
     }
 
+    #[test]
+    fn test_module_view_serialization() {
+        use crate::inventory::{ModuleInfo, ModuleType};
+
+        let module = ModuleInfo {
+            eeprom: 4,
+            i2c: 35,
+            module_type: ModuleType::Unknown(9),
+        };
+        let table = crate::layout::Layout::table();
+        let json = serde_json::to_value(crate::models::ModuleView::new(&module, &table)).unwrap();
+        assert_eq!(
+            json,
+            json!({"eeprom": 4, "i2c": 35, "slot": 4, "type": "unknown", "type_code": 9})
+        );
+    }
+
     #[test]
     fn test_error_response_serialization() {
         let response = crate::models::ErrorResponse {
             status: "error".to_string(),
+            code: "ARDUINO_NOT_CONNECTED".to_string(),
             message: "Arduino not connected".to_string(),
+            details: None,
+            request_id: None,
         };
 
         let json = serde_json::to_value(&response).unwrap();
         assert_eq!(json["status"], "error");
+        assert_eq!(json["code"], "ARDUINO_NOT_CONNECTED");
         assert_eq!(json["message"], "Arduino not connected");
+        assert!(json.get("details").is_none());
+        assert!(json.get("request_id").is_none());
     }
 
     #[test]
@@ -0,0 +1,249 @@
+use crate::protocol::frame::crc8;
+use crate::protocol::Command;
+use std::fmt;
+
+/// Frame type carrying a text command such as `SCAN()`.
+pub const FRAME_COMMAND: u8 = 0x01;
+
+/// Frame type setting several LED lines at once.
+pub const FRAME_LED_BATCH: u8 = 0x02;
+
+/// Bytes per entry of a [`FRAME_LED_BATCH`] frame.
+const LED_ENTRY_LEN: usize = 7;
+
+/// Settings of one LED line, as carried by a batch frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LedSetting {
+    pub led_id: u8,
+    pub forward: bool,
+    pub color: (u8, u8, u8),
+    pub pulse_frequenz: u16,
+}
+
+impl LedSetting {
+    /// The batch entry for a [`Command::Led`], if its values fit the binary
+    /// encoding.
+    pub fn from_command(command: &Command) -> Option<Self> {
+        let Command::Led {
+            led_id,
+            forward,
+            color,
+            pulse_frequenz,
+        } = *command
+        else {
+            return None;
+        };
+        Some(Self {
+            led_id: u8::try_from(led_id).ok()?,
+            forward,
+            color,
+            pulse_frequenz: u16::try_from(pulse_frequenz).ok()?,
+        })
+    }
+
+    /// The equivalent text command, for firmware without binary frames.
+    pub fn to_command(self) -> Command {
+        Command::Led {
+            led_id: self.led_id as i32,
+            forward: self.forward,
+            color: self.color,
+            pulse_frequenz: self.pulse_frequenz as i32,
+        }
+    }
+}
+
+/// A frame of the binary protocol.
+///
+/// Before COBS encoding a frame is
+///
+/// ```text
+/// type (1) | seq (2, big endian) | payload | CRC-8 over everything before it (1)
+/// ```
+///
+/// [`FRAME_COMMAND`] carries the ASCII text of a [`Command`];
+/// [`FRAME_LED_BATCH`] a count byte followed by 7 bytes per line: id,
+/// flags (bit 0 = forward), red, green, blue and the pulse frequency
+/// (big endian). On the wire the COBS encoded frame sits between two `0x00`
+/// delimiters, so a receiver can resynchronize after noise.
+///
+/// The firmware answers with ordinary text lines, always carrying the `#seq`
+/// id and a `*XX` checksum.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BinaryFrame {
+    Command { seq: u16, text: String },
+    LedBatch { seq: u16, leds: Vec<LedSetting> },
+}
+
+impl BinaryFrame {
+    pub fn seq(&self) -> u16 {
+        match self {
+            BinaryFrame::Command { seq, .. } | BinaryFrame::LedBatch { seq, .. } => *seq,
+        }
+    }
+
+    /// The bytes to write to the port, delimiters included.
+    pub fn encode(&self) -> Vec<u8> {
+        let mut raw = Vec::new();
+        match self {
+            BinaryFrame::Command { seq, text } => {
+                raw.push(FRAME_COMMAND);
+                raw.extend_from_slice(&seq.to_be_bytes());
+                raw.extend_from_slice(text.as_bytes());
+            }
+            BinaryFrame::LedBatch { seq, leds } => {
+                raw.push(FRAME_LED_BATCH);
+                raw.extend_from_slice(&seq.to_be_bytes());
+                raw.push(leds.len() as u8);
+                for led in leds {
+                    raw.push(led.led_id);
+                    raw.push(led.forward as u8);
+                    raw.extend_from_slice(&[led.color.0, led.color.1, led.color.2]);
+                    raw.extend_from_slice(&led.pulse_frequenz.to_be_bytes());
+                }
+            }
+        }
+        raw.push(crc8(&raw));
+
+        let mut frame = vec![0];
+        frame.extend(cobs_encode(&raw));
+        frame.push(0);
+        frame
+    }
+
+    /// Parses the COBS encoded bytes between two delimiters.
+    pub fn decode(encoded: &[u8]) -> Result<Self, String> {
+        let raw = cobs_decode(encoded)?;
+        let Some((&sum, raw)) = raw.split_last() else {
+            return Err("empty frame".to_string());
+        };
+        if raw.len() < 3 {
+            return Err("truncated frame".to_string());
+        }
+        if crc8(raw) != sum {
+            return Err("checksum mismatch".to_string());
+        }
+
+        let seq = u16::from_be_bytes([raw[1], raw[2]]);
+        let payload = &raw[3..];
+        match raw[0] {
+            FRAME_COMMAND => Ok(BinaryFrame::Command {
+                seq,
+                text: String::from_utf8(payload.to_vec())
+                    .map_err(|_| "command is not valid text".to_string())?,
+            }),
+            FRAME_LED_BATCH => {
+                let (&count, entries) = payload
+                    .split_first()
+                    .ok_or_else(|| "missing LED count".to_string())?;
+                if entries.len() != count as usize * LED_ENTRY_LEN {
+                    return Err(format!(
+                        "LED batch of {} lines has {} bytes",
+                        count,
+                        entries.len()
+                    ));
+                }
+                let leds = entries
+                    .chunks(LED_ENTRY_LEN)
+                    .map(|entry| LedSetting {
+                        led_id: entry[0],
+                        forward: entry[1] & 1 != 0,
+                        color: (entry[2], entry[3], entry[4]),
+                        pulse_frequenz: u16::from_be_bytes([entry[5], entry[6]]),
+                    })
+                    .collect();
+                Ok(BinaryFrame::LedBatch { seq, leds })
+            }
+            other => Err(format!("unknown frame type 0x{:02X}", other)),
+        }
+    }
+}
+
+impl fmt::Display for BinaryFrame {
+    /// Readable form for logs and captures, e.g. `#5 SCAN()` or
+    /// `#6 LEDS(3:1:FF0000:5, 4:0:00FF00:1)`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BinaryFrame::Command { seq, text } => write!(f, "#{} {}", seq, text),
+            BinaryFrame::LedBatch { seq, leds } => {
+                let entries: Vec<String> = leds
+                    .iter()
+                    .map(|led| {
+                        format!(
+                            "{}:{}:{:02X}{:02X}{:02X}:{}",
+                            led.led_id,
+                            led.forward as u8,
+                            led.color.0,
+                            led.color.1,
+                            led.color.2,
+                            led.pulse_frequenz
+                        )
+                    })
+                    .collect();
+                write!(f, "#{} LEDS({})", seq, entries.join(", "))
+            }
+        }
+    }
+}
+
+/// Readable form of a frame as written to the port: text frames as they
+/// are, binary frames as `bin ` followed by their [`BinaryFrame`] display.
+pub fn describe_frame(frame: &[u8]) -> String {
+    if frame.first() != Some(&0) {
+        return String::from_utf8_lossy(frame).trim_end().to_string();
+    }
+    let encoded: &[u8] = frame
+        .split(|&byte| byte == 0)
+        .find(|part| !part.is_empty())
+        .unwrap_or_default();
+    match BinaryFrame::decode(encoded) {
+        Ok(decoded) => format!("bin {}", decoded),
+        Err(e) => format!("bin <{}>", e),
+    }
+}
+
+/// Consistent Overhead Byte Stuffing: rewrites `data` without any `0x00`
+/// bytes, at the cost of one extra byte per 254.
+pub fn cobs_encode(data: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(data.len() + data.len() / 254 + 2);
+    let mut code_index = 0;
+    out.push(0);
+    let mut code: u8 = 1;
+
+    for &byte in data {
+        if byte != 0 {
+            out.push(byte);
+            code += 1;
+        }
+        if byte == 0 || code == 0xFF {
+            out[code_index] = code;
+            code_index = out.len();
+            out.push(0);
+            code = 1;
+        }
+    }
+    out[code_index] = code;
+    out
+}
+
+/// Inverse of [`cobs_encode`].
+pub fn cobs_decode(data: &[u8]) -> Result<Vec<u8>, String> {
+    let mut out = Vec::with_capacity(data.len());
+    let mut index = 0;
+
+    while index < data.len() {
+        let code = data[index] as usize;
+        if code == 0 {
+            return Err("zero byte inside COBS frame".to_string());
+        }
+        let end = index + code;
+        if end > data.len() {
+            return Err("COBS frame ends early".to_string());
+        }
+        out.extend_from_slice(&data[index + 1..end]);
+        index = end;
+        if code < 0xFF && index < data.len() {
+            out.push(0);
+        }
+    }
+    Ok(out)
+}
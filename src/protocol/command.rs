@@ -0,0 +1,145 @@
+use crate::config::{HEARTBEAT_TIMEOUT, SCAN_TIMEOUT, TIMEOUT};
+use std::fmt;
+use std::time::Duration;
+
+/// A command understood by the Arduino firmware.
+///
+/// [`Command::encode`] produces the text that goes between `<` and `>` on the
+/// wire; [`Command::parse`] is its inverse and is used by the emulator.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+    Update {
+        eeprom: i32,
+        power: i32,
+        charge: i32,
+        time: i32,
+        active: i32,
+    },
+    Led {
+        led_id: i32,
+        forward: bool,
+        color: (u8, u8, u8),
+        pulse_frequenz: i32,
+    },
+    Stop {
+        eeprom: Option<i32>,
+    },
+    Scan,
+    /// Asks the firmware for its [`FirmwareInfo`](crate::protocol::FirmwareInfo).
+    Identify,
+    /// Liveness probe sent by the heartbeat while the board is idle.
+    Ping,
+}
+
+impl Command {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Command::Update { .. } => "UPDATE",
+            Command::Led { .. } => "LED",
+            Command::Stop { .. } => "STOP",
+            Command::Scan => "SCAN",
+            Command::Identify => "IDENTIFY",
+            Command::Ping => "PING",
+        }
+    }
+
+    /// How long the board may take to answer this command.
+    pub fn deadline(&self) -> Duration {
+        match self {
+            Command::Scan => SCAN_TIMEOUT,
+            Command::Ping => HEARTBEAT_TIMEOUT,
+            _ => TIMEOUT,
+        }
+    }
+
+    pub fn encode(&self) -> String {
+        let args = match self {
+            Command::Update {
+                eeprom,
+                power,
+                charge,
+                time,
+                active,
+            } => vec![*eeprom, *power, *charge, *time, *active],
+            Command::Led {
+                led_id,
+                forward,
+                color,
+                pulse_frequenz,
+            } => vec![
+                *led_id,
+                *forward as i32,
+                color.0 as i32,
+                color.1 as i32,
+                color.2 as i32,
+                *pulse_frequenz,
+            ],
+            Command::Stop { eeprom } => eeprom.iter().copied().collect(),
+            Command::Scan | Command::Identify | Command::Ping => Vec::new(),
+        };
+
+        let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+        format!("{}({})", self.name(), args.join(", "))
+    }
+
+    /// Parses the text of one frame, e.g. `LED(1, 1, 255, 0, 0, 5)`.
+    pub fn parse(text: &str) -> Result<Command, String> {
+        let text = text.trim();
+        let open = text
+            .find('(')
+            .filter(|_| text.ends_with(')'))
+            .ok_or_else(|| format!("malformed command {}", text))?;
+
+        let name = text[..open].trim();
+        let inner = text[open + 1..text.len() - 1].trim();
+        let args: Vec<i32> = if inner.is_empty() {
+            Vec::new()
+        } else {
+            inner
+                .split(',')
+                .map(|arg| {
+                    arg.trim()
+                        .parse::<i32>()
+                        .map_err(|_| format!("invalid argument '{}'", arg.trim()))
+                })
+                .collect::<Result<_, _>>()?
+        };
+
+        let channel = |value: i32| {
+            u8::try_from(value).map_err(|_| format!("color value {} out of range", value))
+        };
+
+        match (name, args.as_slice()) {
+            ("UPDATE", &[eeprom, power, charge, time, active]) => Ok(Command::Update {
+                eeprom,
+                power,
+                charge,
+                time,
+                active,
+            }),
+            ("LED", &[led_id, forward, r, g, b, pulse_frequenz]) => Ok(Command::Led {
+                led_id,
+                forward: forward != 0,
+                color: (channel(r)?, channel(g)?, channel(b)?),
+                pulse_frequenz,
+            }),
+            ("STOP", &[]) => Ok(Command::Stop { eeprom: None }),
+            ("STOP", &[eeprom]) => Ok(Command::Stop {
+                eeprom: Some(eeprom),
+            }),
+            ("SCAN", &[]) => Ok(Command::Scan),
+            ("IDENTIFY", &[]) => Ok(Command::Identify),
+            ("PING", &[]) => Ok(Command::Ping),
+            ("UPDATE" | "LED" | "STOP" | "SCAN" | "IDENTIFY" | "PING", _) => {
+                Err(format!("wrong number of arguments for {}", name))
+            }
+            _ => Err(format!("unknown command {}", name)),
+        }
+    }
+}
+
+impl fmt::Display for Command {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.encode())
+    }
+}
@@ -0,0 +1,147 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// A line the firmware prints on its own, not in reply to a command.
+///
+/// Events are told apart from replies by their `EVENT` prefix:
+///
+/// ```text
+/// EVENT PLUG: {"EEPROM":7,"I2C":38,"TYPE":4}
+/// EVENT UNPLUG: {"EEPROM":7}
+/// EVENT BUTTON: {"EEPROM":3,"BUTTON":1}
+/// EVENT FAULT 12: overcurrent on line 4
+/// ```
+///
+/// Events the server does not know, or whose payload does not parse, are
+/// kept as [`Event::Other`].
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Event {
+    ModulePlugged {
+        eeprom: i32,
+        i2c: i32,
+        module_type: i32,
+    },
+    ModuleUnplugged {
+        eeprom: i32,
+    },
+    ButtonPressed {
+        eeprom: i32,
+        button: i32,
+    },
+    Fault {
+        code: Option<i32>,
+        message: String,
+    },
+    Other {
+        name: String,
+        payload: String,
+    },
+}
+
+#[derive(Deserialize)]
+struct Payload {
+    #[serde(rename = "EEPROM")]
+    eeprom: i32,
+    #[serde(rename = "I2C")]
+    i2c: Option<i32>,
+    #[serde(rename = "TYPE")]
+    module_type: Option<i32>,
+    #[serde(rename = "BUTTON")]
+    button: Option<i32>,
+}
+
+impl Event {
+    /// Parses an event line; `None` if the line is not an event at all.
+    pub fn parse(line: &str) -> Option<Event> {
+        let line = line.trim();
+        let (head, body) = match line.split_once(':') {
+            Some((head, body)) => (head.trim(), body.trim()),
+            None => (line, ""),
+        };
+        let mut words = head.split_whitespace();
+        if !words.next()?.eq_ignore_ascii_case("EVENT") {
+            return None;
+        }
+        let name = words.next().unwrap_or("").to_uppercase();
+        let code = words.next().map(str::parse::<i32>);
+
+        let payload = serde_json::from_str::<Payload>(body).ok();
+        let event = match (name.as_str(), code, payload) {
+            (
+                "PLUG",
+                None,
+                Some(Payload {
+                    eeprom,
+                    i2c: Some(i2c),
+                    module_type: Some(module_type),
+                    ..
+                }),
+            ) => Event::ModulePlugged {
+                eeprom,
+                i2c,
+                module_type,
+            },
+            ("UNPLUG", None, Some(Payload { eeprom, .. })) => Event::ModuleUnplugged { eeprom },
+            (
+                "BUTTON",
+                None,
+                Some(Payload {
+                    eeprom,
+                    button: Some(button),
+                    ..
+                }),
+            ) => Event::ButtonPressed { eeprom, button },
+            ("FAULT", None, _) => Event::Fault {
+                code: None,
+                message: body.to_string(),
+            },
+            ("FAULT", Some(Ok(code)), _) => Event::Fault {
+                code: Some(code),
+                message: body.to_string(),
+            },
+            _ => Event::Other {
+                name,
+                payload: body.to_string(),
+            },
+        };
+        Some(event)
+    }
+
+    /// The line as the firmware prints it.
+    pub fn encode(&self) -> String {
+        match self {
+            Event::ModulePlugged {
+                eeprom,
+                i2c,
+                module_type,
+            } => format!(
+                r#"EVENT PLUG: {{"EEPROM":{},"I2C":{},"TYPE":{}}}"#,
+                eeprom, i2c, module_type
+            ),
+            Event::ModuleUnplugged { eeprom } => {
+                format!(r#"EVENT UNPLUG: {{"EEPROM":{}}}"#, eeprom)
+            }
+            Event::ButtonPressed { eeprom, button } => format!(
+                r#"EVENT BUTTON: {{"EEPROM":{},"BUTTON":{}}}"#,
+                eeprom, button
+            ),
+            Event::Fault {
+                code: Some(code),
+                message,
+            } => format!("EVENT FAULT {}: {}", code, message),
+            Event::Fault {
+                code: None,
+                message,
+            } => format!("EVENT FAULT: {}", message),
+            Event::Other { name, payload } if payload.is_empty() => format!("EVENT {}", name),
+            Event::Other { name, payload } => format!("EVENT {}: {}", name, payload),
+        }
+    }
+}
+
+impl fmt::Display for Event {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.encode())
+    }
+}
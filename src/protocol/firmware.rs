@@ -0,0 +1,99 @@
+use crate::config::PROTOCOL_VERSIONS;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Capability announcing that the firmware echoes `#<seq>` ids.
+pub const CAP_SEQUENCED: &str = "sequenced";
+
+/// Capability announcing that the firmware checks and sends `*XX` checksums.
+pub const CAP_CRC8: &str = "crc8";
+
+/// Capability announcing that the firmware accepts COBS encoded binary frames
+/// (see [`crate::protocol::BinaryFrame`]).
+pub const CAP_BINARY: &str = "binary";
+
+/// What the firmware reports about itself in reply to `IDENTIFY()`:
+///
+/// ```text
+/// ID: {"name":"energy-table","version":"2.1.0","protocol":1,"capabilities":["sequenced","crc8"]}
+/// ```
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct FirmwareInfo {
+    pub name: String,
+    pub version: String,
+    /// Revision of the serial protocol, see [`PROTOCOL_VERSIONS`].
+    pub protocol: u32,
+    /// Optional protocol features, e.g. [`CAP_SEQUENCED`] and [`CAP_CRC8`].
+    #[serde(default)]
+    pub capabilities: Vec<String>,
+    /// First and last LED id the board drives, if the firmware says; lets a
+    /// server with several boards route `LED` commands without probing.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub leds: Option<[i32; 2]>,
+    /// `false` for firmware that predates `IDENTIFY()`; the other fields are
+    /// placeholders then.
+    #[serde(default = "identified")]
+    pub identified: bool,
+}
+
+fn identified() -> bool {
+    true
+}
+
+impl FirmwareInfo {
+    /// Stand-in for firmware that answers `IDENTIFY()` with an error: protocol
+    /// revision 1 without optional features.
+    pub fn legacy() -> Self {
+        Self {
+            name: "unknown".to_string(),
+            version: "unknown".to_string(),
+            protocol: 1,
+            capabilities: Vec::new(),
+            leds: None,
+            identified: false,
+        }
+    }
+
+    /// Parses the JSON payload of an `ID:` reply.
+    pub fn from_value(value: serde_json::Value) -> Result<Self, String> {
+        serde_json::from_value(value).map_err(|e| format!("malformed IDENTIFY reply: {}", e))
+    }
+
+    pub fn supports(&self, capability: &str) -> bool {
+        self.capabilities
+            .iter()
+            .any(|c| c.eq_ignore_ascii_case(capability))
+    }
+
+    /// Whether the server can talk to this firmware; the error says why not.
+    pub fn check_compatible(&self) -> Result<(), String> {
+        if PROTOCOL_VERSIONS.contains(&self.protocol) {
+            return Ok(());
+        }
+        Err(format!(
+            "{} {} speaks protocol {}, this server supports {}..={}",
+            self.name,
+            self.version,
+            self.protocol,
+            PROTOCOL_VERSIONS.start(),
+            PROTOCOL_VERSIONS.end()
+        ))
+    }
+}
+
+impl fmt::Display for FirmwareInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if !self.identified {
+            return write!(f, "unidentified firmware (protocol {})", self.protocol);
+        }
+        write!(
+            f,
+            "{} {} (protocol {}",
+            self.name, self.version, self.protocol
+        )?;
+        for capability in &self.capabilities {
+            write!(f, ", {}", capability)?;
+        }
+        f.write_str(")")
+    }
+}
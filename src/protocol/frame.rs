@@ -0,0 +1,121 @@
+use crate::config::FRAMING_ENV_VAR;
+use serde::Serialize;
+
+/// How commands are wrapped on the wire and replies matched to them.
+///
+/// ```text
+/// Legacy:     <UPDATE(1, 100, 50, 30, 1)>      ->  OK: Updated
+/// Sequenced:  <#17 UPDATE(1, 100, 50, 30, 1)>  ->  #17 OK: Updated
+/// Checksum:   <#17 UPDATE(1, 100, 50, 30, 1)*F5>  ->  #17 OK: Updated*5A
+/// ```
+///
+/// With [`Framing::Sequenced`] the firmware echoes the id in front of its
+/// reply, so late answers to timed-out commands and boot noise can be told
+/// apart from the answer being waited for. [`Framing::Legacy`] is kept for
+/// firmware that does not echo ids; there the next line is the answer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Framing {
+    #[default]
+    Legacy,
+    Sequenced,
+}
+
+impl Framing {
+    /// Reads the framing from [`FRAMING_ENV_VAR`]; anything but `sequenced` is legacy.
+    pub fn from_env() -> Self {
+        match std::env::var(FRAMING_ENV_VAR) {
+            Ok(value) if value.eq_ignore_ascii_case("sequenced") => Framing::Sequenced,
+            _ => Framing::Legacy,
+        }
+    }
+
+    /// Builds the complete frame for `command`, including the line ending.
+    ///
+    /// With `checksum` the frame body gets a `*XX` suffix (see [`append_checksum`]).
+    pub fn encode(self, seq: u16, command: &str, checksum: bool) -> String {
+        let body = match self {
+            Framing::Legacy => command.to_string(),
+            Framing::Sequenced => with_sequence(Some(seq), command),
+        };
+        if checksum {
+            format!("<{}>\n", append_checksum(&body))
+        } else {
+            format!("<{}>\n", body)
+        }
+    }
+}
+
+/// Prefixes `text` with `#<seq> ` if there is a sequence id.
+pub fn with_sequence(seq: Option<u16>, text: &str) -> String {
+    match seq {
+        Some(seq) => format!("#{} {}", seq, text),
+        None => text.to_string(),
+    }
+}
+
+/// Splits a leading `#<seq> ` off a frame body or reply line.
+pub fn split_sequence(text: &str) -> (Option<u16>, &str) {
+    let text = text.trim();
+    let Some(rest) = text.strip_prefix('#') else {
+        return (None, text);
+    };
+    let (id, body) = rest.split_once(' ').unwrap_or((rest, ""));
+    match id.parse::<u16>() {
+        Ok(seq) => (Some(seq), body.trim()),
+        Err(_) => (None, text),
+    }
+}
+
+/// Whether `received` is an id that was handed out before `expected`
+/// (within half the id space, so wrap-around is handled).
+pub fn is_stale(expected: u16, received: u16) -> bool {
+    let behind = expected.wrapping_sub(received);
+    behind != 0 && behind <= u16::MAX / 2
+}
+
+/// CRC-8/SMBUS (polynomial `0x07`, initial value `0`), cheap enough for the
+/// firmware to compute per byte.
+pub fn crc8(data: &[u8]) -> u8 {
+    data.iter().fold(0u8, |crc, &byte| {
+        (0..8).fold(crc ^ byte, |crc, _| {
+            if crc & 0x80 != 0 {
+                (crc << 1) ^ 0x07
+            } else {
+                crc << 1
+            }
+        })
+    })
+}
+
+/// Appends the `*XX` checksum suffix covering all of `text`.
+pub fn append_checksum(text: &str) -> String {
+    format!("{}*{:02X}", text, crc8(text.as_bytes()))
+}
+
+/// Outcome of checking the `*XX` suffix of a received line.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Checked<'a> {
+    /// The checksum matches; carries the text without the suffix.
+    Valid(&'a str),
+    /// The line has no checksum suffix at all.
+    Missing,
+    /// The line has a suffix that does not match its content.
+    Corrupted,
+}
+
+/// Verifies and strips a `*XX` checksum suffix.
+pub fn verify_checksum(line: &str) -> Checked<'_> {
+    let line = line.trim();
+    let Some((text, suffix)) = line.rsplit_once('*') else {
+        return Checked::Missing;
+    };
+    if suffix.len() != 2 {
+        return Checked::Missing;
+    }
+    match u8::from_str_radix(suffix, 16) {
+        Ok(sum) if sum == crc8(text.as_bytes()) => Checked::Valid(text),
+        Ok(_) => Checked::Corrupted,
+        Err(_) => Checked::Missing,
+    }
+}
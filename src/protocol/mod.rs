@@ -0,0 +1,16 @@
+pub mod binary;
+pub mod command;
+pub mod event;
+pub mod firmware;
+pub mod frame;
+pub mod response;
+
+#[cfg(test)]
+mod tests;
+
+pub use binary::{BinaryFrame, LedSetting};
+pub use command::Command;
+pub use event::Event;
+pub use firmware::FirmwareInfo;
+pub use frame::Framing;
+pub use response::Response;
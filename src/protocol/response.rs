@@ -0,0 +1,93 @@
+use std::fmt;
+
+/// A line received from the Arduino firmware.
+///
+/// The firmware answers every command with one of:
+///
+/// ```text
+/// OK: message
+/// ERROR: message
+/// ERROR 12: message
+/// SCAN: [{"EEPROM":1,"I2C":32,"TYPE":2}]
+/// ID: {"name":"energy-table","version":"2.1.0","protocol":1,"capabilities":[]}
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub enum Response {
+    /// The command was executed; carries the optional message after `OK:`.
+    Ok(String),
+    /// The firmware rejected the command.
+    Error { code: Option<i32>, message: String },
+    /// Result of `SCAN()`: the JSON payload after `SCAN:`.
+    Scan(serde_json::Value),
+    /// Result of `IDENTIFY()`: the JSON payload after `ID:`.
+    Identify(serde_json::Value),
+    /// Anything the parser does not recognize, kept verbatim.
+    Unknown(String),
+}
+
+impl Response {
+    pub fn parse(line: &str) -> Response {
+        let line = line.trim();
+        let (head, body) = match line.split_once(':') {
+            Some((head, body)) => (head.trim(), body.trim()),
+            None => (line, ""),
+        };
+        let mut words = head.split_whitespace();
+        let keyword = words.next().unwrap_or("").to_uppercase();
+
+        match keyword.as_str() {
+            "OK" if words.next().is_none() => Response::Ok(body.to_string()),
+            "ERROR" => match words.next().map(str::parse::<i32>) {
+                None => Response::Error {
+                    code: None,
+                    message: body.to_string(),
+                },
+                Some(Ok(code)) if words.next().is_none() => Response::Error {
+                    code: Some(code),
+                    message: body.to_string(),
+                },
+                _ => Response::Error {
+                    code: None,
+                    message: line.to_string(),
+                },
+            },
+            "SCAN" if words.next().is_none() => match serde_json::from_str(body) {
+                Ok(data) => Response::Scan(data),
+                Err(_) => Response::Unknown(line.to_string()),
+            },
+            "ID" if words.next().is_none() => match serde_json::from_str(body) {
+                Ok(data) => Response::Identify(data),
+                Err(_) => Response::Unknown(line.to_string()),
+            },
+            _ => Response::Unknown(line.to_string()),
+        }
+    }
+
+    pub fn encode(&self) -> String {
+        match self {
+            Response::Ok(message) if message.is_empty() => "OK".to_string(),
+            Response::Ok(message) => format!("OK: {}", message),
+            Response::Error {
+                code: Some(code),
+                message,
+            } => format!("ERROR {}: {}", code, message),
+            Response::Error {
+                code: None,
+                message,
+            } => format!("ERROR: {}", message),
+            Response::Scan(data) => format!("SCAN: {}", data),
+            Response::Identify(data) => format!("ID: {}", data),
+            Response::Unknown(line) => line.clone(),
+        }
+    }
+
+    pub fn is_error(&self) -> bool {
+        matches!(self, Response::Error { .. })
+    }
+}
+
+impl fmt::Display for Response {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.encode())
+    }
+}
@@ -0,0 +1,589 @@
+//! Tests for the Arduino command and response model
+
+#[cfg(test)]
+mod command_tests {
+    use crate::protocol::Command;
+
+    fn all_commands() -> Vec<Command> {
+        vec![
+            Command::Update {
+                eeprom: 1,
+                power: 100,
+                charge: 50,
+                time: 30,
+                active: 1,
+            },
+            Command::Update {
+                eeprom: 0,
+                power: -5,
+                charge: 0,
+                time: 0,
+                active: 0,
+            },
+            Command::Led {
+                led_id: 2,
+                forward: false,
+                color: (0, 255, 0),
+                pulse_frequenz: 10,
+            },
+            Command::Stop { eeprom: None },
+            Command::Stop { eeprom: Some(7) },
+            Command::Scan,
+            Command::Identify,
+            Command::Ping,
+        ]
+    }
+
+    #[test]
+    fn test_encode() {
+        let encoded: Vec<String> = all_commands().iter().map(Command::encode).collect();
+        assert_eq!(
+            encoded,
+            vec![
+                "UPDATE(1, 100, 50, 30, 1)",
+                "UPDATE(0, -5, 0, 0, 0)",
+                "LED(2, 0, 0, 255, 0, 10)",
+                "STOP()",
+                "STOP(7)",
+                "SCAN()",
+                "IDENTIFY()",
+                "PING()",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_round_trip() {
+        for command in all_commands() {
+            assert_eq!(Command::parse(&command.encode()), Ok(command));
+        }
+    }
+
+    #[test]
+    fn test_parse_tolerates_whitespace() {
+        assert_eq!(
+            Command::parse(" STOP( 3 ) "),
+            Ok(Command::Stop { eeprom: Some(3) })
+        );
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert_eq!(
+            Command::parse("FOO()"),
+            Err("unknown command FOO".to_string())
+        );
+        assert_eq!(
+            Command::parse("SCAN(1)"),
+            Err("wrong number of arguments for SCAN".to_string())
+        );
+        assert!(Command::parse("SCAN").is_err());
+        assert!(Command::parse("STOP(x)").is_err());
+        assert!(Command::parse("LED(1, 1, 256, 0, 0, 1)").is_err());
+    }
+
+    #[test]
+    fn test_scan_has_longer_deadline() {
+        assert!(Command::Scan.deadline() > Command::Stop { eeprom: None }.deadline());
+    }
+}
+
+#[cfg(test)]
+mod response_tests {
+    use crate::protocol::Response;
+    use serde_json::json;
+
+    #[test]
+    fn test_parse_ok() {
+        assert_eq!(
+            Response::parse("OK: Updated\r"),
+            Response::Ok("Updated".to_string())
+        );
+        assert_eq!(Response::parse("OK"), Response::Ok(String::new()));
+    }
+
+    #[test]
+    fn test_parse_error_without_code() {
+        assert_eq!(
+            Response::parse("ERROR: unknown EEPROM 9"),
+            Response::Error {
+                code: None,
+                message: "unknown EEPROM 9".to_string()
+            }
+        );
+        assert!(Response::parse("error: lowercase").is_error());
+    }
+
+    #[test]
+    fn test_parse_error_with_code() {
+        assert_eq!(
+            Response::parse("ERROR 12: unknown EEPROM 9"),
+            Response::Error {
+                code: Some(12),
+                message: "unknown EEPROM 9".to_string()
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_scan() {
+        assert_eq!(
+            Response::parse(r#"SCAN: [{"EEPROM":1,"I2C":32,"TYPE":2}]"#),
+            Response::Scan(json!([{"EEPROM": 1, "I2C": 32, "TYPE": 2}]))
+        );
+    }
+
+    #[test]
+    fn test_parse_identify() {
+        assert_eq!(
+            Response::parse(r#"ID: {"name":"energy-table","version":"2.1.0","protocol":1}"#),
+            Response::Identify(json!({"name": "energy-table", "version": "2.1.0", "protocol": 1}))
+        );
+        assert_eq!(
+            Response::parse("ID: energy-table"),
+            Response::Unknown("ID: energy-table".to_string())
+        );
+    }
+
+    #[test]
+    fn test_parse_unknown() {
+        assert_eq!(
+            Response::parse("SCAN: {broken"),
+            Response::Unknown("SCAN: {broken".to_string())
+        );
+        assert_eq!(
+            Response::parse("Booting..."),
+            Response::Unknown("Booting...".to_string())
+        );
+        assert_eq!(
+            Response::parse("OKAY then"),
+            Response::Unknown("OKAY then".to_string())
+        );
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let responses = vec![
+            Response::Ok("Stopped".to_string()),
+            Response::Ok(String::new()),
+            Response::Error {
+                code: None,
+                message: "unknown LED 31".to_string(),
+            },
+            Response::Error {
+                code: Some(4),
+                message: "bus fault".to_string(),
+            },
+            Response::Scan(json!([{"EEPROM": 1, "I2C": 32, "TYPE": 2}])),
+            Response::Scan(json!([])),
+            Response::Identify(json!({"name": "energy-table", "protocol": 1})),
+            Response::Unknown("hello".to_string()),
+        ];
+        for response in responses {
+            assert_eq!(Response::parse(&response.encode()), response);
+        }
+    }
+}
+
+#[cfg(test)]
+mod frame_tests {
+    use crate::protocol::frame::{
+        append_checksum, crc8, is_stale, split_sequence, verify_checksum, with_sequence, Checked,
+    };
+    use crate::protocol::Framing;
+
+    #[test]
+    fn test_encode() {
+        assert_eq!(Framing::Legacy.encode(7, "SCAN()", false), "<SCAN()>\n");
+        assert_eq!(
+            Framing::Sequenced.encode(7, "SCAN()", false),
+            "<#7 SCAN()>\n"
+        );
+        assert_eq!(
+            Framing::Sequenced.encode(7, "SCAN()", true),
+            format!("<{}>\n", append_checksum("#7 SCAN()"))
+        );
+    }
+
+    #[test]
+    fn test_split_sequence() {
+        assert_eq!(split_sequence("#12 OK: Updated"), (Some(12), "OK: Updated"));
+        assert_eq!(split_sequence("OK: Updated"), (None, "OK: Updated"));
+        assert_eq!(split_sequence("#x OK"), (None, "#x OK"));
+        assert_eq!(split_sequence("#3"), (Some(3), ""));
+    }
+
+    #[test]
+    fn test_with_sequence_round_trip() {
+        let tagged = with_sequence(Some(40000), "STOP(2)");
+        assert_eq!(tagged, "#40000 STOP(2)");
+        assert_eq!(split_sequence(&tagged), (Some(40000), "STOP(2)"));
+        assert_eq!(with_sequence(None, "STOP(2)"), "STOP(2)");
+    }
+
+    #[test]
+    fn test_is_stale_handles_wrap_around() {
+        assert!(is_stale(5, 4));
+        assert!(is_stale(1, u16::MAX));
+        assert!(!is_stale(5, 5));
+        assert!(!is_stale(5, 6));
+        assert!(!is_stale(u16::MAX, 0));
+    }
+
+    #[test]
+    fn test_crc8_check_value() {
+        assert_eq!(crc8(b"123456789"), 0xF4);
+        assert_eq!(crc8(b""), 0);
+    }
+
+    #[test]
+    fn test_verify_checksum() {
+        let line = append_checksum(r#"SCAN: [{"EEPROM":1}]"#);
+        assert_eq!(
+            verify_checksum(&line),
+            Checked::Valid(r#"SCAN: [{"EEPROM":1}]"#)
+        );
+        assert_eq!(verify_checksum("OK: Updated"), Checked::Missing);
+        assert_eq!(verify_checksum("OK: a*b"), Checked::Missing);
+
+        let mut corrupted = line.into_bytes();
+        corrupted[3] = b'X';
+        let corrupted = String::from_utf8(corrupted).unwrap();
+        assert_eq!(verify_checksum(&corrupted), Checked::Corrupted);
+    }
+}
+
+#[cfg(test)]
+mod firmware_info_tests {
+    use crate::protocol::{FirmwareInfo, Framing};
+    use crate::serial::LinkConfig;
+    use serde_json::json;
+
+    fn firmware(protocol: u32, capabilities: &[&str]) -> FirmwareInfo {
+        FirmwareInfo {
+            name: "energy-table".to_string(),
+            version: "2.1.0".to_string(),
+            protocol,
+            capabilities: capabilities.iter().map(|c| c.to_string()).collect(),
+            leds: None,
+            identified: true,
+        }
+    }
+
+    #[test]
+    fn test_from_value() {
+        let info = FirmwareInfo::from_value(json!({
+            "name": "energy-table",
+            "version": "2.1.0",
+            "protocol": 1,
+            "capabilities": ["sequenced", "CRC8"]
+        }))
+        .unwrap();
+        assert_eq!(info, firmware(1, &["sequenced", "CRC8"]));
+        assert!(info.supports("crc8"));
+        assert!(!info.supports("binary"));
+
+        let minimal =
+            FirmwareInfo::from_value(json!({"name": "x", "version": "1", "protocol": 1})).unwrap();
+        assert!(minimal.capabilities.is_empty());
+        assert_eq!(minimal.leds, None);
+        assert!(minimal.identified);
+
+        let split = FirmwareInfo::from_value(
+            json!({"name": "x", "version": "1", "protocol": 1, "leds": [15, 29]}),
+        )
+        .unwrap();
+        assert_eq!(split.leds, Some([15, 29]));
+
+        assert!(FirmwareInfo::from_value(json!({"name": "x"})).is_err());
+    }
+
+    #[test]
+    fn test_compatibility() {
+        assert_eq!(firmware(1, &[]).check_compatible(), Ok(()));
+        assert_eq!(FirmwareInfo::legacy().check_compatible(), Ok(()));
+        assert_eq!(
+            firmware(2, &[]).check_compatible(),
+            Err("energy-table 2.1.0 speaks protocol 2, this server supports 1..=1".to_string())
+        );
+    }
+
+    #[test]
+    fn test_display() {
+        assert_eq!(
+            firmware(1, &["sequenced", "crc8"]).to_string(),
+            "energy-table 2.1.0 (protocol 1, sequenced, crc8)"
+        );
+        assert_eq!(
+            FirmwareInfo::legacy().to_string(),
+            "unidentified firmware (protocol 1)"
+        );
+    }
+
+    #[test]
+    fn test_negotiate_enables_announced_features() {
+        let requested = LinkConfig::default();
+
+        let link = requested.negotiate(&firmware(1, &["sequenced", "crc8"]));
+        assert_eq!(link.framing, Framing::Sequenced);
+        assert!(link.checksum);
+
+        assert_eq!(requested.negotiate(&FirmwareInfo::legacy()), requested);
+    }
+
+    #[test]
+    fn test_negotiate_binary_only_when_requested_and_announced() {
+        let requested = LinkConfig {
+            binary: true,
+            ..LinkConfig::default()
+        };
+
+        let link = requested.negotiate(&firmware(1, &["sequenced", "crc8", "binary"]));
+        assert!(link.binary);
+        assert_eq!(link.framing, Framing::Sequenced);
+        assert!(link.checksum);
+        assert_eq!(link.frame(1, "SCAN()")[0], 0);
+
+        let link = requested.negotiate(&firmware(1, &["sequenced"]));
+        assert!(!link.binary);
+        assert_eq!(link.frame(1, "SCAN()"), b"<#1 SCAN()>\n".to_vec());
+
+        let declined = LinkConfig::default().negotiate(&firmware(1, &["binary"]));
+        assert!(!declined.binary);
+    }
+
+    #[test]
+    fn test_unsupported_requested_features() {
+        let requested = LinkConfig {
+            framing: Framing::Sequenced,
+            checksum: true,
+            retries: 0,
+            ..LinkConfig::default()
+        };
+        assert_eq!(
+            requested.unsupported(&firmware(1, &["sequenced"])),
+            vec!["crc8"]
+        );
+        assert!(requested.unsupported(&FirmwareInfo::legacy()).is_empty());
+        assert!(LinkConfig::default()
+            .unsupported(&firmware(1, &[]))
+            .is_empty());
+    }
+}
+
+#[cfg(test)]
+mod event_tests {
+    use crate::protocol::{Event, Response};
+    use serde_json::json;
+
+    #[test]
+    fn test_parse_known_events() {
+        assert_eq!(
+            Event::parse(r#"EVENT PLUG: {"EEPROM":7,"I2C":38,"TYPE":4}"#),
+            Some(Event::ModulePlugged {
+                eeprom: 7,
+                i2c: 38,
+                module_type: 4
+            })
+        );
+        assert_eq!(
+            Event::parse(r#"event unplug: {"EEPROM":7}"#),
+            Some(Event::ModuleUnplugged { eeprom: 7 })
+        );
+        assert_eq!(
+            Event::parse(r#"EVENT BUTTON: {"EEPROM":3,"BUTTON":1}"#),
+            Some(Event::ButtonPressed {
+                eeprom: 3,
+                button: 1
+            })
+        );
+        assert_eq!(
+            Event::parse("EVENT FAULT 12: overcurrent on line 4"),
+            Some(Event::Fault {
+                code: Some(12),
+                message: "overcurrent on line 4".to_string()
+            })
+        );
+    }
+
+    #[test]
+    fn test_unknown_or_malformed_events_are_kept() {
+        assert_eq!(
+            Event::parse("EVENT TEMP: 41.5"),
+            Some(Event::Other {
+                name: "TEMP".to_string(),
+                payload: "41.5".to_string()
+            })
+        );
+        assert_eq!(
+            Event::parse(r#"EVENT PLUG: {"EEPROM":7}"#),
+            Some(Event::Other {
+                name: "PLUG".to_string(),
+                payload: r#"{"EEPROM":7}"#.to_string()
+            })
+        );
+    }
+
+    #[test]
+    fn test_replies_are_not_events() {
+        assert_eq!(Event::parse("OK: Updated"), None);
+        assert_eq!(Event::parse("ERROR: unknown EEPROM 9"), None);
+        assert_eq!(Event::parse("EVENTS: none"), None);
+        assert_eq!(Event::parse(""), None);
+        assert!(matches!(
+            Response::parse("EVENT PLUG: {}"),
+            Response::Unknown(_)
+        ));
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let events = vec![
+            Event::ModulePlugged {
+                eeprom: 1,
+                i2c: 32,
+                module_type: 2,
+            },
+            Event::ModuleUnplugged { eeprom: 1 },
+            Event::ButtonPressed {
+                eeprom: 1,
+                button: 2,
+            },
+            Event::Fault {
+                code: None,
+                message: "brown-out".to_string(),
+            },
+            Event::Fault {
+                code: Some(3),
+                message: "bus fault".to_string(),
+            },
+            Event::Other {
+                name: "TEMP".to_string(),
+                payload: "41.5".to_string(),
+            },
+        ];
+        for event in events {
+            assert_eq!(Event::parse(&event.encode()), Some(event));
+        }
+    }
+
+    #[test]
+    fn test_serialize() {
+        assert_eq!(
+            serde_json::to_value(Event::ModuleUnplugged { eeprom: 7 }).unwrap(),
+            json!({"event": "module_unplugged", "eeprom": 7})
+        );
+    }
+}
+
+#[cfg(test)]
+mod binary_tests {
+    use crate::protocol::binary::{cobs_decode, cobs_encode, describe_frame};
+    use crate::protocol::{BinaryFrame, Command, LedSetting};
+
+    fn led(led_id: u8) -> LedSetting {
+        LedSetting {
+            led_id,
+            forward: true,
+            color: (0xFF, 0x00, 0x10),
+            pulse_frequenz: 300,
+        }
+    }
+
+    #[test]
+    fn test_cobs_round_trip() {
+        let long_run: Vec<u8> = (1..=255).chain(1..=40).map(|n| n as u8).collect();
+        for data in [
+            vec![],
+            vec![0],
+            vec![0, 0, 1, 0],
+            b"SCAN()".to_vec(),
+            long_run,
+        ] {
+            let encoded = cobs_encode(&data);
+            assert!(!encoded.contains(&0), "{:?}", encoded);
+            assert_eq!(cobs_decode(&encoded).unwrap(), data);
+        }
+        assert_eq!(cobs_encode(&[0x11, 0x00, 0x22]), vec![2, 0x11, 2, 0x22]);
+    }
+
+    #[test]
+    fn test_cobs_rejects_malformed_input() {
+        assert!(cobs_decode(&[5, 1, 2]).is_err());
+        assert!(cobs_decode(&[2, 1, 0, 1]).is_err());
+    }
+
+    #[test]
+    fn test_frame_round_trip() {
+        for frame in [
+            BinaryFrame::Command {
+                seq: 513,
+                text: "SCAN()".to_string(),
+            },
+            BinaryFrame::LedBatch {
+                seq: 7,
+                leds: vec![led(0), led(29)],
+            },
+        ] {
+            let bytes = frame.encode();
+            assert_eq!((bytes[0], bytes[bytes.len() - 1]), (0, 0));
+            assert!(!bytes[1..bytes.len() - 1].contains(&0));
+            assert_eq!(BinaryFrame::decode(&bytes[1..bytes.len() - 1]), Ok(frame));
+        }
+    }
+
+    #[test]
+    fn test_decode_rejects_corruption() {
+        let bytes = BinaryFrame::LedBatch {
+            seq: 1,
+            leds: vec![led(3)],
+        }
+        .encode();
+        let mut raw = cobs_decode(&bytes[1..bytes.len() - 1]).unwrap();
+
+        raw[4] ^= 0x01;
+        let corrupted = cobs_encode(&raw);
+        assert_eq!(
+            BinaryFrame::decode(&corrupted),
+            Err("checksum mismatch".to_string())
+        );
+
+        assert!(BinaryFrame::decode(&cobs_encode(&[0x02, 0x00])).is_err());
+    }
+
+    #[test]
+    fn test_led_setting_from_command() {
+        let command = Command::Led {
+            led_id: 3,
+            forward: true,
+            color: (0xFF, 0x00, 0x10),
+            pulse_frequenz: 300,
+        };
+        let setting = LedSetting::from_command(&command).unwrap();
+        assert_eq!(setting, led(3));
+        assert_eq!(setting.to_command(), command);
+
+        let too_fast = Command::Led {
+            led_id: 3,
+            forward: true,
+            color: (0, 0, 0),
+            pulse_frequenz: 70_000,
+        };
+        assert_eq!(LedSetting::from_command(&too_fast), None);
+        assert_eq!(LedSetting::from_command(&Command::Scan), None);
+    }
+
+    #[test]
+    fn test_describe_frame() {
+        assert_eq!(describe_frame(b"<#2 STOP()*1C>\n"), "<#2 STOP()*1C>");
+        let batch = BinaryFrame::LedBatch {
+            seq: 6,
+            leds: vec![led(3), led(4)],
+        };
+        assert_eq!(
+            describe_frame(&batch.encode()),
+            "bin #6 LEDS(3:1:FF0010:300, 4:1:FF0010:300)"
+        );
+        assert_eq!(describe_frame(&[0, 1, 0]), "bin <empty frame>");
+    }
+}
@@ -0,0 +1,452 @@
+use crate::config::{COMMAND_QUEUE_SIZE, LED_BATCH_SIZE, TIMEOUT};
+use crate::error::{current_request_id, with_request_id, AppError};
+use crate::protocol::{BinaryFrame, Command, FirmwareInfo, LedSetting, Response};
+use crate::serial::communication::{handle_unsolicited, send_command, send_frame};
+use crate::serial::events::{BoardEvent, EventBus, EventSink};
+use crate::serial::health::{ConnectionHealth, ConnectionState};
+use crate::serial::link::{LinkConfig, LinkStats, LinkStatsSnapshot};
+use crate::serial::transport::Transport;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+use tokio::sync::{broadcast, mpsc, oneshot};
+
+enum SerialRequest {
+    Command {
+        command: Command,
+        /// The HTTP request the command belongs to, for the traffic capture.
+        request_id: Option<String>,
+        reply: oneshot::Sender<Result<Response, AppError>>,
+    },
+    Leds {
+        leds: Vec<LedSetting>,
+        request_id: Option<String>,
+        reply: oneshot::Sender<Result<Response, AppError>>,
+    },
+    Attach {
+        transport: Box<dyn Transport>,
+        link: LinkConfig,
+    },
+    Disconnect,
+}
+
+/// What the handshake learned about the current connection.
+struct Session {
+    link: LinkConfig,
+    firmware: Option<FirmwareInfo>,
+}
+
+/// Cheap, cloneable handle to the task that owns the serial transport.
+///
+/// Commands are queued and executed one at a time in arrival order, so HTTP
+/// handlers never hold a lock on the port. All serial I/O is asynchronous.
+///
+/// Between commands the task keeps reading, so `EVENT` lines the board
+/// prints on its own are published (see [`SerialHandle::subscribe`]) instead
+/// of being mistaken for the next reply.
+#[derive(Clone)]
+pub struct SerialHandle {
+    tx: mpsc::Sender<SerialRequest>,
+    link: LinkConfig,
+    stats: Arc<LinkStats>,
+    session: Arc<RwLock<Session>>,
+    events: Arc<RwLock<EventSink>>,
+    health: Arc<RwLock<ConnectionHealth>>,
+    /// Commands queued or in flight.
+    pending: Arc<AtomicUsize>,
+}
+
+/// Counts a command as pending until it is dropped.
+struct PendingGuard<'a>(&'a AtomicUsize);
+
+impl Drop for PendingGuard<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+impl SerialHandle {
+    /// Spawns the serial task, optionally with an already opened transport,
+    /// using the default [`LinkConfig`] (legacy framing, no checksum, no retries).
+    pub fn spawn(transport: Option<Box<dyn Transport>>) -> Self {
+        Self::with_link(transport, LinkConfig::default())
+    }
+
+    /// Spawns the serial task with explicit link settings.
+    ///
+    /// `link` is what is requested; [`SerialHandle::attach`] widens it to the
+    /// features the firmware announces.
+    pub fn with_link(transport: Option<Box<dyn Transport>>, link: LinkConfig) -> Self {
+        let (tx, rx) = mpsc::channel(COMMAND_QUEUE_SIZE);
+        let stats = Arc::new(LinkStats::default());
+        let events = Arc::new(RwLock::new(EventSink::default()));
+        let health = Arc::new(RwLock::new(ConnectionHealth::new(match transport {
+            Some(_) => ConnectionState::Connected,
+            None => ConnectionState::Searching,
+        })));
+        // Binary frames only once the handshake has shown the firmware reads them.
+        let unnegotiated = LinkConfig {
+            binary: false,
+            ..link
+        };
+        tokio::spawn(run(
+            transport,
+            unnegotiated,
+            Arc::clone(&stats),
+            Arc::clone(&events),
+            Arc::clone(&health),
+            rx,
+        ));
+        Self {
+            tx,
+            link,
+            stats,
+            session: Arc::new(RwLock::new(Session {
+                link: unnegotiated,
+                firmware: None,
+            })),
+            events,
+            health,
+            pending: Arc::new(AtomicUsize::new(0)),
+        }
+    }
+
+    /// Receives the events this board publishes from now on.
+    pub fn subscribe(&self) -> broadcast::Receiver<BoardEvent> {
+        self.events.read().unwrap().bus.subscribe()
+    }
+
+    /// Publishes this board's events on `bus`, labelled with `port`.
+    pub(crate) fn publish_to(&self, port: &str, bus: EventBus) {
+        *self.events.write().unwrap() = EventSink {
+            port: port.to_string(),
+            bus,
+        };
+    }
+
+    /// Settings of the current connection and frame counters since startup.
+    pub fn stats(&self) -> LinkStatsSnapshot {
+        self.stats.snapshot(&self.session.read().unwrap().link)
+    }
+
+    /// The firmware found by the last handshake, if the board went through one.
+    pub fn firmware(&self) -> Option<FirmwareInfo> {
+        self.session.read().unwrap().firmware.clone()
+    }
+
+    /// Connection state, reconnect counters and last error of this board.
+    pub fn health(&self) -> ConnectionHealth {
+        self.health.read().unwrap().clone()
+    }
+
+    /// Records a connection attempt made outside the serial task.
+    pub(crate) fn update_health(&self, update: impl FnOnce(&mut ConnectionHealth)) {
+        update_health(&self.health, &self.events.read().unwrap(), update);
+    }
+
+    /// Whether a transport is currently attached.
+    pub fn is_connected(&self) -> bool {
+        self.health.read().unwrap().is_up()
+    }
+
+    /// Whether commands are waiting for the board or being answered by it.
+    pub fn is_busy(&self) -> bool {
+        self.pending.load(Ordering::Relaxed) > 0
+    }
+
+    fn track(&self) -> PendingGuard<'_> {
+        self.pending.fetch_add(1, Ordering::Relaxed);
+        PendingGuard(&self.pending)
+    }
+
+    /// Sends one command and waits for the board's answer, at most
+    /// [`Command::deadline`] long.
+    ///
+    /// A firmware `ERROR` reply comes back as [`AppError::Device`] and keeps
+    /// the port; timeouts and I/O failures drop it.
+    pub async fn send(&self, command: Command) -> Result<Response, AppError> {
+        let _pending = self.track();
+        let (reply, response) = oneshot::channel();
+        let request = SerialRequest::Command {
+            command,
+            request_id: current_request_id(),
+            reply,
+        };
+        if self.tx.send(request).await.is_err() {
+            return Err(AppError::NotConnected);
+        }
+        response.await.unwrap_or(Err(AppError::NotConnected))
+    }
+
+    /// Sets several LED lines, as one [`BinaryFrame::LedBatch`] per
+    /// [`LED_BATCH_SIZE`] lines if the link is binary, otherwise as one `LED`
+    /// command per line. Stops at the first failure; on success the answer
+    /// to the last frame is returned.
+    pub async fn send_leds(&self, leds: Vec<LedSetting>) -> Result<Response, AppError> {
+        let _pending = self.track();
+        let (reply, response) = oneshot::channel();
+        let request = SerialRequest::Leds {
+            leds,
+            request_id: current_request_id(),
+            reply,
+        };
+        if self.tx.send(request).await.is_err() {
+            return Err(AppError::NotConnected);
+        }
+        response.await.unwrap_or(Err(AppError::NotConnected))
+    }
+
+    /// Hands a freshly opened and identified transport to the serial task,
+    /// using the link features `firmware` supports from now on.
+    pub async fn attach(&self, transport: Box<dyn Transport>, firmware: FirmwareInfo) {
+        let link = self.link.negotiate(&firmware);
+        *self.session.write().unwrap() = Session {
+            link,
+            firmware: Some(firmware),
+        };
+        self.update_health(ConnectionHealth::connected);
+        let _ = self
+            .tx
+            .send(SerialRequest::Attach { transport, link })
+            .await;
+    }
+
+    /// Drops the current transport so the connection monitor reopens it.
+    pub async fn disconnect(&self) {
+        self.update_health(|health| health.enter(ConnectionState::Searching));
+        let _ = self.tx.send(SerialRequest::Disconnect).await;
+    }
+}
+
+/// Applies `update` to `health` and announces the new state on the board's
+/// event bus if it changed.
+fn update_health(
+    health: &RwLock<ConnectionHealth>,
+    events: &EventSink,
+    update: impl FnOnce(&mut ConnectionHealth),
+) {
+    let mut health = health.write().unwrap();
+    let before = health.state;
+    update(&mut health);
+    if health.state != before {
+        events.publish_connection(health.clone());
+    }
+}
+
+/// What one frame of a request carries.
+enum Payload {
+    Text(String),
+    LedBatch(Vec<LedSetting>),
+}
+
+/// Sends `frames` one after another, each with the next sequence id, and
+/// returns the answer to the last one. The first failure ends the request.
+async fn execute(
+    port: Option<&mut Box<dyn Transport>>,
+    link: &LinkConfig,
+    stats: &LinkStats,
+    events: &EventSink,
+    seq: &mut u16,
+    frames: Vec<(Payload, Duration)>,
+) -> Result<Response, AppError> {
+    let port = port.ok_or(AppError::NotConnected)?;
+    let mut response = Response::Ok(String::new());
+    for (payload, deadline) in frames {
+        *seq = seq.wrapping_add(1);
+        let frame = match payload {
+            Payload::Text(text) => link.frame(*seq, &text),
+            Payload::LedBatch(leds) => BinaryFrame::LedBatch { seq: *seq, leds }.encode(),
+        };
+        let line = send_frame(port.as_mut(), link, stats, events, *seq, &frame, deadline).await?;
+        response = match Response::parse(&line) {
+            Response::Error { code, message } => return Err(AppError::Device { code, message }),
+            response => response,
+        };
+    }
+    Ok(response)
+}
+
+/// What woke the serial task up.
+enum Incoming {
+    Request(Option<SerialRequest>),
+    Line(Result<String, AppError>),
+    /// The board has been idle for the heartbeat interval.
+    Heartbeat,
+}
+
+async fn run(
+    mut transport: Option<Box<dyn Transport>>,
+    mut link: LinkConfig,
+    stats: Arc<LinkStats>,
+    events: Arc<RwLock<EventSink>>,
+    health: Arc<RwLock<ConnectionHealth>>,
+    mut rx: mpsc::Receiver<SerialRequest>,
+) {
+    let mut seq: u16 = 0;
+    let mut last_activity = Instant::now();
+    let mut missed_heartbeats: u32 = 0;
+    let sink = || events.read().unwrap().clone();
+
+    loop {
+        let incoming = match transport.as_mut() {
+            Some(port) => {
+                let heartbeat = async {
+                    match link.heartbeat.interval {
+                        Some(interval) => {
+                            tokio::time::sleep_until((last_activity + interval).into()).await
+                        }
+                        None => std::future::pending().await,
+                    }
+                };
+                tokio::select! {
+                    request = rx.recv() => Incoming::Request(request),
+                    line = port.receive_unsolicited() => Incoming::Line(line),
+                    _ = heartbeat => Incoming::Heartbeat,
+                }
+            }
+            None => Incoming::Request(rx.recv().await),
+        };
+
+        let request = match incoming {
+            Incoming::Request(Some(request)) => request,
+            Incoming::Request(None) => break,
+            Incoming::Line(Ok(line)) => {
+                handle_unsolicited(&line, &link, &stats, &sink());
+                last_activity = Instant::now();
+                continue;
+            }
+            Incoming::Line(Err(e)) => {
+                println!("[Error] while reading from Arduino: {}", e);
+                update_health(&health, &sink(), |health| health.lost(&e));
+                transport = None;
+                continue;
+            }
+            Incoming::Heartbeat => {
+                let Some(port) = transport.as_mut() else {
+                    continue;
+                };
+                seq = seq.wrapping_add(1);
+                let started = Instant::now();
+                stats.heartbeat_sent();
+                // One attempt per ping; missing it is what the threshold counts.
+                let result = send_command(
+                    port.as_mut(),
+                    &LinkConfig { retries: 0, ..link },
+                    &stats,
+                    &sink(),
+                    seq,
+                    &Command::Ping.encode(),
+                    Command::Ping.deadline(),
+                )
+                .await;
+                last_activity = Instant::now();
+
+                match result {
+                    // Any reply, even an ERROR from firmware without PING,
+                    // shows the board is alive.
+                    Ok(_) => {
+                        stats.heartbeat_answered(started.elapsed());
+                        missed_heartbeats = 0;
+                        update_health(&health, &sink(), |health| {
+                            health.enter(ConnectionState::Connected)
+                        });
+                    }
+                    Err(e) => {
+                        stats.heartbeat_missed();
+                        missed_heartbeats += 1;
+                        if missed_heartbeats >= link.heartbeat.misses
+                            || matches!(e, AppError::Io(_))
+                        {
+                            println!(
+                                "[Error] Arduino missed {} heartbeat(s), reconnecting: {}",
+                                missed_heartbeats, e
+                            );
+                            update_health(&health, &sink(), |health| health.lost(&e));
+                            transport = None;
+                            missed_heartbeats = 0;
+                        } else {
+                            update_health(&health, &sink(), |health| {
+                                health.enter(ConnectionState::Degraded)
+                            });
+                        }
+                    }
+                }
+                continue;
+            }
+        };
+
+        let retransmissions = stats.retransmissions();
+        let (result, reply) = match request {
+            SerialRequest::Command {
+                command,
+                request_id,
+                reply,
+            } => {
+                let frames = vec![(Payload::Text(command.encode()), command.deadline())];
+                let result = with_request_id(
+                    request_id,
+                    execute(transport.as_mut(), &link, &stats, &sink(), &mut seq, frames),
+                )
+                .await;
+                (result, reply)
+            }
+            SerialRequest::Leds {
+                leds,
+                request_id,
+                reply,
+            } => {
+                let frames = if link.binary {
+                    leds.chunks(LED_BATCH_SIZE)
+                        .map(|chunk| (Payload::LedBatch(chunk.to_vec()), TIMEOUT))
+                        .collect()
+                } else {
+                    leds.into_iter()
+                        .map(|led| (Payload::Text(led.to_command().encode()), TIMEOUT))
+                        .collect()
+                };
+                let result = with_request_id(
+                    request_id,
+                    execute(transport.as_mut(), &link, &stats, &sink(), &mut seq, frames),
+                )
+                .await;
+                (result, reply)
+            }
+            SerialRequest::Attach {
+                transport: port,
+                link: negotiated,
+            } => {
+                transport = Some(port);
+                link = negotiated;
+                last_activity = Instant::now();
+                missed_heartbeats = 0;
+                continue;
+            }
+            SerialRequest::Disconnect => {
+                transport = None;
+                continue;
+            }
+        };
+
+        match &result {
+            Err(e) if e.is_transport_failure() => {
+                update_health(&health, &sink(), |health| health.lost(e));
+                transport = None;
+            }
+            Err(AppError::NotConnected) => {}
+            // The board answered, but only after a retransmission.
+            _ if stats.retransmissions() > retransmissions => {
+                update_health(&health, &sink(), |health| {
+                    health.enter(ConnectionState::Degraded)
+                });
+            }
+            _ => {
+                missed_heartbeats = 0;
+                update_health(&health, &sink(), |health| {
+                    health.enter(ConnectionState::Connected)
+                });
+            }
+        }
+        last_activity = Instant::now();
+        let _ = reply.send(result);
+    }
+}
@@ -0,0 +1,465 @@
+use crate::error::AppError;
+use crate::protocol::{Command, FirmwareInfo, LedSetting, Response};
+use crate::serial::actor::SerialHandle;
+use crate::serial::connection::Connection;
+use crate::serial::events::{BoardEvent, EventBus};
+use crate::serial::health::{overall_state, ConnectionHealth, ConnectionState};
+use crate::serial::link::{LinkConfig, LinkStatsSnapshot};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::sync::{Arc, RwLock};
+use std::time::Instant;
+use tokio::sync::broadcast;
+
+/// What a board can own: a module (by EEPROM id) or an LED line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Key {
+    Module(i32),
+    Led(i32),
+}
+
+struct Board {
+    port: String,
+    handle: SerialHandle,
+}
+
+#[derive(Default)]
+struct Inner {
+    boards: Vec<Board>,
+    owners: BTreeMap<Key, String>,
+}
+
+/// A board that did not answer its part of a merged `SCAN()`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ScanFailure {
+    pub port: String,
+    pub error: String,
+}
+
+/// Status of one board as reported by `GET /api/boards`.
+#[derive(Debug, Clone, Serialize)]
+pub struct BoardStatus {
+    pub port: String,
+    pub connected: bool,
+    /// EEPROM ids of the modules this board answered for.
+    pub eeproms: Vec<i32>,
+    /// LED lines this board answered for.
+    pub leds: Vec<i32>,
+    /// Result of the last handshake; `None` if the board never went through one.
+    pub firmware: Option<FirmwareInfo>,
+    pub link: LinkStatsSnapshot,
+}
+
+/// Connection state of one board, as listed by `GET /api/connection` and
+/// announced on every change (see [`Boards::subscribe_connections`]).
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct BoardConnection {
+    pub port: String,
+    #[serde(flatten)]
+    pub health: ConnectionHealth,
+}
+
+/// Connection state of all boards, as reported by `GET /api/connection`.
+#[derive(Debug, Clone, Serialize)]
+pub struct ConnectionStatus {
+    /// See [`overall_state`].
+    pub state: ConnectionState,
+    pub boards: Vec<BoardConnection>,
+}
+
+/// All Arduino boards the server talks to, keyed by port name.
+///
+/// [`Boards::send`] routes every command to the board that owns its module
+/// or LED line. Module ownership is learned from `SCAN()` results, LED
+/// ownership from the `leds` range a board reports to `IDENTIFY()`. Ids
+/// neither has claimed are offered to each board in turn: a board that
+/// answers `ERROR: unknown EEPROM n` / `unknown LED n` does not own the id,
+/// the first one that accepts it does.
+///
+/// Unsolicited events and connection state changes of all boards are
+/// published on one [`EventBus`], see [`Boards::subscribe`] and
+/// [`Boards::subscribe_connections`].
+#[derive(Clone, Default)]
+pub struct Boards {
+    inner: Arc<RwLock<Inner>>,
+    link: LinkConfig,
+    events: EventBus,
+}
+
+impl Boards {
+    /// An empty set whose boards will use `link`.
+    pub fn new(link: LinkConfig) -> Self {
+        Self {
+            inner: Arc::default(),
+            link,
+            events: EventBus::default(),
+        }
+    }
+
+    /// A set containing exactly one board.
+    pub fn single(port: impl Into<String>, handle: SerialHandle) -> Self {
+        let boards = Self::default();
+        boards.insert(port, handle);
+        boards
+    }
+
+    /// Adds a board, replacing any board on the same port. Its events are
+    /// published on the shared bus from now on.
+    pub fn insert(&self, port: impl Into<String>, handle: SerialHandle) {
+        let port = port.into();
+        handle.publish_to(&port, self.events.clone());
+        let mut inner = self.inner.write().unwrap();
+        inner.boards.retain(|board| board.port != port);
+        inner.boards.push(Board { port, handle });
+    }
+
+    /// Receives the unsolicited events of every board from now on.
+    pub fn subscribe(&self) -> broadcast::Receiver<BoardEvent> {
+        self.events.subscribe()
+    }
+
+    /// Receives every connection state change of every board from now on.
+    pub fn subscribe_connections(&self) -> broadcast::Receiver<BoardConnection> {
+        self.events.subscribe_connections()
+    }
+
+    /// Link settings requested for new connections, before negotiation.
+    pub fn link(&self) -> LinkConfig {
+        self.link
+    }
+
+    /// Hands a freshly opened connection to the board on its port, creating
+    /// the board if the port is new.
+    pub async fn attach(&self, connection: Connection) {
+        let board = self.board(&connection.port);
+        if let Some(rate) = connection.baud_rate {
+            board.update_health(|health| health.baud_rate = Some(rate));
+        }
+        if let Some(leds) = connection.firmware.leds {
+            self.record_leds(&connection.port, leds);
+        }
+        board
+            .attach(connection.transport, connection.firmware)
+            .await;
+    }
+
+    /// The board on `port`, created without a transport if the port is new,
+    /// so connection attempts can be tracked before the first success.
+    pub fn board(&self, port: &str) -> SerialHandle {
+        self.handle(port).unwrap_or_else(|| {
+            let handle = SerialHandle::with_link(None, self.link);
+            self.insert(port, handle.clone());
+            handle
+        })
+    }
+
+    /// Connection state of every board and of the set as a whole.
+    pub fn connection(&self) -> ConnectionStatus {
+        let boards: Vec<BoardConnection> = self
+            .boards()
+            .into_iter()
+            .map(|(port, handle)| BoardConnection {
+                port,
+                health: handle.health(),
+            })
+            .collect();
+        let health: Vec<ConnectionHealth> =
+            boards.iter().map(|board| board.health.clone()).collect();
+        ConnectionStatus {
+            state: overall_state(&health),
+            boards,
+        }
+    }
+
+    /// Marks boards that are down and whose port is not in `present` as
+    /// searching again.
+    pub fn mark_missing(&self, present: &[String]) {
+        for (port, handle) in self.boards() {
+            if !present.contains(&port) && !handle.health().is_up() {
+                handle.update_health(|health| health.enter(ConnectionState::Searching));
+            }
+        }
+    }
+
+    /// The earliest scheduled reconnection attempt of any board.
+    pub fn next_attempt(&self) -> Option<Instant> {
+        self.boards()
+            .iter()
+            .filter_map(|(_, handle)| handle.health().next_attempt())
+            .min()
+    }
+
+    /// Whether at least one board is connected.
+    pub fn is_connected(&self) -> bool {
+        self.boards()
+            .iter()
+            .any(|(_, handle)| handle.is_connected())
+    }
+
+    /// Whether any connected board has commands queued or in flight.
+    pub fn is_busy(&self) -> bool {
+        self.connected().iter().any(|(_, handle)| handle.is_busy())
+    }
+
+    /// Whether the board on `port` is known and connected.
+    pub fn is_port_connected(&self, port: &str) -> bool {
+        self.handle(port)
+            .is_some_and(|handle| handle.is_connected())
+    }
+
+    /// Sends `command` to the board(s) responsible for it.
+    ///
+    /// `STOP()`, `IDENTIFY()` and `PING()` go to every board, `SCAN()` is answered with
+    /// the merged module list of all boards.
+    pub async fn send(&self, command: Command) -> Result<Response, AppError> {
+        match command {
+            Command::Update { eeprom, .. }
+            | Command::Stop {
+                eeprom: Some(eeprom),
+            } => self.route(Key::Module(eeprom), command).await,
+            Command::Led { led_id, .. } => self.route(Key::Led(led_id), command).await,
+            Command::Stop { eeprom: None } | Command::Identify | Command::Ping => {
+                self.broadcast(command).await
+            }
+            Command::Scan => self.scan().await.map(|(response, _)| response),
+        }
+    }
+
+    /// Sets several LED lines at once, batched per board (see
+    /// [`SerialHandle::send_leds`]).
+    ///
+    /// Lines whose owner is known go to that board; with several boards,
+    /// lines no board has claimed yet are routed one by one like `LED`
+    /// commands. The first error wins, otherwise the last reply is returned.
+    pub async fn send_leds(&self, leds: Vec<LedSetting>) -> Result<Response, AppError> {
+        let boards = self.connected();
+        if let [(_, handle)] = boards.as_slice() {
+            return handle.send_leds(leds).await;
+        }
+
+        let mut batches: BTreeMap<String, Vec<LedSetting>> = BTreeMap::new();
+        let mut unowned = Vec::new();
+        {
+            let inner = self.inner.read().unwrap();
+            for led in leds {
+                match inner.owners.get(&Key::Led(led.led_id as i32)) {
+                    Some(port) => batches.entry(port.clone()).or_default().push(led),
+                    None => unowned.push(led),
+                }
+            }
+        }
+
+        let mut replies = Vec::new();
+        for (port, batch) in batches {
+            replies.push(match self.handle(&port) {
+                Some(handle) => handle.send_leds(batch).await,
+                None => Err(AppError::NotConnected),
+            });
+        }
+        for led in unowned {
+            replies.push(
+                self.route(Key::Led(led.led_id as i32), led.to_command())
+                    .await,
+            );
+        }
+        match replies.iter().position(Result::is_err) {
+            Some(index) => replies.swap_remove(index),
+            None => replies.pop().unwrap_or(Err(AppError::NotConnected)),
+        }
+    }
+
+    pub fn statuses(&self) -> Vec<BoardStatus> {
+        let inner = self.inner.read().unwrap();
+        inner
+            .boards
+            .iter()
+            .map(|board| {
+                let owned = |wanted: fn(&Key) -> Option<i32>| -> Vec<i32> {
+                    inner
+                        .owners
+                        .iter()
+                        .filter(|(_, port)| **port == board.port)
+                        .filter_map(|(key, _)| wanted(key))
+                        .collect()
+                };
+                BoardStatus {
+                    port: board.port.clone(),
+                    connected: board.handle.is_connected(),
+                    eeproms: owned(|key| match key {
+                        Key::Module(eeprom) => Some(*eeprom),
+                        Key::Led(_) => None,
+                    }),
+                    leds: owned(|key| match key {
+                        Key::Led(led) => Some(*led),
+                        Key::Module(_) => None,
+                    }),
+                    firmware: board.handle.firmware(),
+                    link: board.handle.stats(),
+                }
+            })
+            .collect()
+    }
+
+    fn boards(&self) -> Vec<(String, SerialHandle)> {
+        let inner = self.inner.read().unwrap();
+        inner
+            .boards
+            .iter()
+            .map(|board| (board.port.clone(), board.handle.clone()))
+            .collect()
+    }
+
+    fn connected(&self) -> Vec<(String, SerialHandle)> {
+        let mut boards = self.boards();
+        boards.retain(|(_, handle)| handle.is_connected());
+        boards
+    }
+
+    fn handle(&self, port: &str) -> Option<SerialHandle> {
+        let inner = self.inner.read().unwrap();
+        inner
+            .boards
+            .iter()
+            .find(|board| board.port == port)
+            .map(|board| board.handle.clone())
+    }
+
+    fn owner(&self, key: Key) -> Option<SerialHandle> {
+        let port = self.inner.read().unwrap().owners.get(&key).cloned()?;
+        self.handle(&port)
+    }
+
+    fn set_owner(&self, key: Key, port: &str) {
+        let mut inner = self.inner.write().unwrap();
+        inner.owners.insert(key, port.to_string());
+    }
+
+    async fn route(&self, key: Key, command: Command) -> Result<Response, AppError> {
+        if let Some(handle) = self.owner(key) {
+            return handle.send(command).await;
+        }
+
+        let mut result = Err(AppError::NotConnected);
+        for (port, handle) in self.connected() {
+            result = handle.send(command.clone()).await;
+            match &result {
+                Err(e) if is_not_owner(e) || e.is_transport_failure() => continue,
+                Err(AppError::NotConnected) => continue,
+                _ => {
+                    self.set_owner(key, &port);
+                    break;
+                }
+            }
+        }
+        result
+    }
+
+    /// Sends `command` to every board; the first error wins, otherwise the
+    /// last reply is returned.
+    async fn broadcast(&self, command: Command) -> Result<Response, AppError> {
+        let mut result = Err(AppError::NotConnected);
+        for (index, (_, handle)) in self.connected().into_iter().enumerate() {
+            let reply = handle.send(command.clone()).await;
+            if index == 0 || result.is_ok() {
+                result = reply;
+            }
+        }
+        result
+    }
+
+    /// Sends `SCAN()` to every board and merges the module lists, along with
+    /// the boards that failed to answer with one.
+    ///
+    /// Fails only if no board answered with a module list: with the first
+    /// error, or else the first unexpected reply, handed over untouched.
+    pub async fn scan(&self) -> Result<(Response, Vec<ScanFailure>), AppError> {
+        let boards = self.connected();
+        if boards.is_empty() {
+            return Err(AppError::NotConnected);
+        }
+        if boards.len() == 1 {
+            let (port, handle) = &boards[0];
+            let response = handle.send(Command::Scan).await?;
+            if let Response::Scan(serde_json::Value::Array(found)) = &response {
+                self.record_modules(port, found);
+            }
+            return Ok((response, Vec::new()));
+        }
+
+        let mut modules = Vec::new();
+        let mut answered = false;
+        let mut failures = Vec::new();
+        let mut first_error = None;
+        let mut first_unexpected = None;
+        for (port, handle) in &boards {
+            let error = match handle.send(Command::Scan).await {
+                Ok(Response::Scan(serde_json::Value::Array(found))) => {
+                    self.record_modules(port, &found);
+                    modules.extend(found);
+                    answered = true;
+                    continue;
+                }
+                Ok(response) => {
+                    let error = format!("Unexpected reply: {}", response);
+                    first_unexpected.get_or_insert(response);
+                    error
+                }
+                Err(e) => {
+                    let error = e.to_string();
+                    first_error.get_or_insert(e);
+                    error
+                }
+            };
+            println!("[Scan] {} did not answer SCAN(): {}", port, error);
+            failures.push(ScanFailure {
+                port: port.clone(),
+                error,
+            });
+        }
+
+        if !answered {
+            return match (first_error, first_unexpected) {
+                (Some(error), _) => Err(error),
+                (None, Some(response)) => Ok((response, failures)),
+                (None, None) => Err(AppError::NotConnected),
+            };
+        }
+        Ok((Response::Scan(serde_json::Value::Array(modules)), failures))
+    }
+
+    /// Makes `port` the owner of the LED ids `first..=last`, instead of the
+    /// LEDs it owned so far.
+    fn record_leds(&self, port: &str, [first, last]: [i32; 2]) {
+        let mut inner = self.inner.write().unwrap();
+        inner
+            .owners
+            .retain(|key, owner| !(matches!(key, Key::Led(_)) && owner == port));
+        for led in first..=last {
+            inner.owners.insert(Key::Led(led), port.to_string());
+        }
+    }
+
+    /// Makes `port` the owner of every module listed in its scan result.
+    fn record_modules(&self, port: &str, found: &[serde_json::Value]) {
+        let mut inner = self.inner.write().unwrap();
+        inner
+            .owners
+            .retain(|key, owner| !(matches!(key, Key::Module(_)) && owner == port));
+        for module in found {
+            if let Some(eeprom) = module["EEPROM"].as_i64() {
+                inner
+                    .owners
+                    .insert(Key::Module(eeprom as i32), port.to_string());
+            }
+        }
+    }
+}
+
+/// The firmware's answer for ids that belong to a different board.
+fn is_not_owner(error: &AppError) -> bool {
+    matches!(
+        error,
+        AppError::Device { message, .. }
+            if message.starts_with("unknown EEPROM") || message.starts_with("unknown LED")
+    )
+}
@@ -0,0 +1,195 @@
+use crate::config::{CAPTURE_ENV_VAR, CAPTURE_FILES, CAPTURE_MAX_BYTES};
+use crate::error::{current_request_id, AppError};
+use crate::protocol::binary::describe_frame;
+use crate::serial::transport::{Transport, TransportFuture};
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Which way a captured line travelled.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Direction {
+    /// Server to board.
+    Tx,
+    /// Board to server.
+    Rx,
+}
+
+/// One line of a capture file:
+///
+/// ```json
+/// {"ts_ms":1729250000123,"port":"/dev/ttyACM0","dir":"tx","data":"<#4 STOP()>","request_id":"6712a9c0-000004"}
+/// ```
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct CaptureRecord {
+    /// Milliseconds since the Unix epoch.
+    pub ts_ms: u64,
+    pub port: String,
+    pub dir: Direction,
+    /// The frame or line, without the line ending. Binary frames are stored
+    /// decoded, e.g. `bin #6 LEDS(3:1:FF0000:5)` (see [`describe_frame`]).
+    pub data: String,
+    /// The HTTP request that caused the traffic; `None` for the handshake and
+    /// for lines the board sent on its own.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<String>,
+}
+
+struct RotatingFile {
+    path: PathBuf,
+    max_bytes: u64,
+    keep: usize,
+    file: Option<File>,
+    written: u64,
+}
+
+impl RotatingFile {
+    fn write_line(&mut self, line: &str) -> std::io::Result<()> {
+        let len = line.len() as u64 + 1;
+        if self.written > 0 && self.written + len > self.max_bytes {
+            self.rotate()?;
+        }
+        if self.file.is_none() {
+            let file = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&self.path)?;
+            self.written = file.metadata()?.len();
+            self.file = Some(file);
+        }
+        if let Some(file) = self.file.as_mut() {
+            writeln!(file, "{}", line)?;
+            self.written += len;
+        }
+        Ok(())
+    }
+
+    /// Shifts `capture.jsonl` to `capture.jsonl.1`, `.1` to `.2` and so on,
+    /// dropping the oldest file.
+    fn rotate(&mut self) -> std::io::Result<()> {
+        self.file = None;
+        self.written = 0;
+        for index in (1..self.keep).rev() {
+            let from = rotated(&self.path, index);
+            if from.exists() {
+                fs::rename(&from, rotated(&self.path, index + 1))?;
+            }
+        }
+        if self.keep > 0 {
+            fs::rename(&self.path, rotated(&self.path, 1))?;
+        } else {
+            fs::remove_file(&self.path)?;
+        }
+        Ok(())
+    }
+}
+
+fn rotated(path: &Path, index: usize) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(format!(".{}", index));
+    PathBuf::from(name)
+}
+
+/// Opt-in log of all serial traffic, shared by every board.
+///
+/// Records are appended as JSON lines. Once the file would grow beyond
+/// `max_bytes` it is rotated, keeping `keep` older files next to it.
+#[derive(Clone)]
+pub struct CaptureLog {
+    file: Arc<Mutex<RotatingFile>>,
+}
+
+impl CaptureLog {
+    pub fn new(path: impl Into<PathBuf>, max_bytes: u64, keep: usize) -> Self {
+        Self {
+            file: Arc::new(Mutex::new(RotatingFile {
+                path: path.into(),
+                max_bytes,
+                keep,
+                file: None,
+                written: 0,
+            })),
+        }
+    }
+
+    /// The capture requested through [`CAPTURE_ENV_VAR`], if any.
+    pub fn from_env() -> Option<Self> {
+        let path = std::env::var(CAPTURE_ENV_VAR).ok()?;
+        println!("Capturing serial traffic to {}", path);
+        Some(Self::new(path, CAPTURE_MAX_BYTES, CAPTURE_FILES))
+    }
+
+    /// Appends a record; a failing write is logged and does not affect the
+    /// serial link.
+    pub fn record(&self, port: &str, dir: Direction, data: &str) {
+        let record = CaptureRecord {
+            ts_ms: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|elapsed| elapsed.as_millis() as u64)
+                .unwrap_or_default(),
+            port: port.to_string(),
+            dir,
+            data: data.trim_end().to_string(),
+            request_id: current_request_id(),
+        };
+        let Ok(line) = serde_json::to_string(&record) else {
+            return;
+        };
+        if let Err(e) = self.file.lock().unwrap().write_line(&line) {
+            println!("[Error] while writing serial capture: {}", e);
+        }
+    }
+
+    /// Wraps `transport` so everything it sends and receives is recorded
+    /// under `port`.
+    pub fn wrap(&self, port: &str, transport: Box<dyn Transport>) -> Box<dyn Transport> {
+        Box::new(CaptureTransport {
+            inner: transport,
+            port: port.to_string(),
+            log: self.clone(),
+        })
+    }
+}
+
+/// [`Transport`] decorator that records traffic in a [`CaptureLog`].
+pub struct CaptureTransport {
+    inner: Box<dyn Transport>,
+    port: String,
+    log: CaptureLog,
+}
+
+impl Transport for CaptureTransport {
+    fn send_frame<'a>(&'a mut self, frame: &'a [u8]) -> TransportFuture<'a, ()> {
+        self.log
+            .record(&self.port, Direction::Tx, &describe_frame(frame));
+        self.inner.send_frame(frame)
+    }
+
+    fn receive_line(&mut self) -> TransportFuture<'_, String> {
+        Box::pin(async move {
+            let line = self.inner.receive_line().await?;
+            self.log.record(&self.port, Direction::Rx, &line);
+            Ok::<_, AppError>(line)
+        })
+    }
+
+    fn receive_unsolicited(&mut self) -> TransportFuture<'_, String> {
+        Box::pin(async move {
+            let line = self.inner.receive_unsolicited().await?;
+            self.log.record(&self.port, Direction::Rx, &line);
+            Ok(line)
+        })
+    }
+
+    fn reset(&mut self) -> TransportFuture<'_, ()> {
+        self.inner.reset()
+    }
+
+    fn describe(&self) -> String {
+        format!("{} (captured)", self.inner.describe())
+    }
+}
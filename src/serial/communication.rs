@@ -1,61 +1,193 @@
-use std::io::Write;
+use crate::error::AppError;
+use crate::protocol::binary::describe_frame;
+use crate::protocol::frame::{is_stale, split_sequence, verify_checksum, Checked, Framing};
+use crate::protocol::Event;
+use crate::serial::events::EventSink;
+use crate::serial::link::{LinkConfig, LinkStats};
+use crate::serial::transport::Transport;
 use std::time::Duration;
 
-pub fn send_data(port: &mut Box<dyn serialport::SerialPort>, data: &str) -> Result<String, String> {
-    port.write_all(b"<")
-        .and_then(|_| port.write_all(data.as_bytes()))
-        .and_then(|_| port.write_all(b">\n"))
-        .map_err(|e| format!("Fehler beim Senden: {}", e))?;
+/// Frames `data` as `<data>\n`, sends it and waits up to `deadline` for the reply line.
+pub async fn send_data(
+    transport: &mut dyn Transport,
+    data: &str,
+    deadline: Duration,
+) -> Result<String, AppError> {
+    let link = LinkConfig::default();
+    let stats = LinkStats::default();
+    send_command(
+        transport,
+        &link,
+        &stats,
+        &EventSink::default(),
+        0,
+        data,
+        deadline,
+    )
+    .await
+}
 
-    std::thread::sleep(Duration::from_millis(100));
-    read_serial(port, false)
+/// Sends `data` over `link` and returns the reply belonging to it.
+///
+/// In [`Framing::Sequenced`] mode lines without an id and late replies to
+/// earlier commands are logged and skipped; a reply carrying an id that has
+/// not been issued yet is reported as [`AppError::SequenceMismatch`].
+/// `EVENT` lines arriving in between are handed to `events` in any framing.
+///
+/// Each attempt may take up to `deadline`. After a timeout or a reply with a
+/// bad checksum the frame is sent again, at most `link.retries` times.
+pub async fn send_command(
+    transport: &mut dyn Transport,
+    link: &LinkConfig,
+    stats: &LinkStats,
+    events: &EventSink,
+    seq: u16,
+    data: &str,
+    deadline: Duration,
+) -> Result<String, AppError> {
+    let frame = link.frame(seq, data);
+    send_frame(transport, link, stats, events, seq, &frame, deadline).await
 }
 
-pub fn get_all_responses(port: &mut Box<dyn serialport::SerialPort>) -> String {
-    let mut response = String::new();
-    let mut buffer = [0u8; 1024];
+/// [`send_command`] for a frame that is already encoded, e.g. a binary LED
+/// batch.
+pub(crate) async fn send_frame(
+    transport: &mut dyn Transport,
+    link: &LinkConfig,
+    stats: &LinkStats,
+    events: &EventSink,
+    seq: u16,
+    frame: &[u8],
+    deadline: Duration,
+) -> Result<String, AppError> {
+    let mut attempt = 0;
 
     loop {
-        match port.read(&mut buffer) {
-            Ok(n) if n > 0 => {
-                if let Ok(line) = std::str::from_utf8(&buffer[..n]) {
-                    response.push_str(line);
+        transport.send_frame(frame).await?;
+        stats.frame_sent();
+
+        let result =
+            match tokio::time::timeout(deadline, read_reply(transport, link, stats, events, seq))
+                .await
+            {
+                Ok(result) => result,
+                Err(_) => {
+                    stats.timeout();
+                    Err(AppError::Timeout(deadline))
                 }
-                std::thread::sleep(Duration::from_millis(100));
+            };
+
+        match result {
+            Err(AppError::Timeout(_) | AppError::Checksum { .. }) if attempt < link.retries => {
+                attempt += 1;
+                stats.retransmission();
+                println!(
+                    "Retransmitting {} (attempt {})",
+                    describe_frame(frame),
+                    attempt + 1
+                );
             }
-            _ => break,
+            result => return result,
         }
     }
-    response
 }
 
-pub fn read_serial(
-    port: &mut Box<dyn serialport::SerialPort>,
-    print_response: bool,
-) -> Result<String, String> {
-    let mut buffer = Vec::with_capacity(256);
-    let mut temp_buf = [0u8; 1];
-
+async fn read_reply(
+    transport: &mut dyn Transport,
+    link: &LinkConfig,
+    stats: &LinkStats,
+    events: &EventSink,
+    seq: u16,
+) -> Result<String, AppError> {
     loop {
-        match port.read(&mut temp_buf) {
-            Ok(n) if n > 0 => {
-                buffer.push(temp_buf[0]);
-                if temp_buf[0] == b'\n' {
-                    let line = String::from_utf8_lossy(&buffer).to_string();
-                    if print_response {
-                        println!("Got from Arduino: {}", line);
-                    }
-                    return Ok(line.trim().to_string());
-                }
+        let line = transport.receive_line().await?;
+
+        let body = match (link.checksum, verify_checksum(&line)) {
+            (false, _) => line.as_str(),
+            (true, Checked::Valid(body)) => body,
+            // Firmware may print events before it has seen a checksummed frame.
+            (true, Checked::Missing) if Event::parse(&line).is_some() => line.as_str(),
+            // Unchecked noise can be skipped when replies are matched by id.
+            (true, Checked::Missing) if link.framing == Framing::Sequenced => {
+                println!("Discarding stray line: {}", line);
+                stats.discarded_line();
+                continue;
             }
-            Ok(_) => continue,
-            Err(ref e) if e.kind() == std::io::ErrorKind::TimedOut => {
-                if !buffer.is_empty() {
-                    return Ok(String::from_utf8_lossy(&buffer).trim().to_string());
-                }
-                return Err("Timeout".to_string());
+            (true, _) => {
+                stats.corrupted_reply();
+                return Err(AppError::Checksum { line });
+            }
+        };
+
+        if let Some(event) = Event::parse(body) {
+            stats.event_received();
+            events.publish(event);
+            continue;
+        }
+
+        if link.framing == Framing::Legacy {
+            stats.reply_received();
+            return Ok(body.to_string());
+        }
+
+        match split_sequence(body) {
+            (Some(id), reply) if id == seq => {
+                stats.reply_received();
+                return Ok(reply.to_string());
+            }
+            (Some(id), _) if is_stale(seq, id) => {
+                println!("Discarding late reply to #{}: {}", id, line);
+                stats.discarded_line();
+            }
+            (Some(id), _) => {
+                return Err(AppError::SequenceMismatch {
+                    expected: seq,
+                    received: id,
+                })
+            }
+            (None, _) => {
+                println!("Discarding stray line: {}", line);
+                stats.discarded_line();
             }
-            Err(e) => return Err(format!("Error while reading: {}", e)),
         }
     }
 }
+
+/// Handles a line that arrived while no command was waiting for a reply:
+/// events are published, anything else is logged and dropped.
+pub(crate) fn handle_unsolicited(
+    line: &str,
+    link: &LinkConfig,
+    stats: &LinkStats,
+    events: &EventSink,
+) {
+    let body = match (link.checksum, verify_checksum(line)) {
+        (false, _) | (true, Checked::Missing) => Some(line),
+        (true, Checked::Valid(body)) => Some(body),
+        (true, Checked::Corrupted) => None,
+    };
+    match body.and_then(Event::parse) {
+        Some(event) => {
+            stats.event_received();
+            events.publish(event);
+        }
+        None => {
+            println!("Discarding unsolicited line: {}", line);
+            stats.discarded_line();
+        }
+    }
+}
+
+pub async fn read_serial(
+    transport: &mut dyn Transport,
+    deadline: Duration,
+    print_response: bool,
+) -> Result<String, AppError> {
+    let line = tokio::time::timeout(deadline, transport.receive_line())
+        .await
+        .map_err(|_| AppError::Timeout(deadline))??;
+    if print_response {
+        println!("Got from Arduino: {}", line);
+    }
+    Ok(line)
+}
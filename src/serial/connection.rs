@@ -1,51 +1,206 @@
-use crate::config::{BAUD_RATE, MANUFACTURER, RECONNECT_INTERVAL, TIMEOUT};
+use crate::config::RECONNECT_INTERVAL;
+use crate::error::AppError;
 use crate::models::AppState;
-use crate::serial::communication::get_all_responses;
-use std::time::Duration;
-
-pub fn get_com_port() -> Option<String> {
-    let ports = serialport::available_ports().ok()?;
-    for port in ports {
-        if let serialport::SerialPortType::UsbPort(info) = &port.port_type {
-            if let Some(manufacturer) = &info.manufacturer {
-                if manufacturer == MANUFACTURER {
-                    return Some(port.port_name);
-                }
-            }
+use crate::protocol::FirmwareInfo;
+use crate::serial::actor::SerialHandle;
+use crate::serial::capture::CaptureLog;
+use crate::serial::discovery::DiscoveryConfig;
+use crate::serial::handshake::{detect_baud_rate, handshake};
+use crate::serial::health::{Backoff, ConnectionState};
+use crate::serial::link::LinkConfig;
+use crate::serial::replay::{captured_ports, load_capture, ReplayTransport};
+use crate::serial::transport::{SerialTransport, Transport};
+use std::time::Instant;
+
+/// A board that has been opened and has passed the handshake.
+pub struct Connection {
+    pub port: String,
+    pub transport: Box<dyn Transport>,
+    pub firmware: FirmwareInfo,
+    /// The rate the board answered at; `None` for replayed boards.
+    pub baud_rate: Option<u32>,
+}
+
+/// Opens and identifies every board found by the discovery rules.
+pub async fn connect_arduinos(
+    discovery: &DiscoveryConfig,
+    link: &LinkConfig,
+    capture: Option<&CaptureLog>,
+) -> Vec<Connection> {
+    let mut boards = Vec::new();
+    let rates = discovery.baud_rates(None);
+    for port_name in discovery.discover() {
+        if let Some(connection) = connect_port(&port_name, &rates, link, capture).await {
+            boards.push(connection);
         }
     }
-    None
+    boards
 }
 
-pub async fn connect_arduino() -> Option<Box<dyn serialport::SerialPort>> {
-    let port_name = get_com_port()?;
+/// Opens `port_name` and identifies the firmware, trying each of `rates`
+/// (see [`detect_baud_rate`]); boards that do not answer or fail the
+/// compatibility check are closed again. With a `capture` the traffic is
+/// recorded from the handshake on.
+pub async fn connect_port(
+    port_name: &str,
+    rates: &[u32],
+    link: &LinkConfig,
+    capture: Option<&CaptureLog>,
+) -> Option<Connection> {
     println!("Port: {}", port_name);
 
-    match serialport::new(&port_name, BAUD_RATE)
-        .timeout(TIMEOUT)
-        .open()
-    {
-        Ok(mut port) => {
-            tokio::time::sleep(Duration::from_secs(2)).await;
-            get_all_responses(&mut port);
-            println!("Arduino connected!");
-            Some(port)
+    let detected = detect_baud_rate(rates, link, |rate| open_port(port_name, rate, capture)).await;
+    match detected {
+        Ok((rate, transport, firmware)) => {
+            println!(
+                "Arduino connected via {} at {} baud: {}",
+                transport.describe(),
+                rate,
+                firmware
+            );
+            Some(Connection {
+                port: port_name.to_string(),
+                transport,
+                firmware,
+                baud_rate: Some(rate),
+            })
         }
         Err(e) => {
-            println!("[Error] while connecting with Arduino: {}", e);
+            println!(
+                "[Error] while connecting with Arduino on {}: {}",
+                port_name, e
+            );
             None
         }
     }
 }
 
-pub async fn monitor_arduino_connection(state: AppState) {
-    loop {
-        tokio::time::sleep(RECONNECT_INTERVAL).await;
+/// Opens `port_name` at `baud_rate`, wrapped in `capture` if given.
+fn open_port(
+    port_name: &str,
+    baud_rate: u32,
+    capture: Option<&CaptureLog>,
+) -> Result<Box<dyn Transport>, AppError> {
+    let transport =
+        SerialTransport::open(port_name, baud_rate).map_err(|e| AppError::Io(e.to_string()))?;
+    Ok(match capture {
+        Some(capture) => capture.wrap(port_name, Box::new(transport)),
+        None => Box::new(transport),
+    })
+}
 
-        let mut arduino = state.arduino.lock().await;
-        if arduino.is_none() {
-            println!("Try connecting with Arduino...");
-            *arduino = connect_arduino().await;
+/// [`connect_port`], reporting each step in the health of `board`.
+async fn connect_tracked(
+    port_name: &str,
+    rates: &[u32],
+    link: &LinkConfig,
+    capture: Option<&CaptureLog>,
+    board: &SerialHandle,
+) -> Result<Connection, AppError> {
+    let (rate, transport, firmware) = detect_baud_rate(rates, link, |rate| {
+        board.update_health(|health| health.enter(ConnectionState::Opening));
+        let transport = open_port(port_name, rate, capture)?;
+        board.update_health(|health| health.enter(ConnectionState::Handshaking));
+        Ok(transport)
+    })
+    .await?;
+    println!(
+        "Arduino connected via {} at {} baud: {}",
+        transport.describe(),
+        rate,
+        firmware
+    );
+    Ok(Connection {
+        port: port_name.to_string(),
+        transport,
+        firmware,
+        baud_rate: Some(rate),
+    })
+}
+
+/// One replayed board per port recorded in the capture at `path`.
+///
+/// The handshake is replayed as well; when the capture does not contain one
+/// (e.g. a rotated file) the board is treated as legacy firmware and the link
+/// settings come from the environment alone.
+pub async fn replay_arduinos(path: &str, link: &LinkConfig) -> Vec<Connection> {
+    let records = match load_capture(path) {
+        Ok(records) => records,
+        Err(e) => {
+            println!("[Error] while loading replay: {}", e);
+            return Vec::new();
         }
+    };
+
+    let mut boards = Vec::new();
+    for port in captured_ports(&records) {
+        let mut transport = ReplayTransport::new(&port, &records);
+        println!("Replaying {} commands of {}", transport.len(), port);
+        let firmware = match handshake(&mut transport, link).await {
+            Ok(firmware) => firmware,
+            Err(e) => {
+                println!("[Error] replayed handshake on {} failed: {}", port, e);
+                FirmwareInfo::legacy()
+            }
+        };
+        boards.push(Connection {
+            port,
+            transport: Box::new(transport),
+            firmware,
+            baud_rate: None,
+        });
+    }
+    boards
+}
+
+/// Tries to connect every discovered board that is down and due for an
+/// attempt, scheduling the next one with `backoff` on failure.
+///
+/// Returns when the next pass should run: after [`RECONNECT_INTERVAL`] to
+/// pick up newly plugged boards, or earlier if a retry is due before that.
+pub async fn reconnect(state: &AppState, backoff: &Backoff) -> Instant {
+    let ports = state.discovery.discover();
+    state.arduino.mark_missing(&ports);
+
+    for port_name in &ports {
+        let board = state.arduino.board(port_name);
+        if !board.health().is_due(Instant::now()) {
+            continue;
+        }
+        println!("Try connecting with Arduino on {}...", port_name);
+        let link = state.arduino.link();
+        // A board that answered before most likely still talks at that rate.
+        let rates = state.discovery.baud_rates(board.health().baud_rate);
+        let capture = state.capture.as_ref();
+        match connect_tracked(port_name, &rates, &link, capture, &board).await {
+            Ok(connection) => state.arduino.attach(connection).await,
+            Err(e) => {
+                let delay = backoff.delay(board.health().failures + 1);
+                println!(
+                    "[Error] connecting with Arduino on {} failed: {}; retrying in {:.1}s",
+                    port_name,
+                    e,
+                    delay.as_secs_f64()
+                );
+                board.update_health(|health| health.failed(&e, delay));
+            }
+        }
+    }
+
+    let next_pass = Instant::now() + RECONNECT_INTERVAL;
+    state
+        .arduino
+        .next_attempt()
+        .map_or(next_pass, |due| due.min(next_pass))
+}
+
+/// Keeps running [`reconnect`] passes: reopens lost boards with exponential
+/// backoff and picks up newly plugged ones.
+pub async fn monitor_arduino_connection(state: AppState) {
+    let backoff = Backoff::default();
+    let mut next_pass = Instant::now() + RECONNECT_INTERVAL;
+    loop {
+        tokio::time::sleep_until(next_pass.into()).await;
+        next_pass = reconnect(&state, &backoff).await;
     }
 }
@@ -0,0 +1,386 @@
+use crate::config::{
+    BAUD_ENV_VAR, BAUD_RATES, DISCOVERY_ENV_VAR, DISCOVERY_FILE, MANUFACTURER, PORT_ENV_VAR,
+};
+use serde::{Deserialize, Deserializer, Serialize};
+use serialport::{SerialPortInfo, SerialPortType};
+use std::fmt;
+use std::path::Path;
+
+/// One way of recognizing a board among the enumerated serial ports.
+///
+/// Every field that is set has to match; a rule without any field matches
+/// nothing. Strings are compared case-insensitively, `vid`/`pid` are hex.
+///
+/// ```json
+/// { "name": "CH340 clone", "vid": "1a86", "pid": "7523" }
+/// { "glob": "/dev/ttyACM*" }
+/// ```
+#[derive(Deserialize, Serialize, Clone, Debug, Default, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct DiscoveryRule {
+    /// Label shown in `GET /api/ports`; defaults to the rule's fields.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(
+        default,
+        deserialize_with = "hex_vid",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub vid: Option<u16>,
+    #[serde(
+        default,
+        deserialize_with = "hex_pid",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub pid: Option<u16>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub serial_number: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub manufacturer: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub product: Option<String>,
+    /// Exact port path; the port is tried even if enumeration misses it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+    /// Port path pattern with `*` and `?` wildcards.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub glob: Option<String>,
+}
+
+/// The discovery rules in effect, in priority order.
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct DiscoveryConfig {
+    pub rules: Vec<DiscoveryRule>,
+    /// Baud rates tried when opening a port, in order, until the board
+    /// answers the handshake.
+    #[serde(default = "default_baud_rates")]
+    pub baud_rates: Vec<u32>,
+}
+
+fn default_baud_rates() -> Vec<u32> {
+    BAUD_RATES.to_vec()
+}
+
+/// An enumerated serial port as reported by `GET /api/ports`.
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct PortInfo {
+    pub port: String,
+    /// `usb`, `pci`, `bluetooth`, `unknown`, or `configured` for explicit
+    /// paths that enumeration did not report.
+    pub kind: String,
+    pub vid: Option<String>,
+    pub pid: Option<String>,
+    pub serial_number: Option<String>,
+    pub manufacturer: Option<String>,
+    pub product: Option<String>,
+    /// The first rule that matched, if any.
+    pub matched_rule: Option<String>,
+    pub connected: bool,
+}
+
+impl Default for DiscoveryConfig {
+    /// Boards reporting the platform's [`MANUFACTURER`], plus the USB vendor
+    /// ids of genuine Arduino boards.
+    fn default() -> Self {
+        let vid = |name: &str, vid: u16| DiscoveryRule {
+            name: Some(name.to_string()),
+            vid: Some(vid),
+            ..DiscoveryRule::default()
+        };
+        Self {
+            rules: vec![
+                DiscoveryRule {
+                    manufacturer: Some(MANUFACTURER.to_string()),
+                    ..DiscoveryRule::default()
+                },
+                vid("Arduino SA", 0x2341),
+                vid("Arduino.org", 0x2a03),
+            ],
+            baud_rates: default_baud_rates(),
+        }
+    }
+}
+
+impl DiscoveryConfig {
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, String> {
+        let path = path.as_ref();
+        let content = std::fs::read_to_string(path)
+            .map_err(|e| format!("Cannot read discovery rules {}: {}", path.display(), e))?;
+        serde_json::from_str(&content)
+            .map_err(|e| format!("Invalid discovery rules {}: {}", path.display(), e))
+    }
+
+    /// Loads the file named by [`DISCOVERY_ENV_VAR`] (or [`DISCOVERY_FILE`]
+    /// if it exists), falling back to [`DiscoveryConfig::default`].
+    /// [`BAUD_ENV_VAR`] overrides the baud rates.
+    pub fn from_env() -> Self {
+        let mut config = Self::from_file();
+        if let Ok(rates) = std::env::var(BAUD_ENV_VAR) {
+            match parse_baud_rates(&rates) {
+                Ok(rates) => config.baud_rates = rates,
+                Err(e) => println!("[Error] {}; probing {:?}", e, config.baud_rates),
+            }
+        }
+        config
+    }
+
+    fn from_file() -> Self {
+        let path = match std::env::var(DISCOVERY_ENV_VAR) {
+            Ok(path) => path,
+            Err(_) if Path::new(DISCOVERY_FILE).exists() => DISCOVERY_FILE.to_string(),
+            Err(_) => return Self::default(),
+        };
+        match Self::load(&path) {
+            Ok(config) => {
+                println!(
+                    "Loaded {} discovery rules from {}",
+                    config.rules.len(),
+                    path
+                );
+                config
+            }
+            Err(e) => {
+                println!("[Error] {}; using default discovery rules", e);
+                Self::default()
+            }
+        }
+    }
+
+    /// The baud rates to try in order, `preferred` (e.g. the rate a board
+    /// used before) first.
+    pub fn baud_rates(&self, preferred: Option<u32>) -> Vec<u32> {
+        let mut rates: Vec<u32> = preferred.into_iter().collect();
+        for &rate in &self.baud_rates {
+            if !rates.contains(&rate) {
+                rates.push(rate);
+            }
+        }
+        if rates.is_empty() {
+            rates = default_baud_rates();
+        }
+        rates
+    }
+
+    /// The first rule matching `port`.
+    pub fn matching_rule(&self, port: &SerialPortInfo) -> Option<&DiscoveryRule> {
+        self.rules.iter().find(|rule| rule.matches(port))
+    }
+
+    /// Every enumerated port, plus configured paths enumeration did not report,
+    /// with the rule each one matched.
+    pub fn enumerate(&self) -> Vec<PortInfo> {
+        let mut ports: Vec<PortInfo> = serialport::available_ports()
+            .unwrap_or_default()
+            .iter()
+            .map(|port| PortInfo::new(port, self.matching_rule(port)))
+            .collect();
+
+        for rule in &self.rules {
+            let Some(path) = &rule.path else { continue };
+            if ports.iter().all(|port| &port.port != path) {
+                let port = SerialPortInfo {
+                    port_name: path.clone(),
+                    port_type: SerialPortType::Unknown,
+                };
+                let mut info = PortInfo::new(&port, self.matching_rule(&port));
+                info.kind = "configured".to_string();
+                ports.push(info);
+            }
+        }
+        ports
+    }
+
+    /// Paths of all ports that should be opened.
+    ///
+    /// [`PORT_ENV_VAR`] (comma separated) takes precedence over the rules.
+    pub fn discover(&self) -> Vec<String> {
+        if let Ok(port_names) = std::env::var(PORT_ENV_VAR) {
+            return port_names
+                .split(',')
+                .map(str::trim)
+                .filter(|name| !name.is_empty())
+                .map(str::to_string)
+                .collect();
+        }
+
+        self.enumerate()
+            .into_iter()
+            .filter(|port| port.matched_rule.is_some())
+            .map(|port| port.port)
+            .collect()
+    }
+}
+
+/// Parses a comma separated list of baud rates such as `115200,9600`.
+pub fn parse_baud_rates(text: &str) -> Result<Vec<u32>, String> {
+    let rates = text
+        .split(',')
+        .map(str::trim)
+        .filter(|rate| !rate.is_empty())
+        .map(|rate| {
+            rate.parse::<u32>()
+                .ok()
+                .filter(|rate| *rate > 0)
+                .ok_or_else(|| format!("'{}' is not a baud rate", rate))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    if rates.is_empty() {
+        return Err("no baud rate given".to_string());
+    }
+    Ok(rates)
+}
+
+impl DiscoveryRule {
+    pub fn matches(&self, port: &SerialPortInfo) -> bool {
+        let usb = match &port.port_type {
+            SerialPortType::UsbPort(info) => Some(info),
+            _ => None,
+        };
+        let text = |wanted: &Option<String>, actual: Option<&Option<String>>| match wanted {
+            None => true,
+            Some(wanted) => actual
+                .and_then(Option::as_deref)
+                .is_some_and(|actual| actual.eq_ignore_ascii_case(wanted)),
+        };
+
+        let has_criteria = self.vid.is_some()
+            || self.pid.is_some()
+            || self.serial_number.is_some()
+            || self.manufacturer.is_some()
+            || self.product.is_some()
+            || self.path.is_some()
+            || self.glob.is_some();
+
+        has_criteria
+            && self.vid.is_none_or(|vid| usb.is_some_and(|u| u.vid == vid))
+            && self.pid.is_none_or(|pid| usb.is_some_and(|u| u.pid == pid))
+            && text(&self.serial_number, usb.map(|u| &u.serial_number))
+            && text(&self.manufacturer, usb.map(|u| &u.manufacturer))
+            && text(&self.product, usb.map(|u| &u.product))
+            && self
+                .path
+                .as_ref()
+                .is_none_or(|path| *path == port.port_name)
+            && self
+                .glob
+                .as_ref()
+                .is_none_or(|glob| glob_match(glob, &port.port_name))
+    }
+}
+
+impl fmt::Display for DiscoveryRule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(name) = &self.name {
+            return f.write_str(name);
+        }
+        let mut parts = Vec::new();
+        if let Some(vid) = self.vid {
+            parts.push(format!("vid={:04x}", vid));
+        }
+        if let Some(pid) = self.pid {
+            parts.push(format!("pid={:04x}", pid));
+        }
+        let fields = [
+            ("serial_number", &self.serial_number),
+            ("manufacturer", &self.manufacturer),
+            ("product", &self.product),
+            ("path", &self.path),
+            ("glob", &self.glob),
+        ];
+        for (key, value) in fields {
+            if let Some(value) = value {
+                parts.push(format!("{}={}", key, value));
+            }
+        }
+        f.write_str(&parts.join(" "))
+    }
+}
+
+impl PortInfo {
+    fn new(port: &SerialPortInfo, rule: Option<&DiscoveryRule>) -> Self {
+        let (kind, usb) = match &port.port_type {
+            SerialPortType::UsbPort(info) => ("usb", Some(info)),
+            SerialPortType::PciPort => ("pci", None),
+            SerialPortType::BluetoothPort => ("bluetooth", None),
+            SerialPortType::Unknown => ("unknown", None),
+        };
+        Self {
+            port: port.port_name.clone(),
+            kind: kind.to_string(),
+            vid: usb.map(|u| format!("{:04x}", u.vid)),
+            pid: usb.map(|u| format!("{:04x}", u.pid)),
+            serial_number: usb.and_then(|u| u.serial_number.clone()),
+            manufacturer: usb.and_then(|u| u.manufacturer.clone()),
+            product: usb.and_then(|u| u.product.clone()),
+            matched_rule: rule.map(ToString::to_string),
+            connected: false,
+        }
+    }
+}
+
+/// Matches `text` against a pattern where `*` stands for any run of
+/// characters and `?` for exactly one.
+pub fn glob_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    let (mut p, mut t) = (0, 0);
+    let mut backtrack = None;
+
+    while t < text.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                backtrack = Some((p, t));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == text[t] => {
+                p += 1;
+                t += 1;
+            }
+            _ => match backtrack {
+                Some((star, matched)) => {
+                    p = star + 1;
+                    t = matched + 1;
+                    backtrack = Some((star, matched + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+fn hex_vid<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<u16>, D::Error> {
+    hex_id(deserializer, "vid")
+}
+
+fn hex_pid<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<u16>, D::Error> {
+    hex_id(deserializer, "pid")
+}
+
+/// Accepts `"2341"` or `"0x2341"`. Numbers are rejected: `2341` looks like
+/// the same id but would be read as decimal.
+fn hex_id<'de, D: Deserializer<'de>>(
+    deserializer: D,
+    field: &str,
+) -> Result<Option<u16>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Raw {
+        Text(String),
+        Number(serde_json::Number),
+    }
+
+    match Raw::deserialize(deserializer)? {
+        Raw::Number(value) => Err(serde::de::Error::custom(format!(
+            "{} must be a hex string, e.g. \"{}\" instead of {}",
+            field, value, value
+        ))),
+        Raw::Text(text) => {
+            let digits = text.trim_start_matches("0x").trim_start_matches("0X");
+            u16::from_str_radix(digits, 16)
+                .map(Some)
+                .map_err(|_| serde::de::Error::custom(format!("invalid hex {} '{}'", field, text)))
+        }
+    }
+}
@@ -0,0 +1,98 @@
+use crate::config::EVENT_CHANNEL_SIZE;
+use crate::protocol::Event;
+use crate::serial::boards::BoardConnection;
+use crate::serial::health::ConnectionHealth;
+use serde::Serialize;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::sync::broadcast;
+
+/// An [`Event`] together with the board that sent it.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct BoardEvent {
+    pub port: String,
+    /// Milliseconds since the Unix epoch at which the line was read.
+    pub timestamp_ms: u64,
+    #[serde(flatten)]
+    pub event: Event,
+}
+
+/// Broadcast channels carrying the unsolicited events and the connection
+/// state changes of all boards.
+///
+/// Subscribers that fall more than [`EVENT_CHANNEL_SIZE`] events behind miss
+/// the oldest ones (`RecvError::Lagged`); publishing never blocks the serial
+/// task.
+#[derive(Clone, Debug)]
+pub struct EventBus {
+    tx: broadcast::Sender<BoardEvent>,
+    connections: broadcast::Sender<BoardConnection>,
+}
+
+impl Default for EventBus {
+    fn default() -> Self {
+        let (tx, _) = broadcast::channel(EVENT_CHANNEL_SIZE);
+        let (connections, _) = broadcast::channel(EVENT_CHANNEL_SIZE);
+        Self { tx, connections }
+    }
+}
+
+impl EventBus {
+    pub fn subscribe(&self) -> broadcast::Receiver<BoardEvent> {
+        self.tx.subscribe()
+    }
+
+    /// Receives the health of a board each time its connection state changes.
+    pub fn subscribe_connections(&self) -> broadcast::Receiver<BoardConnection> {
+        self.connections.subscribe()
+    }
+
+    /// Announces that the board on `port` entered `health.state`.
+    pub fn publish_connection(&self, port: &str, health: ConnectionHealth) {
+        let _ = self.connections.send(BoardConnection {
+            port: port.to_string(),
+            health,
+        });
+    }
+
+    /// Sends `event` to every current subscriber; without subscribers it is
+    /// dropped.
+    pub fn publish(&self, port: &str, event: Event) {
+        let timestamp_ms = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_millis() as u64)
+            .unwrap_or_default();
+        let _ = self.tx.send(BoardEvent {
+            port: port.to_string(),
+            timestamp_ms,
+            event,
+        });
+    }
+}
+
+/// Where the serial task of one board publishes its events.
+#[derive(Clone, Debug)]
+pub struct EventSink {
+    pub port: String,
+    pub bus: EventBus,
+}
+
+impl Default for EventSink {
+    /// A sink of its own, for a board used outside [`crate::serial::Boards`].
+    fn default() -> Self {
+        Self {
+            port: "serial".to_string(),
+            bus: EventBus::default(),
+        }
+    }
+}
+
+impl EventSink {
+    pub fn publish(&self, event: Event) {
+        println!("Event from {}: {}", self.port, event);
+        self.bus.publish(&self.port, event);
+    }
+
+    pub fn publish_connection(&self, health: ConnectionHealth) {
+        self.bus.publish_connection(&self.port, health);
+    }
+}
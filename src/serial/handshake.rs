@@ -0,0 +1,139 @@
+use crate::config::{HANDSHAKE_ATTEMPTS, HANDSHAKE_TIMEOUT};
+use crate::error::AppError;
+use crate::protocol::frame::{split_sequence, verify_checksum, Checked};
+use crate::protocol::{Command, FirmwareInfo, Response};
+use crate::serial::link::LinkConfig;
+use crate::serial::transport::Transport;
+
+/// Identifies the board on a freshly opened transport.
+///
+/// `IDENTIFY()` is sent until the board answers, at most
+/// [`HANDSHAKE_ATTEMPTS`] times: boards reset when the port is opened and
+/// ignore input while the bootloader runs. Boot messages printed in between
+/// are skipped. Firmware that answers with `ERROR` predates the command and
+/// is reported as [`FirmwareInfo::legacy`], as is firmware that ignores it
+/// but answers a `SCAN()` sent after the last attempt.
+///
+/// Fails with [`AppError::IncompatibleFirmware`] if the board speaks an
+/// unsupported protocol revision or lacks a feature `link` requires.
+pub async fn handshake(
+    transport: &mut dyn Transport,
+    link: &LinkConfig,
+) -> Result<FirmwareInfo, AppError> {
+    let frame = link
+        .framing
+        .encode(0, &Command::Identify.encode(), link.checksum);
+
+    for attempt in 1..=HANDSHAKE_ATTEMPTS {
+        transport.reset().await?;
+        transport.send_frame(frame.as_bytes()).await?;
+
+        match tokio::time::timeout(HANDSHAKE_TIMEOUT, read_identity(transport)).await {
+            Ok(firmware) => {
+                let firmware = firmware?;
+                check(&firmware, link)?;
+                return Ok(firmware);
+            }
+            Err(_) => println!(
+                "No answer to IDENTIFY() (attempt {} of {})",
+                attempt, HANDSHAKE_ATTEMPTS
+            ),
+        }
+    }
+
+    let probe = Command::Scan;
+    let frame = link.framing.encode(0, &probe.encode(), link.checksum);
+    transport.reset().await?;
+    transport.send_frame(frame.as_bytes()).await?;
+    match tokio::time::timeout(probe.deadline(), read_scan(transport)).await {
+        Ok(scan) => {
+            scan?;
+            println!("Firmware ignores IDENTIFY() but answers SCAN()");
+            let firmware = FirmwareInfo::legacy();
+            check(&firmware, link)?;
+            Ok(firmware)
+        }
+        Err(_) => Err(AppError::Timeout(HANDSHAKE_TIMEOUT)),
+    }
+}
+
+/// Finds the baud rate the board talks at: opens the port with `open` at each
+/// of `rates` in turn and runs the [`handshake`], locking onto the first rate
+/// that gets a valid answer.
+///
+/// Only a board that stays silent (or prints nothing but noise, as it does
+/// at a wrong rate) moves on to the next rate; other failures, such as
+/// incompatible firmware, end the search.
+pub async fn detect_baud_rate(
+    rates: &[u32],
+    link: &LinkConfig,
+    mut open: impl FnMut(u32) -> Result<Box<dyn Transport>, AppError>,
+) -> Result<(u32, Box<dyn Transport>, FirmwareInfo), AppError> {
+    let mut last_error = AppError::Timeout(HANDSHAKE_TIMEOUT);
+    for &rate in rates {
+        let mut transport = open(rate)?;
+        match handshake(transport.as_mut(), link).await {
+            Ok(firmware) => return Ok((rate, transport, firmware)),
+            Err(e @ AppError::Timeout(_)) => {
+                if rates.len() > 1 {
+                    println!("No answer at {} baud", rate);
+                }
+                last_error = e;
+            }
+            Err(e) => return Err(e),
+        }
+    }
+    Err(last_error)
+}
+
+async fn read_identity(transport: &mut dyn Transport) -> Result<FirmwareInfo, AppError> {
+    loop {
+        let line = transport.receive_line().await?;
+        let body = match verify_checksum(&line) {
+            Checked::Valid(body) => body,
+            _ => line.as_str(),
+        };
+
+        match Response::parse(split_sequence(body).1) {
+            Response::Identify(payload) => {
+                return FirmwareInfo::from_value(payload).map_err(AppError::IncompatibleFirmware)
+            }
+            Response::Error { message, .. } => {
+                println!("Firmware does not identify itself ({})", message);
+                return Ok(FirmwareInfo::legacy());
+            }
+            _ => println!("Skipping boot message: {}", line),
+        }
+    }
+}
+
+/// Waits for a `SCAN:` reply, skipping boot messages and the noise a wrong
+/// baud rate produces.
+async fn read_scan(transport: &mut dyn Transport) -> Result<(), AppError> {
+    loop {
+        let line = transport.receive_line().await?;
+        let body = match verify_checksum(&line) {
+            Checked::Valid(body) => body,
+            _ => line.as_str(),
+        };
+        if let Response::Scan(_) = Response::parse(split_sequence(body).1) {
+            return Ok(());
+        }
+    }
+}
+
+fn check(firmware: &FirmwareInfo, link: &LinkConfig) -> Result<(), AppError> {
+    firmware
+        .check_compatible()
+        .map_err(AppError::IncompatibleFirmware)?;
+
+    let missing = link.unsupported(firmware);
+    if missing.is_empty() {
+        return Ok(());
+    }
+    Err(AppError::IncompatibleFirmware(format!(
+        "{} lacks {}, which the link settings require",
+        firmware,
+        missing.join(", ")
+    )))
+}
@@ -0,0 +1,196 @@
+use crate::config::{RECONNECT_INTERVAL, RECONNECT_JITTER, RECONNECT_MAX_INTERVAL};
+use crate::error::AppError;
+use serde::Serialize;
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// Where a board is in its connection life cycle.
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ConnectionState {
+    /// Waiting for the port to show up in discovery.
+    Searching,
+    /// Opening the serial port.
+    Opening,
+    /// Port open, waiting for the answer to `IDENTIFY()`.
+    Handshaking,
+    Connected,
+    /// Connected, but the link is unreliable: the last command needed a
+    /// retransmission or heartbeats went unanswered.
+    Degraded,
+    /// The last attempt failed; the next one is scheduled with backoff.
+    Failed,
+}
+
+/// Connection status of one board, as reported by `GET /api/connection`.
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct ConnectionHealth {
+    pub state: ConnectionState,
+    /// When the current state was entered, in milliseconds since the Unix epoch.
+    pub since_ms: u64,
+    /// Failed attempts since the board was last connected.
+    pub failures: u32,
+    /// Successful connections after the first one.
+    pub reconnects: u64,
+    pub last_error: Option<String>,
+    pub last_error_ms: Option<u64>,
+    /// When the monitor tries again, while [`ConnectionState::Failed`].
+    pub next_attempt_ms: Option<u64>,
+    /// The rate the board last answered the handshake at.
+    pub baud_rate: Option<u32>,
+    #[serde(skip)]
+    connections: u64,
+    #[serde(skip)]
+    next_attempt: Option<Instant>,
+}
+
+fn unix_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_millis() as u64)
+        .unwrap_or_default()
+}
+
+impl ConnectionHealth {
+    pub fn new(state: ConnectionState) -> Self {
+        let mut health = Self {
+            state,
+            since_ms: unix_millis(),
+            failures: 0,
+            reconnects: 0,
+            last_error: None,
+            last_error_ms: None,
+            next_attempt_ms: None,
+            baud_rate: None,
+            connections: 0,
+            next_attempt: None,
+        };
+        if state == ConnectionState::Connected {
+            health.connected();
+        }
+        health
+    }
+
+    /// Moves to `state`; entering the current state again changes nothing.
+    pub fn enter(&mut self, state: ConnectionState) {
+        if self.state != state {
+            self.state = state;
+            self.since_ms = unix_millis();
+        }
+    }
+
+    /// A connection has been established.
+    pub fn connected(&mut self) {
+        self.enter(ConnectionState::Connected);
+        self.connections += 1;
+        self.reconnects = self.connections - 1;
+        self.failures = 0;
+        self.next_attempt = None;
+        self.next_attempt_ms = None;
+    }
+
+    /// A connection attempt failed; the next one is due after `retry_in`.
+    pub fn failed(&mut self, error: &AppError, retry_in: Duration) {
+        self.failures += 1;
+        self.record_error(error);
+        self.enter(ConnectionState::Failed);
+        self.next_attempt = Some(Instant::now() + retry_in);
+        self.next_attempt_ms = Some(unix_millis() + retry_in.as_millis() as u64);
+    }
+
+    /// An established connection broke; reconnecting may start right away.
+    pub fn lost(&mut self, error: &AppError) {
+        self.record_error(error);
+        self.enter(ConnectionState::Failed);
+        self.next_attempt = None;
+        self.next_attempt_ms = None;
+    }
+
+    /// Whether a connection is established, healthy or not.
+    pub fn is_up(&self) -> bool {
+        matches!(
+            self.state,
+            ConnectionState::Connected | ConnectionState::Degraded
+        )
+    }
+
+    /// Whether the monitor should try to connect now.
+    pub fn is_due(&self, now: Instant) -> bool {
+        !self.is_up() && self.next_attempt.is_none_or(|at| at <= now)
+    }
+
+    /// When the next attempt is due, if one is scheduled.
+    pub fn next_attempt(&self) -> Option<Instant> {
+        self.next_attempt.filter(|_| !self.is_up())
+    }
+
+    fn record_error(&mut self, error: &AppError) {
+        self.last_error = Some(error.to_string());
+        self.last_error_ms = Some(unix_millis());
+    }
+}
+
+/// Combined state of all boards: connected only if every board is, degraded
+/// while at least one is up, otherwise the most advanced state any board is in.
+pub fn overall_state(boards: &[ConnectionHealth]) -> ConnectionState {
+    let any = |state: ConnectionState| boards.iter().any(|board| board.state == state);
+    if boards.is_empty() {
+        ConnectionState::Searching
+    } else if boards
+        .iter()
+        .all(|board| board.state == ConnectionState::Connected)
+    {
+        ConnectionState::Connected
+    } else if boards.iter().any(ConnectionHealth::is_up) {
+        ConnectionState::Degraded
+    } else if any(ConnectionState::Handshaking) {
+        ConnectionState::Handshaking
+    } else if any(ConnectionState::Opening) {
+        ConnectionState::Opening
+    } else if any(ConnectionState::Failed) {
+        ConnectionState::Failed
+    } else {
+        ConnectionState::Searching
+    }
+}
+
+/// Exponential backoff between connection attempts.
+///
+/// The `n`th consecutive failure waits `initial * 2^(n-1)`, at most `max`,
+/// scaled by a random factor in `1 ± jitter` so boards that failed together
+/// do not retry in lockstep.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Backoff {
+    pub initial: Duration,
+    pub max: Duration,
+    pub jitter: f64,
+}
+
+impl Default for Backoff {
+    fn default() -> Self {
+        Self {
+            initial: RECONNECT_INTERVAL,
+            max: RECONNECT_MAX_INTERVAL,
+            jitter: RECONNECT_JITTER,
+        }
+    }
+}
+
+impl Backoff {
+    /// Delay before retrying after `failures` consecutive failed attempts.
+    pub fn delay(&self, failures: u32) -> Duration {
+        let exponent = failures.saturating_sub(1).min(16);
+        let base = self.initial.saturating_mul(1 << exponent).min(self.max);
+        let factor = 1.0 + self.jitter * (2.0 * random_unit() - 1.0);
+        base.mul_f64(factor.max(0.0))
+    }
+}
+
+/// A number in `[0, 1)`, random enough to spread retries (every
+/// `RandomState` is seeded differently).
+fn random_unit() -> f64 {
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_u64(unix_millis());
+    (hasher.finish() >> 11) as f64 / (1u64 << 53) as f64
+}
@@ -0,0 +1,280 @@
+use crate::config::{
+    BINARY_ENV_VAR, CHECKSUM_ENV_VAR, HEARTBEAT_ENV_VAR, HEARTBEAT_INTERVAL, HEARTBEAT_MISSES,
+    HEARTBEAT_MISSES_ENV_VAR, MAX_RETRIES, RETRIES_ENV_VAR,
+};
+use crate::protocol::firmware::{FirmwareInfo, CAP_BINARY, CAP_CRC8, CAP_SEQUENCED};
+use crate::protocol::{BinaryFrame, Framing};
+use serde::Serialize;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// When the serial task probes an idle board with `PING()`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Heartbeat {
+    /// Idle time before a ping; `None` switches the heartbeat off.
+    pub interval: Option<Duration>,
+    /// Missed pings in a row after which the connection is dropped.
+    pub misses: u32,
+}
+
+impl Heartbeat {
+    /// Reads the heartbeat settings from the environment, defaulting to
+    /// [`HEARTBEAT_INTERVAL`] and [`HEARTBEAT_MISSES`].
+    pub fn from_env() -> Self {
+        let interval = match std::env::var(HEARTBEAT_ENV_VAR).map(|value| value.parse::<u64>()) {
+            Ok(Ok(0)) => None,
+            Ok(Ok(millis)) => Some(Duration::from_millis(millis)),
+            _ => Some(HEARTBEAT_INTERVAL),
+        };
+        let misses = std::env::var(HEARTBEAT_MISSES_ENV_VAR)
+            .ok()
+            .and_then(|value| value.parse().ok())
+            .filter(|misses| *misses > 0)
+            .unwrap_or(HEARTBEAT_MISSES);
+        Self { interval, misses }
+    }
+}
+
+/// How the serial task talks to the board.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct LinkConfig {
+    pub framing: Framing,
+    /// Append a CRC-8 suffix to every frame and require one on every reply.
+    pub checksum: bool,
+    /// How often a command is retransmitted after a timeout or corrupted reply.
+    pub retries: u32,
+    /// Off by default; [`LinkConfig::from_env`] switches it on.
+    pub heartbeat: Heartbeat,
+    /// Send [`BinaryFrame`]s. Only switched on by [`LinkConfig::negotiate`],
+    /// and only if requested (the default of [`LinkConfig::from_env`]).
+    pub binary: bool,
+}
+
+impl LinkConfig {
+    /// Reads the link settings from the environment.
+    ///
+    /// See [`crate::config::FRAMING_ENV_VAR`], [`CHECKSUM_ENV_VAR`] and
+    /// [`RETRIES_ENV_VAR`]; retries default to [`MAX_RETRIES`]. The heartbeat
+    /// is configured by [`Heartbeat::from_env`]. Binary frames are requested
+    /// unless [`BINARY_ENV_VAR`] is `off`.
+    pub fn from_env() -> Self {
+        let checksum = matches!(
+            std::env::var(CHECKSUM_ENV_VAR),
+            Ok(value) if value.eq_ignore_ascii_case("crc8")
+        );
+        let retries = std::env::var(RETRIES_ENV_VAR)
+            .ok()
+            .and_then(|value| value.parse().ok())
+            .unwrap_or(MAX_RETRIES);
+
+        Self {
+            framing: Framing::from_env(),
+            checksum,
+            retries,
+            heartbeat: Heartbeat::from_env(),
+            binary: !matches!(
+                std::env::var(BINARY_ENV_VAR),
+                Ok(value) if value.eq_ignore_ascii_case("off")
+            ),
+        }
+    }
+
+    /// The settings to use with `firmware`: features it announces are
+    /// switched on, features requested through the environment stay on.
+    ///
+    /// Binary frames need firmware announcing them; their replies always
+    /// carry an id and a checksum.
+    pub fn negotiate(self, firmware: &FirmwareInfo) -> Self {
+        if self.binary && firmware.supports(CAP_BINARY) {
+            return Self {
+                framing: Framing::Sequenced,
+                checksum: true,
+                ..self
+            };
+        }
+        let framing = if firmware.supports(CAP_SEQUENCED) {
+            Framing::Sequenced
+        } else {
+            self.framing
+        };
+        Self {
+            framing,
+            checksum: self.checksum || firmware.supports(CAP_CRC8),
+            binary: false,
+            ..self
+        }
+    }
+
+    /// The bytes to write for text `command` sent as frame `seq`.
+    pub fn frame(&self, seq: u16, command: &str) -> Vec<u8> {
+        if self.binary {
+            BinaryFrame::Command {
+                seq,
+                text: command.to_string(),
+            }
+            .encode()
+        } else {
+            self.framing
+                .encode(seq, command, self.checksum)
+                .into_bytes()
+        }
+    }
+
+    /// Features requested through the environment that `firmware` says it
+    /// does not have. For unidentified firmware the environment is trusted.
+    pub fn unsupported(&self, firmware: &FirmwareInfo) -> Vec<&'static str> {
+        let mut missing = Vec::new();
+        if firmware.identified {
+            if self.framing == Framing::Sequenced && !firmware.supports(CAP_SEQUENCED) {
+                missing.push(CAP_SEQUENCED);
+            }
+            if self.checksum && !firmware.supports(CAP_CRC8) {
+                missing.push(CAP_CRC8);
+            }
+        }
+        missing
+    }
+}
+
+/// Counters describing the health of the serial link since startup.
+#[derive(Debug, Default)]
+pub struct LinkStats {
+    frames_sent: AtomicU64,
+    replies_received: AtomicU64,
+    corrupted_replies: AtomicU64,
+    retransmissions: AtomicU64,
+    timeouts: AtomicU64,
+    discarded_lines: AtomicU64,
+    events: AtomicU64,
+    heartbeats_sent: AtomicU64,
+    heartbeats_missed: AtomicU64,
+    round_trips: Mutex<RoundTrips>,
+}
+
+/// Round-trip times of answered heartbeats.
+#[derive(Debug, Default)]
+struct RoundTrips {
+    count: u64,
+    total: Duration,
+    last: Option<Duration>,
+    min: Option<Duration>,
+    max: Option<Duration>,
+}
+
+/// Heartbeat settings and round-trip statistics, in milliseconds.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct HeartbeatSnapshot {
+    /// `None` while the heartbeat is off.
+    pub interval_ms: Option<u64>,
+    pub miss_threshold: u32,
+    pub sent: u64,
+    pub missed: u64,
+    pub last_rtt_ms: Option<f64>,
+    pub min_rtt_ms: Option<f64>,
+    pub max_rtt_ms: Option<f64>,
+    pub avg_rtt_ms: Option<f64>,
+}
+
+/// Point-in-time copy of [`LinkStats`], as returned by `GET /api/serial/stats`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct LinkStatsSnapshot {
+    pub framing: Framing,
+    pub binary: bool,
+    pub checksum: bool,
+    pub max_retries: u32,
+    pub frames_sent: u64,
+    pub replies_received: u64,
+    pub corrupted_replies: u64,
+    pub retransmissions: u64,
+    pub timeouts: u64,
+    pub discarded_lines: u64,
+    /// Unsolicited `EVENT` lines received.
+    pub events: u64,
+    pub heartbeat: HeartbeatSnapshot,
+}
+
+impl LinkStats {
+    pub(crate) fn frame_sent(&self) {
+        self.frames_sent.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn reply_received(&self) {
+        self.replies_received.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn corrupted_reply(&self) {
+        self.corrupted_replies.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn retransmission(&self) {
+        self.retransmissions.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn retransmissions(&self) -> u64 {
+        self.retransmissions.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn heartbeat_sent(&self) {
+        self.heartbeats_sent.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn heartbeat_missed(&self) {
+        self.heartbeats_missed.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn heartbeat_answered(&self, round_trip: Duration) {
+        let mut trips = self.round_trips.lock().unwrap();
+        trips.count += 1;
+        trips.total += round_trip;
+        trips.last = Some(round_trip);
+        trips.min = Some(trips.min.map_or(round_trip, |min| min.min(round_trip)));
+        trips.max = Some(trips.max.map_or(round_trip, |max| max.max(round_trip)));
+    }
+
+    pub(crate) fn timeout(&self) {
+        self.timeouts.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn discarded_line(&self) {
+        self.discarded_lines.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn event_received(&self) {
+        self.events.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn snapshot(&self, config: &LinkConfig) -> LinkStatsSnapshot {
+        LinkStatsSnapshot {
+            framing: config.framing,
+            binary: config.binary,
+            checksum: config.checksum,
+            max_retries: config.retries,
+            frames_sent: self.frames_sent.load(Ordering::Relaxed),
+            replies_received: self.replies_received.load(Ordering::Relaxed),
+            corrupted_replies: self.corrupted_replies.load(Ordering::Relaxed),
+            retransmissions: self.retransmissions.load(Ordering::Relaxed),
+            timeouts: self.timeouts.load(Ordering::Relaxed),
+            discarded_lines: self.discarded_lines.load(Ordering::Relaxed),
+            events: self.events.load(Ordering::Relaxed),
+            heartbeat: self.heartbeat_snapshot(&config.heartbeat),
+        }
+    }
+
+    fn heartbeat_snapshot(&self, heartbeat: &Heartbeat) -> HeartbeatSnapshot {
+        let trips = self.round_trips.lock().unwrap();
+        let millis = |duration: Duration| duration.as_secs_f64() * 1000.0;
+        HeartbeatSnapshot {
+            interval_ms: heartbeat
+                .interval
+                .map(|interval| interval.as_millis() as u64),
+            miss_threshold: heartbeat.misses,
+            sent: self.heartbeats_sent.load(Ordering::Relaxed),
+            missed: self.heartbeats_missed.load(Ordering::Relaxed),
+            last_rtt_ms: trips.last.map(millis),
+            min_rtt_ms: trips.min.map(millis),
+            max_rtt_ms: trips.max.map(millis),
+            avg_rtt_ms: (trips.count > 0).then(|| millis(trips.total) / trips.count as f64),
+        }
+    }
+}
@@ -0,0 +1,135 @@
+use crate::error::AppError;
+use crate::protocol::binary::describe_frame;
+use crate::serial::transport::{Transport, TransportFuture};
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use tokio::sync::Notify;
+
+/// One scripted reaction of a [`MockTransport`] to `receive_line`.
+#[derive(Clone, Debug)]
+pub enum MockReply {
+    /// The device answers with this line.
+    Line(String),
+    /// The device stays silent, so the caller's deadline expires.
+    Timeout,
+    /// The read fails with this I/O error message.
+    Failure(String),
+}
+
+#[derive(Default)]
+struct MockInner {
+    script: VecDeque<MockReply>,
+    unsolicited: VecDeque<String>,
+    sent: Vec<String>,
+    resets: usize,
+}
+
+/// In-memory [`Transport`] that answers from a script.
+///
+/// Clones share the same script and log, so a test can keep one handle while
+/// the other one is moved into `AppState`. Once the script runs out the
+/// device stays silent.
+///
+/// Lines queued with [`MockTransport::push_unsolicited`] are what the device
+/// prints on its own; they are only read while no command is in flight.
+#[derive(Clone, Default)]
+pub struct MockTransport {
+    inner: Arc<Mutex<MockInner>>,
+    wake: Arc<Notify>,
+}
+
+impl MockTransport {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Queues a line the device will answer with.
+    pub fn push_reply(&self, line: impl Into<String>) -> &Self {
+        self.push(MockReply::Line(line.into()))
+    }
+
+    /// Queues a read that times out.
+    pub fn push_timeout(&self) -> &Self {
+        self.push(MockReply::Timeout)
+    }
+
+    /// Queues a read that fails with an I/O error.
+    pub fn push_failure(&self, message: impl Into<String>) -> &Self {
+        self.push(MockReply::Failure(message.into()))
+    }
+
+    /// Queues a line the device sends while idle, e.g. an `EVENT`.
+    pub fn push_unsolicited(&self, line: impl Into<String>) -> &Self {
+        self.inner
+            .lock()
+            .unwrap()
+            .unsolicited
+            .push_back(line.into());
+        self.wake.notify_one();
+        self
+    }
+
+    pub fn push(&self, reply: MockReply) -> &Self {
+        self.inner.lock().unwrap().script.push_back(reply);
+        self
+    }
+
+    /// Every frame written so far, decoded as text; binary frames as
+    /// [`describe_frame`] shows them.
+    pub fn sent(&self) -> Vec<String> {
+        self.inner.lock().unwrap().sent.clone()
+    }
+
+    /// How often `reset` has been called.
+    pub fn resets(&self) -> usize {
+        self.inner.lock().unwrap().resets
+    }
+
+    /// Number of scripted replies that have not been consumed yet.
+    pub fn pending(&self) -> usize {
+        self.inner.lock().unwrap().script.len()
+    }
+}
+
+impl Transport for MockTransport {
+    fn send_frame<'a>(&'a mut self, frame: &'a [u8]) -> TransportFuture<'a, ()> {
+        let text = match frame.first() {
+            Some(0) => describe_frame(frame),
+            _ => String::from_utf8_lossy(frame).to_string(),
+        };
+        self.inner.lock().unwrap().sent.push(text);
+        Box::pin(async { Ok(()) })
+    }
+
+    fn receive_line(&mut self) -> TransportFuture<'_, String> {
+        let reply = self.inner.lock().unwrap().script.pop_front();
+        Box::pin(async move {
+            match reply {
+                Some(MockReply::Line(line)) => Ok(line),
+                Some(MockReply::Failure(message)) => Err(AppError::Io(message)),
+                Some(MockReply::Timeout) | None => std::future::pending().await,
+            }
+        })
+    }
+
+    fn receive_unsolicited(&mut self) -> TransportFuture<'_, String> {
+        Box::pin(async move {
+            loop {
+                let woken = self.wake.notified();
+                if let Some(line) = self.inner.lock().unwrap().unsolicited.pop_front() {
+                    return Ok(line);
+                }
+                woken.await;
+            }
+        })
+    }
+
+    fn reset(&mut self) -> TransportFuture<'_, ()> {
+        self.inner.lock().unwrap().resets += 1;
+        Box::pin(async { Ok(()) })
+    }
+
+    fn describe(&self) -> String {
+        "mock transport".to_string()
+    }
+}
@@ -1,5 +1,33 @@
+pub mod actor;
+pub mod boards;
+pub mod capture;
 pub mod communication;
 pub mod connection;
+pub mod discovery;
+pub mod events;
+pub mod handshake;
+pub mod health;
+pub mod link;
+pub mod mock;
+pub mod replay;
+pub mod transport;
 
+#[cfg(test)]
+mod tests;
+
+pub use actor::SerialHandle;
+pub use boards::{BoardConnection, BoardStatus, Boards, ConnectionStatus, ScanFailure};
+pub use capture::{CaptureLog, CaptureRecord, Direction};
 pub use communication::send_data;
-pub use connection::{connect_arduino, monitor_arduino_connection};
+pub use connection::{
+    connect_arduinos, connect_port, monitor_arduino_connection, reconnect, replay_arduinos,
+    Connection,
+};
+pub use discovery::{DiscoveryConfig, DiscoveryRule, PortInfo};
+pub use events::{BoardEvent, EventBus, EventSink};
+pub use handshake::{detect_baud_rate, handshake};
+pub use health::{Backoff, ConnectionHealth, ConnectionState};
+pub use link::{LinkConfig, LinkStats, LinkStatsSnapshot};
+pub use mock::MockTransport;
+pub use replay::{load_capture, ReplayTransport};
+pub use transport::{SerialTransport, Transport};
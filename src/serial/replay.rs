@@ -0,0 +1,190 @@
+use crate::protocol::binary::describe_frame;
+use crate::protocol::frame::{
+    append_checksum, split_sequence, verify_checksum, with_sequence, Checked,
+};
+use crate::serial::capture::{CaptureRecord, Direction};
+use crate::serial::transport::{Transport, TransportFuture};
+use std::collections::VecDeque;
+use std::path::Path;
+
+/// A command from the capture and the lines the board sent until the next one.
+struct Exchange {
+    command: String,
+    replies: Vec<String>,
+}
+
+/// [`Transport`] that plays a captured session back as if it were the board.
+///
+/// Each frame the server sends is answered with the lines the board sent
+/// after the next matching command in the capture (wrapping around, so
+/// repeated polls keep working). Sequence ids and checksums in the replies
+/// are rewritten to fit the frame actually sent. Frames the capture has no
+/// answer for are rejected with an `ERROR` line, like unknown commands on the
+/// firmware.
+pub struct ReplayTransport {
+    port: String,
+    exchanges: Vec<Exchange>,
+    cursor: usize,
+    pending: VecDeque<String>,
+}
+
+/// Reads a capture file written by [`crate::serial::CaptureLog`].
+pub fn load_capture(path: impl AsRef<Path>) -> Result<Vec<CaptureRecord>, String> {
+    let path = path.as_ref();
+    let content = std::fs::read_to_string(path)
+        .map_err(|e| format!("Cannot read capture {}: {}", path.display(), e))?;
+    content
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .enumerate()
+        .map(|(index, line)| {
+            serde_json::from_str(line)
+                .map_err(|e| format!("{} line {}: {}", path.display(), index + 1, e))
+        })
+        .collect()
+}
+
+/// The ports appearing in `records`, in order of first appearance.
+pub fn captured_ports(records: &[CaptureRecord]) -> Vec<String> {
+    let mut ports: Vec<String> = Vec::new();
+    for record in records {
+        if !ports.contains(&record.port) {
+            ports.push(record.port.clone());
+        }
+    }
+    ports
+}
+
+impl ReplayTransport {
+    /// Replays the traffic of `port` in `records`. Lines received before the
+    /// first command (boot messages) are dropped.
+    pub fn new(port: &str, records: &[CaptureRecord]) -> Self {
+        let mut exchanges: Vec<Exchange> = Vec::new();
+        for record in records.iter().filter(|record| record.port == port) {
+            match record.dir {
+                Direction::Tx => exchanges.push(Exchange {
+                    command: parse_frame(&record.data).command,
+                    replies: Vec::new(),
+                }),
+                Direction::Rx => {
+                    if let Some(exchange) = exchanges.last_mut() {
+                        exchange.replies.push(record.data.clone());
+                    }
+                }
+            }
+        }
+        Self {
+            port: port.to_string(),
+            exchanges,
+            cursor: 0,
+            pending: VecDeque::new(),
+        }
+    }
+
+    /// Number of captured commands available for replay.
+    pub fn len(&self) -> usize {
+        self.exchanges.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.exchanges.is_empty()
+    }
+
+    fn find(&self, command: &str) -> Option<usize> {
+        let count = self.exchanges.len();
+        (0..count)
+            .map(|offset| (self.cursor + offset) % count)
+            .find(|&index| self.exchanges[index].command == command)
+    }
+}
+
+/// A frame as sent by the server.
+struct Frame {
+    seq: Option<u16>,
+    command: String,
+    /// Whether replies to it carry a checksum.
+    checked: bool,
+}
+
+/// Splits a frame such as `<#4 STOP()*1C>`, or a binary one recorded as
+/// `bin #4 STOP()`, into its sequence id and command.
+fn parse_frame(frame: &str) -> Frame {
+    let frame = frame.trim();
+    let binary = frame.strip_prefix("bin ");
+    let body = binary
+        .unwrap_or(frame)
+        .trim_start_matches('<')
+        .trim_end_matches('>');
+    let (body, checked) = match verify_checksum(body) {
+        Checked::Valid(body) => (body, true),
+        _ => (body, binary.is_some()),
+    };
+    let (seq, command) = split_sequence(body);
+    Frame {
+        seq,
+        command: command.to_string(),
+        checked,
+    }
+}
+
+/// Gives a captured reply the sequence id of the frame being answered.
+fn readdress(line: &str, seq: Option<u16>) -> String {
+    let (body, checked) = match verify_checksum(line) {
+        Checked::Valid(body) => (body, true),
+        _ => (line, false),
+    };
+    let body = match split_sequence(body) {
+        (Some(_), reply) => with_sequence(seq, reply),
+        (None, _) => body.to_string(),
+    };
+    if checked {
+        append_checksum(&body)
+    } else {
+        body
+    }
+}
+
+impl Transport for ReplayTransport {
+    fn send_frame<'a>(&'a mut self, frame: &'a [u8]) -> TransportFuture<'a, ()> {
+        let frame = parse_frame(&describe_frame(frame));
+        match self.find(&frame.command) {
+            Some(index) => {
+                let replies = self.exchanges[index]
+                    .replies
+                    .iter()
+                    .map(|line| readdress(line, frame.seq));
+                self.pending.extend(replies);
+                self.cursor = index + 1;
+            }
+            None => {
+                println!("[Replay] {} has no answer to {}", self.port, frame.command);
+                let error = format!("ERROR: no recorded answer to {}", frame.command);
+                let error = with_sequence(frame.seq, &error);
+                self.pending.push_back(if frame.checked {
+                    append_checksum(&error)
+                } else {
+                    error
+                });
+            }
+        }
+        Box::pin(async { Ok(()) })
+    }
+
+    fn receive_line(&mut self) -> TransportFuture<'_, String> {
+        Box::pin(async move {
+            match self.pending.pop_front() {
+                Some(line) => Ok(line),
+                None => std::future::pending().await,
+            }
+        })
+    }
+
+    fn reset(&mut self) -> TransportFuture<'_, ()> {
+        self.pending.clear();
+        Box::pin(async { Ok(()) })
+    }
+
+    fn describe(&self) -> String {
+        format!("replay of {}", self.port)
+    }
+}
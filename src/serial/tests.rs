@@ -0,0 +1,1325 @@
+//! Tests for serial framing and communication
+
+#[cfg(test)]
+mod line_buffer_tests {
+    use crate::serial::transport::LineBuffer;
+
+    #[test]
+    fn test_next_line_waits_for_newline() {
+        let mut lines = LineBuffer::default();
+        lines.extend(b"OK: Upd");
+        assert_eq!(lines.next_line(), None);
+        lines.extend(b"ated\r\n");
+        assert_eq!(lines.next_line(), Some("OK: Updated".to_string()));
+        assert_eq!(lines.next_line(), None);
+    }
+
+    #[test]
+    fn test_next_line_splits_chunks() {
+        let mut lines = LineBuffer::default();
+        lines.extend(b"OK: Stopped\r\nSCAN: []\r\nOK");
+        assert_eq!(lines.next_line(), Some("OK: Stopped".to_string()));
+        assert_eq!(lines.next_line(), Some("SCAN: []".to_string()));
+        assert_eq!(lines.next_line(), None);
+    }
+
+    #[test]
+    fn test_next_line_skips_empty_lines() {
+        let mut lines = LineBuffer::default();
+        lines.extend(b"\r\n\n  \r\nOK\n");
+        assert_eq!(lines.next_line(), Some("OK".to_string()));
+    }
+
+    #[test]
+    fn test_clear_discards_partial_line() {
+        let mut lines = LineBuffer::default();
+        lines.extend(b"boot noi");
+        lines.clear();
+        lines.extend(b"OK\n");
+        assert_eq!(lines.next_line(), Some("OK".to_string()));
+    }
+}
+
+#[cfg(test)]
+mod communication_tests {
+    use crate::error::AppError;
+    use crate::protocol::frame::append_checksum;
+    use crate::protocol::{Event, Framing};
+    use crate::serial::communication::{send_command, send_data};
+    use crate::serial::{EventSink, LinkConfig, LinkStats};
+
+    fn sequenced() -> LinkConfig {
+        LinkConfig {
+            framing: Framing::Sequenced,
+            ..LinkConfig::default()
+        }
+    }
+    use crate::serial::MockTransport;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_send_data_frames_command() {
+        let mock = MockTransport::new();
+        mock.push_reply("OK: Updated");
+        let mut transport = mock.clone();
+
+        let response = send_data(
+            &mut transport,
+            "UPDATE(1, 2, 3, 4, 1)",
+            Duration::from_secs(1),
+        )
+        .await;
+        assert_eq!(response, Ok("OK: Updated".to_string()));
+        assert_eq!(mock.sent(), vec!["<UPDATE(1, 2, 3, 4, 1)>\n"]);
+    }
+
+    #[tokio::test]
+    async fn test_send_data_deadline() {
+        let mock = MockTransport::new();
+        mock.push_timeout();
+        let mut transport = mock.clone();
+
+        let response = send_data(&mut transport, "SCAN()", Duration::from_millis(20)).await;
+        assert_eq!(response, Err(AppError::Timeout(Duration::from_millis(20))));
+    }
+
+    #[tokio::test]
+    async fn test_send_data_read_failure() {
+        let mock = MockTransport::new();
+        mock.push_failure("Error while reading: device disconnected");
+        let mut transport = mock.clone();
+
+        let response = send_data(&mut transport, "STOP()", Duration::from_secs(1)).await;
+        assert_eq!(
+            response,
+            Err(AppError::Io(
+                "Error while reading: device disconnected".to_string()
+            ))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_sequenced_skips_stray_and_late_lines() {
+        let mock = MockTransport::new();
+        mock.push_reply("Booting...")
+            .push_reply("#4 OK: Updated")
+            .push_reply("#5 OK: Stopped");
+        let mut transport = mock.clone();
+
+        let response = send_command(
+            &mut transport,
+            &sequenced(),
+            &LinkStats::default(),
+            &EventSink::default(),
+            5,
+            "STOP()",
+            Duration::from_secs(1),
+        )
+        .await;
+        assert_eq!(response, Ok("OK: Stopped".to_string()));
+        assert_eq!(mock.sent(), vec!["<#5 STOP()>\n"]);
+    }
+
+    #[tokio::test]
+    async fn test_events_are_not_taken_for_replies() {
+        let mock = MockTransport::new();
+        mock.push_reply(r#"EVENT UNPLUG: {"EEPROM":3}"#)
+            .push_reply("OK: Stopped");
+        let mut transport = mock.clone();
+        let stats = LinkStats::default();
+        let events = EventSink::default();
+        let mut received = events.bus.subscribe();
+
+        let response = send_command(
+            &mut transport,
+            &LinkConfig::default(),
+            &stats,
+            &events,
+            1,
+            "STOP()",
+            Duration::from_secs(1),
+        )
+        .await;
+        assert_eq!(response, Ok("OK: Stopped".to_string()));
+        assert_eq!(
+            received.try_recv().unwrap().event,
+            Event::ModuleUnplugged { eeprom: 3 }
+        );
+        assert_eq!(stats.snapshot(&LinkConfig::default()).events, 1);
+    }
+
+    #[tokio::test]
+    async fn test_unchecked_events_accepted_with_checksums() {
+        let mock = MockTransport::new();
+        mock.push_reply(r#"EVENT BUTTON: {"EEPROM":3,"BUTTON":1}"#)
+            .push_reply(append_checksum("#2 OK: Stopped"));
+        let mut transport = mock.clone();
+        let link = LinkConfig {
+            checksum: true,
+            ..sequenced()
+        };
+        let events = EventSink::default();
+        let mut received = events.bus.subscribe();
+
+        let response = send_command(
+            &mut transport,
+            &link,
+            &LinkStats::default(),
+            &events,
+            2,
+            "STOP()",
+            Duration::from_secs(1),
+        )
+        .await;
+        assert_eq!(response, Ok("OK: Stopped".to_string()));
+        assert!(received.try_recv().is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_sequenced_reports_mismatch() {
+        let mock = MockTransport::new();
+        mock.push_reply("#9 OK: Stopped");
+        let mut transport = mock.clone();
+
+        let response = send_command(
+            &mut transport,
+            &sequenced(),
+            &LinkStats::default(),
+            &EventSink::default(),
+            5,
+            "STOP()",
+            Duration::from_secs(1),
+        )
+        .await;
+        assert_eq!(
+            response,
+            Err(AppError::SequenceMismatch {
+                expected: 5,
+                received: 9
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_checksum_frames_and_replies() {
+        let mock = MockTransport::new();
+        mock.push_reply(append_checksum("OK: Stopped"));
+        let mut transport = mock.clone();
+        let link = LinkConfig {
+            checksum: true,
+            ..LinkConfig::default()
+        };
+
+        let response = send_command(
+            &mut transport,
+            &link,
+            &LinkStats::default(),
+            &EventSink::default(),
+            1,
+            "STOP()",
+            Duration::from_secs(1),
+        )
+        .await;
+        assert_eq!(response, Ok("OK: Stopped".to_string()));
+        assert_eq!(
+            mock.sent(),
+            vec![format!("<{}>\n", append_checksum("STOP()"))]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_corrupted_reply_is_retransmitted() {
+        let mock = MockTransport::new();
+        mock.push_reply("#1 OK: Stopped*00")
+            .push_timeout()
+            .push_reply(append_checksum("#1 OK: Stopped"));
+        let mut transport = mock.clone();
+        let link = LinkConfig {
+            framing: Framing::Sequenced,
+            checksum: true,
+            retries: 2,
+            ..LinkConfig::default()
+        };
+        let stats = LinkStats::default();
+
+        let response = send_command(
+            &mut transport,
+            &link,
+            &stats,
+            &EventSink::default(),
+            1,
+            "STOP()",
+            Duration::from_millis(20),
+        )
+        .await;
+        assert_eq!(response, Ok("OK: Stopped".to_string()));
+        assert_eq!(mock.sent().len(), 3);
+
+        let snapshot = stats.snapshot(&link);
+        assert_eq!(snapshot.frames_sent, 3);
+        assert_eq!(snapshot.corrupted_replies, 1);
+        assert_eq!(snapshot.timeouts, 1);
+        assert_eq!(snapshot.retransmissions, 2);
+        assert_eq!(snapshot.replies_received, 1);
+    }
+
+    #[tokio::test]
+    async fn test_retries_are_bounded() {
+        let mock = MockTransport::new();
+        mock.push_reply("OK*00").push_reply("OK*00");
+        let mut transport = mock.clone();
+        let link = LinkConfig {
+            checksum: true,
+            retries: 1,
+            ..LinkConfig::default()
+        };
+
+        let response = send_command(
+            &mut transport,
+            &link,
+            &LinkStats::default(),
+            &EventSink::default(),
+            1,
+            "SCAN()",
+            Duration::from_secs(1),
+        )
+        .await;
+        assert_eq!(
+            response,
+            Err(AppError::Checksum {
+                line: "OK*00".to_string()
+            })
+        );
+        assert_eq!(mock.sent().len(), 2);
+    }
+}
+
+#[cfg(test)]
+mod boards_tests {
+    use crate::error::AppError;
+    use crate::protocol::{Command, Event, FirmwareInfo, Response};
+    use crate::serial::{Boards, Connection, MockTransport, SerialHandle};
+    use serde_json::json;
+    use std::time::Duration;
+
+    fn two_boards(left: &MockTransport, right: &MockTransport) -> Boards {
+        let boards = Boards::single("left", SerialHandle::spawn(Some(Box::new(left.clone()))));
+        boards.insert("right", SerialHandle::spawn(Some(Box::new(right.clone()))));
+        boards
+    }
+
+    fn led(led_id: i32) -> Command {
+        Command::Led {
+            led_id,
+            forward: true,
+            color: (255, 0, 0),
+            pulse_frequenz: 1,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_idle_events_are_published_with_port() {
+        let left = MockTransport::new();
+        let right = MockTransport::new();
+        let boards = two_boards(&left, &right);
+        let mut events = boards.subscribe();
+
+        right.push_unsolicited("Brown-out detected");
+        right.push_unsolicited(r#"EVENT PLUG: {"EEPROM":7,"I2C":38,"TYPE":4}"#);
+        let received = tokio::time::timeout(Duration::from_secs(1), events.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(received.port, "right");
+        assert_eq!(
+            received.event,
+            Event::ModulePlugged {
+                eeprom: 7,
+                i2c: 38,
+                module_type: 4
+            }
+        );
+
+        let stats = &boards.statuses()[1].link;
+        assert_eq!((stats.events, stats.discarded_lines), (1, 1));
+    }
+
+    #[tokio::test]
+    async fn test_idle_reads_leave_replies_alone() {
+        let mock = MockTransport::new();
+        mock.push_reply("OK: Stopped");
+        let boards = Boards::single("mock", SerialHandle::spawn(Some(Box::new(mock.clone()))));
+        tokio::time::sleep(Duration::from_millis(20)).await;
+
+        assert_eq!(
+            boards.send(Command::Stop { eeprom: None }).await,
+            Ok(Response::Ok("Stopped".to_string()))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_led_owner_is_probed_and_remembered() {
+        let left = MockTransport::new();
+        let right = MockTransport::new();
+        left.push_reply("ERROR: unknown LED 20");
+        right.push_reply("OK: LED").push_reply("OK: LED");
+        let boards = two_boards(&left, &right);
+
+        assert_eq!(
+            boards.send(led(20)).await,
+            Ok(Response::Ok("LED".to_string()))
+        );
+        assert_eq!(
+            boards.send(led(20)).await,
+            Ok(Response::Ok("LED".to_string()))
+        );
+        assert_eq!(left.sent().len(), 1);
+        assert_eq!(right.sent().len(), 2);
+        assert_eq!(boards.statuses()[1].leds, vec![20]);
+    }
+
+    #[tokio::test]
+    async fn test_led_owners_come_from_identify() {
+        let left = MockTransport::new();
+        let right = MockTransport::new();
+        right.push_reply("OK: LED");
+        let boards = Boards::default();
+        for (port, mock, leds) in [("left", &left, [0, 14]), ("right", &right, [15, 29])] {
+            boards
+                .attach(Connection {
+                    port: port.to_string(),
+                    transport: Box::new(mock.clone()),
+                    firmware: FirmwareInfo {
+                        leds: Some(leds),
+                        ..FirmwareInfo::legacy()
+                    },
+                    baud_rate: None,
+                })
+                .await;
+        }
+
+        assert_eq!(
+            boards.send(led(20)).await,
+            Ok(Response::Ok("LED".to_string()))
+        );
+        assert!(left.sent().is_empty());
+        assert_eq!(right.sent().len(), 1);
+        assert_eq!(boards.statuses()[0].leds, (0..=14).collect::<Vec<_>>());
+    }
+
+    #[tokio::test]
+    async fn test_scan_merges_the_boards_that_answered() {
+        let left = MockTransport::new();
+        let right = MockTransport::new();
+        left.push_timeout();
+        right.push_reply(r#"SCAN: [{"EEPROM":7,"I2C":32,"TYPE":4}]"#);
+        let boards = two_boards(&left, &right);
+
+        let (response, failures) = boards.scan().await.unwrap();
+        assert_eq!(
+            response,
+            Response::Scan(json!([{"EEPROM": 7, "I2C": 32, "TYPE": 4}]))
+        );
+        assert_eq!(failures.len(), 1);
+        assert_eq!(failures[0].port, "left");
+        assert!(failures[0].error.starts_with("No response"));
+        assert_eq!(boards.statuses()[1].eeproms, vec![7]);
+
+        // Without any answer the scan fails as a whole.
+        left.push_reply("ERROR: busy");
+        right.push_reply("ERROR: busy");
+        assert!(matches!(boards.scan().await, Err(AppError::Device { .. })));
+    }
+
+    #[tokio::test]
+    async fn test_unowned_id_returns_firmware_error() {
+        let left = MockTransport::new();
+        let right = MockTransport::new();
+        left.push_reply("ERROR: unknown EEPROM 9");
+        right.push_reply("ERROR: unknown EEPROM 9");
+        let boards = two_boards(&left, &right);
+
+        let result = boards.send(Command::Stop { eeprom: Some(9) }).await;
+        assert!(matches!(result, Err(AppError::Device { .. })));
+        assert!(boards
+            .statuses()
+            .iter()
+            .all(|board| board.eeproms.is_empty()));
+    }
+
+    #[tokio::test]
+    async fn test_stop_all_reaches_every_board() {
+        let left = MockTransport::new();
+        let right = MockTransport::new();
+        left.push_reply("OK: Stopped");
+        right.push_reply("OK: Stopped");
+        let boards = two_boards(&left, &right);
+
+        assert!(boards.send(Command::Stop { eeprom: None }).await.is_ok());
+        assert_eq!(left.sent(), vec!["<STOP()>\n"]);
+        assert_eq!(right.sent(), vec!["<STOP()>\n"]);
+    }
+
+    #[tokio::test]
+    async fn test_busy_while_a_command_waits() {
+        let mock = MockTransport::new();
+        mock.push_timeout();
+        let boards = Boards::single("mock", SerialHandle::spawn(Some(Box::new(mock.clone()))));
+        assert!(!boards.is_busy());
+
+        let pending = tokio::spawn({
+            let boards = boards.clone();
+            async move { boards.send(Command::Stop { eeprom: None }).await }
+        });
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert!(boards.is_busy());
+
+        assert!(pending.await.unwrap().is_err());
+        assert!(!boards.is_busy());
+    }
+
+    #[tokio::test]
+    async fn test_empty_set_is_not_connected() {
+        let boards = Boards::default();
+        assert!(!boards.is_connected());
+        assert_eq!(
+            boards.send(Command::Scan).await,
+            Err(AppError::NotConnected)
+        );
+    }
+}
+
+#[cfg(test)]
+mod discovery_tests {
+    use crate::serial::discovery::{glob_match, parse_baud_rates, DiscoveryConfig, DiscoveryRule};
+    use serialport::{SerialPortInfo, SerialPortType, UsbPortInfo};
+
+    fn usb(port: &str, vid: u16, pid: u16, product: &str) -> SerialPortInfo {
+        SerialPortInfo {
+            port_name: port.to_string(),
+            port_type: SerialPortType::UsbPort(UsbPortInfo {
+                vid,
+                pid,
+                serial_number: Some("A1B2".to_string()),
+                manufacturer: Some("wch.cn".to_string()),
+                product: Some(product.to_string()),
+            }),
+        }
+    }
+
+    #[test]
+    fn test_glob_match() {
+        assert!(glob_match("/dev/ttyACM*", "/dev/ttyACM0"));
+        assert!(glob_match("/dev/tty???0", "/dev/ttyUSB0"));
+        assert!(glob_match("*", ""));
+        assert!(glob_match("/dev/*USB*", "/dev/ttyUSB12"));
+        assert!(!glob_match("/dev/ttyACM*", "/dev/ttyUSB0"));
+        assert!(!glob_match("/dev/ttyACM?", "/dev/ttyACM10"));
+    }
+
+    #[test]
+    fn test_all_fields_must_match() {
+        let port = usb("/dev/ttyUSB0", 0x1a86, 0x7523, "USB2.0-Serial");
+        let rule = |vid, pid| DiscoveryRule {
+            vid: Some(vid),
+            pid: Some(pid),
+            ..DiscoveryRule::default()
+        };
+        assert!(rule(0x1a86, 0x7523).matches(&port));
+        assert!(!rule(0x1a86, 0x5523).matches(&port));
+        assert!(DiscoveryRule {
+            product: Some("usb2.0-serial".to_string()),
+            serial_number: Some("A1B2".to_string()),
+            ..DiscoveryRule::default()
+        }
+        .matches(&port));
+        assert!(!DiscoveryRule::default().matches(&port));
+    }
+
+    #[test]
+    fn test_rules_from_json() {
+        let config: DiscoveryConfig = serde_json::from_str(
+            r#"{"rules": [{"vid": "0x1A86", "pid": "7523"}, {"glob": "/dev/ttyACM*"}]}"#,
+        )
+        .unwrap();
+        assert_eq!(config.rules[0].vid, Some(0x1a86));
+        assert_eq!(config.rules[0].to_string(), "vid=1a86 pid=7523");
+
+        let port = usb("/dev/ttyACM0", 0x2341, 0x0043, "Uno");
+        assert_eq!(
+            config.matching_rule(&port).map(ToString::to_string),
+            Some("glob=/dev/ttyACM*".to_string())
+        );
+    }
+
+    #[test]
+    fn test_invalid_rules_are_rejected() {
+        assert!(serde_json::from_str::<DiscoveryConfig>(r#"{"rules": [{"vid": "xyz"}]}"#).is_err());
+        assert!(serde_json::from_str::<DiscoveryConfig>(r#"{"rules": [{"port": "x"}]}"#).is_err());
+    }
+
+    #[test]
+    fn test_numeric_usb_ids_are_rejected() {
+        let error =
+            serde_json::from_str::<DiscoveryConfig>(r#"{"rules": [{"pid": 7523}]}"#).unwrap_err();
+        assert!(
+            error
+                .to_string()
+                .contains(r#"pid must be a hex string, e.g. "7523""#),
+            "{}",
+            error
+        );
+        assert!(serde_json::from_str::<DiscoveryConfig>(r#"{"rules": [{"vid": 2341}]}"#).is_err());
+    }
+
+    #[test]
+    fn test_baud_rates() {
+        let config: DiscoveryConfig =
+            serde_json::from_str(r#"{"rules": [], "baud_rates": [115200, 9600]}"#).unwrap();
+        assert_eq!(config.baud_rates(None), vec![115200, 9600]);
+        assert_eq!(config.baud_rates(Some(9600)), vec![9600, 115200]);
+        assert_eq!(config.baud_rates(Some(57600)), vec![57600, 115200, 9600]);
+
+        let config: DiscoveryConfig = serde_json::from_str(r#"{"rules": []}"#).unwrap();
+        assert_eq!(config.baud_rates(None)[0], crate::config::BAUD_RATE);
+
+        assert_eq!(parse_baud_rates(" 115200, 9600 "), Ok(vec![115200, 9600]));
+        assert!(parse_baud_rates("fast").is_err());
+        assert!(parse_baud_rates("0").is_err());
+        assert!(parse_baud_rates("").is_err());
+    }
+
+    #[test]
+    fn test_example_file_loads() {
+        let config = DiscoveryConfig::load("examples/discovery.json").unwrap();
+        assert_eq!(config.rules.len(), 7);
+        assert_eq!(config.baud_rates, vec![9600, 115200]);
+    }
+}
+
+#[cfg(test)]
+mod handshake_tests {
+    use crate::config::HANDSHAKE_ATTEMPTS;
+    use crate::emulator::Emulator;
+    use crate::error::AppError;
+    use crate::protocol::frame::append_checksum;
+    use crate::protocol::{FirmwareInfo, Framing};
+    use crate::serial::transport::{Transport, TransportFuture};
+    use crate::serial::{detect_baud_rate, handshake, LinkConfig, MockTransport};
+    use std::collections::VecDeque;
+
+    const IDENTITY: &str =
+        r#"ID: {"name":"energy-table","version":"2.1.0","protocol":1,"capabilities":["crc8"]}"#;
+
+    /// Feeds frames straight into an [`Emulator`]; silent while it has
+    /// nothing to say.
+    struct EmulatedBoard {
+        emulator: Emulator,
+        replies: VecDeque<String>,
+        sent: Vec<String>,
+    }
+
+    impl EmulatedBoard {
+        fn new(emulator: Emulator) -> Self {
+            Self {
+                emulator,
+                replies: VecDeque::new(),
+                sent: Vec::new(),
+            }
+        }
+    }
+
+    impl Transport for EmulatedBoard {
+        fn send_frame<'a>(&'a mut self, frame: &'a [u8]) -> TransportFuture<'a, ()> {
+            self.sent.push(String::from_utf8_lossy(frame).to_string());
+            self.replies.extend(self.emulator.feed(frame));
+            Box::pin(async { Ok(()) })
+        }
+
+        fn receive_line(&mut self) -> TransportFuture<'_, String> {
+            Box::pin(async move {
+                match self.replies.pop_front() {
+                    Some(line) => Ok(line),
+                    None => std::future::pending().await,
+                }
+            })
+        }
+
+        fn reset(&mut self) -> TransportFuture<'_, ()> {
+            self.replies.clear();
+            Box::pin(async { Ok(()) })
+        }
+
+        fn describe(&self) -> String {
+            "emulated board".to_string()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_skips_boot_messages() {
+        let mock = MockTransport::new();
+        mock.push_reply("Booting energy table...")
+            .push_reply("I2C bus ready")
+            .push_reply(IDENTITY);
+        let mut transport = mock.clone();
+
+        let firmware = handshake(&mut transport, &LinkConfig::default())
+            .await
+            .unwrap();
+        assert_eq!(firmware.name, "energy-table");
+        assert!(firmware.supports("crc8"));
+        assert_eq!(mock.sent(), vec!["<IDENTIFY()>\n"]);
+        assert_eq!(mock.resets(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_accepts_checksummed_and_sequenced_reply() {
+        let mock = MockTransport::new();
+        mock.push_reply(append_checksum(&format!("#0 {}", IDENTITY)));
+        let mut transport = mock.clone();
+
+        let firmware = handshake(&mut transport, &LinkConfig::default()).await;
+        assert!(firmware.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_retries_while_board_boots() {
+        let mock = MockTransport::new();
+        mock.push_timeout().push_reply(IDENTITY);
+        let mut transport = mock.clone();
+
+        let firmware = handshake(&mut transport, &LinkConfig::default()).await;
+        assert!(firmware.is_ok());
+        assert_eq!(mock.sent().len(), 2);
+        assert_eq!(mock.resets(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_error_reply_means_legacy_firmware() {
+        let mock = MockTransport::new();
+        mock.push_reply("ERROR: unknown command IDENTIFY");
+        let mut transport = mock.clone();
+
+        let firmware = handshake(&mut transport, &LinkConfig::default()).await;
+        assert_eq!(firmware, Ok(FirmwareInfo::legacy()));
+    }
+
+    #[tokio::test]
+    async fn test_silent_firmware_answering_scan_is_legacy() {
+        let mut board = EmulatedBoard::new(Emulator::default().with_silent_legacy());
+
+        let firmware = handshake(&mut board, &LinkConfig::default()).await;
+        assert_eq!(firmware, Ok(FirmwareInfo::legacy()));
+        assert_eq!(board.sent.len(), HANDSHAKE_ATTEMPTS as usize + 1);
+        assert_eq!(board.sent.last().unwrap(), "<SCAN()>\n");
+    }
+
+    #[tokio::test]
+    async fn test_rejects_unsupported_protocol() {
+        let mock = MockTransport::new();
+        mock.push_reply(r#"ID: {"name":"energy-table","version":"3.0.0","protocol":2}"#);
+        let mut transport = mock.clone();
+
+        let error = handshake(&mut transport, &LinkConfig::default())
+            .await
+            .unwrap_err();
+        assert_eq!(error.code(), "INCOMPATIBLE_FIRMWARE");
+        assert!(error.to_string().contains("protocol 2"));
+    }
+
+    #[tokio::test]
+    async fn test_rejects_missing_required_feature() {
+        let mock = MockTransport::new();
+        mock.push_reply(IDENTITY);
+        let mut transport = mock.clone();
+        let link = LinkConfig {
+            framing: Framing::Sequenced,
+            ..LinkConfig::default()
+        };
+
+        let error = handshake(&mut transport, &link).await.unwrap_err();
+        assert_eq!(
+            error,
+            AppError::IncompatibleFirmware(
+                "energy-table 2.1.0 (protocol 1, crc8) lacks sequenced, which the link settings require"
+                    .to_string()
+            )
+        );
+    }
+
+    #[tokio::test]
+    async fn test_detect_baud_rate_moves_on_from_silent_rates() {
+        let silent = MockTransport::new();
+        let answering = MockTransport::new();
+        answering.push_reply(IDENTITY);
+        let mut opened = Vec::new();
+
+        let (rate, _, firmware) =
+            detect_baud_rate(&[9600, 115200], &LinkConfig::default(), |rate| {
+                opened.push(rate);
+                let mock = if rate == 9600 { &silent } else { &answering };
+                Ok(Box::new(mock.clone()))
+            })
+            .await
+            .unwrap();
+        assert_eq!(rate, 115200);
+        assert_eq!(opened, vec![9600, 115200]);
+        assert_eq!(firmware.name, "energy-table");
+        // Every IDENTIFY() attempt, then the SCAN() probe.
+        assert_eq!(silent.sent().len(), HANDSHAKE_ATTEMPTS as usize + 1);
+    }
+
+    #[tokio::test]
+    async fn test_detect_baud_rate_stops_at_incompatible_firmware() {
+        let mock = MockTransport::new();
+        mock.push_reply(r#"ID: {"name":"energy-table"}"#);
+        let mut opened = Vec::new();
+
+        let result = detect_baud_rate(&[9600, 115200], &LinkConfig::default(), |rate| {
+            opened.push(rate);
+            Ok(Box::new(mock.clone()))
+        })
+        .await;
+        assert!(matches!(result, Err(AppError::IncompatibleFirmware(_))));
+        assert_eq!(opened, vec![9600]);
+    }
+
+    #[tokio::test]
+    async fn test_malformed_identity_is_incompatible() {
+        let mock = MockTransport::new();
+        mock.push_reply(r#"ID: {"name":"energy-table"}"#);
+        let mut transport = mock.clone();
+
+        let error = handshake(&mut transport, &LinkConfig::default())
+            .await
+            .unwrap_err();
+        assert_eq!(error.code(), "INCOMPATIBLE_FIRMWARE");
+    }
+}
+
+#[cfg(test)]
+mod capture_tests {
+    use crate::error::with_request_id;
+    use crate::serial::{CaptureLog, CaptureRecord, Direction, MockTransport};
+    use std::path::PathBuf;
+
+    fn capture_path(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("capture-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir.join("capture.jsonl")
+    }
+
+    fn records(path: &PathBuf) -> Vec<CaptureRecord> {
+        std::fs::read_to_string(path)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_records_both_directions_with_request_id() {
+        let path = capture_path("directions");
+        let mock = MockTransport::new();
+        mock.push_reply("OK: Stopped");
+        let mut transport = CaptureLog::new(&path, 1024, 1).wrap("ttyACM0", Box::new(mock));
+
+        with_request_id(Some("req-1".to_string()), async {
+            transport.send_frame(b"<STOP()>\n").await.unwrap();
+            transport.receive_line().await.unwrap();
+        })
+        .await;
+
+        let records = records(&path);
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].dir, Direction::Tx);
+        assert_eq!(records[0].data, "<STOP()>");
+        assert_eq!(records[1].dir, Direction::Rx);
+        assert_eq!(records[1].data, "OK: Stopped");
+        assert!(records.iter().all(
+            |record| record.port == "ttyACM0" && record.request_id.as_deref() == Some("req-1")
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_unsolicited_lines_have_no_request_id() {
+        let path = capture_path("unsolicited");
+        let mock = MockTransport::new();
+        mock.push_unsolicited("EVENT UNPLUG: {\"EEPROM\":7}");
+        let mut transport = CaptureLog::new(&path, 1024, 1).wrap("ttyACM0", Box::new(mock));
+
+        transport.receive_unsolicited().await.unwrap();
+
+        let line = std::fs::read_to_string(&path).unwrap();
+        assert!(!line.contains("request_id"));
+        assert_eq!(records(&path)[0].dir, Direction::Rx);
+    }
+
+    #[test]
+    fn test_rotates_and_keeps_bounded_files() {
+        let path = capture_path("rotation");
+        let capture = CaptureLog::new(&path, 200, 2);
+
+        for index in 0..20 {
+            capture.record(
+                "ttyACM0",
+                Direction::Tx,
+                &format!("<LED({},1,FF0000,1)>", index),
+            );
+        }
+
+        let rotated = |index: usize| PathBuf::from(format!("{}.{}", path.display(), index));
+        assert!(std::fs::metadata(&path).unwrap().len() <= 200);
+        assert!(rotated(1).exists());
+        assert!(rotated(2).exists());
+        assert!(!rotated(3).exists());
+        let newest = records(&path);
+        assert_eq!(newest.last().unwrap().data, "<LED(19,1,FF0000,1)>");
+    }
+}
+
+#[cfg(test)]
+mod replay_tests {
+    use crate::protocol::frame::append_checksum;
+    use crate::protocol::Framing;
+    use crate::serial::{
+        handshake, CaptureRecord, Direction, LinkConfig, ReplayTransport, Transport,
+    };
+
+    fn record(port: &str, dir: Direction, data: &str) -> CaptureRecord {
+        CaptureRecord {
+            ts_ms: 0,
+            port: port.to_string(),
+            dir,
+            data: data.to_string(),
+            request_id: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_answers_matching_command_with_new_sequence() {
+        let records = vec![
+            record("a", Direction::Rx, "Booting energy table..."),
+            record(
+                "a",
+                Direction::Tx,
+                &format!("<{}>", append_checksum("#7 STOP()")),
+            ),
+            record("a", Direction::Rx, &append_checksum("#7 OK: Stopped")),
+            record("b", Direction::Tx, "<SCAN()>"),
+            record("b", Direction::Rx, "OK: {}"),
+        ];
+        let mut transport = ReplayTransport::new("a", &records);
+        assert_eq!(transport.len(), 1);
+
+        let frame = format!("<{}>\n", append_checksum("#2 STOP()"));
+        transport.send_frame(frame.as_bytes()).await.unwrap();
+        assert_eq!(
+            transport.receive_line().await.unwrap(),
+            append_checksum("#2 OK: Stopped")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_unknown_command_is_rejected() {
+        let records = vec![
+            record("a", Direction::Tx, "<STOP()>"),
+            record("a", Direction::Rx, "OK: Stopped"),
+        ];
+        let mut transport = ReplayTransport::new("a", &records);
+
+        transport.send_frame(b"<SCAN()>\n").await.unwrap();
+        assert_eq!(
+            transport.receive_line().await.unwrap(),
+            "ERROR: no recorded answer to SCAN()"
+        );
+
+        let frame = format!("<{}>\n", append_checksum("#3 SCAN()"));
+        transport.send_frame(frame.as_bytes()).await.unwrap();
+        assert_eq!(
+            transport.receive_line().await.unwrap(),
+            append_checksum("#3 ERROR: no recorded answer to SCAN()")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_dropped_receive_keeps_the_reply() {
+        let records = vec![
+            record("a", Direction::Tx, "<STOP()>"),
+            record("a", Direction::Rx, "OK: Stopped"),
+        ];
+        let mut transport = ReplayTransport::new("a", &records);
+
+        transport.send_frame(b"<STOP()>\n").await.unwrap();
+        // Like a `select!` that picked another branch before polling it.
+        drop(transport.receive_line());
+        assert_eq!(transport.receive_line().await.unwrap(), "OK: Stopped");
+    }
+
+    #[tokio::test]
+    async fn test_repeated_commands_follow_the_capture() {
+        let records = vec![
+            record("a", Direction::Tx, "<SCAN()>"),
+            record("a", Direction::Rx, "OK: {\"1\":[1]}"),
+            record("a", Direction::Tx, "<SCAN()>"),
+            record("a", Direction::Rx, "OK: {\"1\":[2]}"),
+        ];
+        let mut transport = ReplayTransport::new("a", &records);
+
+        let mut replies = Vec::new();
+        for _ in 0..3 {
+            transport.send_frame(b"<SCAN()>\n").await.unwrap();
+            replies.push(transport.receive_line().await.unwrap());
+        }
+        assert_eq!(
+            replies,
+            vec!["OK: {\"1\":[1]}", "OK: {\"1\":[2]}", "OK: {\"1\":[1]}"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_replayed_handshake_negotiates_recorded_features() {
+        let identity = r#"ID: {"name":"arduino-emulator","version":"1.0.0","protocol":1,"capabilities":["sequenced","crc8"]}"#;
+        let records = vec![
+            record("a", Direction::Tx, "<IDENTIFY()>"),
+            record("a", Direction::Rx, identity),
+        ];
+        let mut transport = ReplayTransport::new("a", &records);
+
+        let firmware = handshake(&mut transport, &LinkConfig::default())
+            .await
+            .unwrap();
+        let link = LinkConfig::default().negotiate(&firmware);
+        assert_eq!(link.framing, Framing::Sequenced);
+        assert!(link.checksum);
+    }
+}
+
+#[cfg(test)]
+mod health_tests {
+    use crate::error::AppError;
+    use crate::protocol::frame::append_checksum;
+    use crate::protocol::{Command, FirmwareInfo};
+    use crate::serial::health::overall_state;
+    use crate::serial::{
+        Backoff, Boards, ConnectionHealth, ConnectionState, LinkConfig, MockTransport, SerialHandle,
+    };
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn test_backoff_doubles_up_to_max() {
+        let backoff = Backoff {
+            initial: Duration::from_secs(1),
+            max: Duration::from_secs(10),
+            jitter: 0.0,
+        };
+        let delays: Vec<u64> = (1..=6)
+            .map(|failures| backoff.delay(failures).as_secs())
+            .collect();
+        assert_eq!(delays, vec![1, 2, 4, 8, 10, 10]);
+    }
+
+    #[test]
+    fn test_backoff_jitter_stays_in_bounds() {
+        let backoff = Backoff {
+            initial: Duration::from_secs(4),
+            max: Duration::from_secs(60),
+            jitter: 0.25,
+        };
+        for _ in 0..100 {
+            let delay = backoff.delay(1);
+            assert!(delay >= Duration::from_secs(3) && delay <= Duration::from_secs(5));
+        }
+    }
+
+    #[test]
+    fn test_failures_schedule_retry_and_reset_on_connect() {
+        let mut health = ConnectionHealth::new(ConnectionState::Searching);
+        assert!(health.is_due(Instant::now()));
+
+        health.failed(&AppError::Io("busy".to_string()), Duration::from_secs(30));
+        assert_eq!(health.state, ConnectionState::Failed);
+        assert_eq!(health.failures, 1);
+        assert_eq!(health.last_error.as_deref(), Some("busy"));
+        assert!(!health.is_due(Instant::now()));
+        assert!(health.is_due(Instant::now() + Duration::from_secs(31)));
+
+        health.connected();
+        health.lost(&AppError::Timeout(Duration::from_secs(1)));
+        health.connected();
+        assert_eq!(health.state, ConnectionState::Connected);
+        assert_eq!((health.failures, health.reconnects), (0, 1));
+        assert!(health.next_attempt_ms.is_none());
+    }
+
+    #[test]
+    fn test_overall_state() {
+        let board = |state| ConnectionHealth::new(state);
+        assert_eq!(overall_state(&[]), ConnectionState::Searching);
+        assert_eq!(
+            overall_state(&[board(ConnectionState::Connected)]),
+            ConnectionState::Connected
+        );
+        assert_eq!(
+            overall_state(&[
+                board(ConnectionState::Connected),
+                board(ConnectionState::Failed)
+            ]),
+            ConnectionState::Degraded
+        );
+        assert_eq!(
+            overall_state(&[
+                board(ConnectionState::Failed),
+                board(ConnectionState::Handshaking)
+            ]),
+            ConnectionState::Handshaking
+        );
+        assert_eq!(
+            overall_state(&[board(ConnectionState::Failed)]),
+            ConnectionState::Failed
+        );
+    }
+
+    #[tokio::test]
+    async fn test_retransmission_degrades_until_clean_reply() {
+        let mock = MockTransport::new();
+        mock.push_reply("OK: Stopped*00")
+            .push_reply(append_checksum("OK: Stopped"))
+            .push_reply(append_checksum("OK: Stopped"));
+        let link = LinkConfig {
+            checksum: true,
+            retries: 1,
+            ..LinkConfig::default()
+        };
+        let handle = SerialHandle::with_link(Some(Box::new(mock)), link);
+
+        handle.send(Command::Stop { eeprom: None }).await.unwrap();
+        assert_eq!(handle.health().state, ConnectionState::Degraded);
+        handle.send(Command::Stop { eeprom: None }).await.unwrap();
+        assert_eq!(handle.health().state, ConnectionState::Connected);
+    }
+
+    #[tokio::test]
+    async fn test_transport_failure_is_reported_and_reconnect_counted() {
+        let mock = MockTransport::new();
+        mock.push_failure("device unplugged");
+        let boards = Boards::single("mock", SerialHandle::spawn(Some(Box::new(mock))));
+
+        let _ = boards.send(Command::Stop { eeprom: None }).await;
+        let status = boards.connection();
+        assert_eq!(status.state, ConnectionState::Failed);
+        assert_eq!(
+            status.boards[0].health.last_error.as_deref(),
+            Some("device unplugged")
+        );
+
+        boards
+            .board("mock")
+            .attach(Box::new(MockTransport::new()), FirmwareInfo::legacy())
+            .await;
+        let status = boards.connection();
+        assert_eq!(status.state, ConnectionState::Connected);
+        assert_eq!(status.boards[0].health.reconnects, 1);
+    }
+
+    #[tokio::test]
+    async fn test_missing_ports_go_back_to_searching() {
+        let boards = Boards::default();
+        boards
+            .board("gone")
+            .update_health(|health| health.lost(&AppError::NotConnected));
+
+        boards.mark_missing(&[]);
+        assert_eq!(
+            boards.connection().boards[0].health.state,
+            ConnectionState::Searching
+        );
+    }
+}
+
+#[cfg(test)]
+mod heartbeat_tests {
+    use crate::serial::link::Heartbeat;
+    use crate::serial::{ConnectionState, LinkConfig, MockTransport, SerialHandle};
+    use std::time::Duration;
+
+    fn with_heartbeat(mock: &MockTransport, interval_ms: u64, misses: u32) -> SerialHandle {
+        let link = LinkConfig {
+            heartbeat: Heartbeat {
+                interval: Some(Duration::from_millis(interval_ms)),
+                misses,
+            },
+            ..LinkConfig::default()
+        };
+        SerialHandle::with_link(Some(Box::new(mock.clone())), link)
+    }
+
+    #[tokio::test]
+    async fn test_idle_board_is_pinged() {
+        let mock = MockTransport::new();
+        mock.push_reply("OK: PONG");
+        let handle = with_heartbeat(&mock, 20, 3);
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        assert_eq!(mock.sent()[0], "<PING()>\n");
+        let heartbeat = handle.stats().heartbeat;
+        assert_eq!(heartbeat.interval_ms, Some(20));
+        assert_eq!(heartbeat.missed, 0);
+        assert!(heartbeat.sent >= 1);
+        assert!(heartbeat.last_rtt_ms.is_some() && heartbeat.avg_rtt_ms.is_some());
+        assert_eq!(handle.health().state, ConnectionState::Connected);
+    }
+
+    #[tokio::test]
+    async fn test_firmware_error_counts_as_alive() {
+        let mock = MockTransport::new();
+        mock.push_reply("ERROR: unknown command PING");
+        let handle = with_heartbeat(&mock, 20, 1);
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        assert_eq!(handle.stats().heartbeat.missed, 0);
+        assert!(handle.is_connected());
+    }
+
+    #[tokio::test]
+    async fn test_missed_heartbeats_degrade_then_drop() {
+        let mock = MockTransport::new();
+        let handle = with_heartbeat(&mock, 10, 2);
+
+        // Each ping waits HEARTBEAT_TIMEOUT (500 ms) for its answer.
+        tokio::time::sleep(Duration::from_millis(700)).await;
+        assert_eq!(handle.health().state, ConnectionState::Degraded);
+        assert!(handle.is_connected());
+
+        tokio::time::sleep(Duration::from_millis(600)).await;
+        let health = handle.health();
+        assert_eq!(health.state, ConnectionState::Failed);
+        assert!(health.last_error.is_some());
+        assert!(!handle.is_connected());
+        assert_eq!(handle.stats().heartbeat.missed, 2);
+        assert_eq!(mock.sent(), vec!["<PING()>\n", "<PING()>\n"]);
+    }
+
+    #[tokio::test]
+    async fn test_commands_keep_the_heartbeat_quiet() {
+        let mock = MockTransport::new();
+        let handle = with_heartbeat(&mock, 60, 3);
+        for _ in 0..4 {
+            mock.push_reply("OK: Stopped");
+        }
+
+        for _ in 0..4 {
+            tokio::time::sleep(Duration::from_millis(30)).await;
+            handle
+                .send(crate::protocol::Command::Stop { eeprom: None })
+                .await
+                .unwrap();
+        }
+        assert!(mock.sent().iter().all(|frame| frame == "<STOP()>\n"));
+    }
+}
+
+#[cfg(test)]
+mod led_batch_tests {
+    use crate::error::AppError;
+    use crate::protocol::frame::append_checksum;
+    use crate::protocol::{FirmwareInfo, LedSetting, Response};
+    use crate::serial::{LinkConfig, MockTransport, SerialHandle};
+
+    fn leds(count: u8) -> Vec<LedSetting> {
+        (0..count)
+            .map(|led_id| LedSetting {
+                led_id,
+                forward: true,
+                color: (0, 0x80, 0xFF),
+                pulse_frequenz: 2,
+            })
+            .collect()
+    }
+
+    async fn binary_board(mock: &MockTransport) -> SerialHandle {
+        let handle = SerialHandle::with_link(
+            None,
+            LinkConfig {
+                binary: true,
+                ..LinkConfig::default()
+            },
+        );
+        let firmware = FirmwareInfo {
+            name: "energy-table".to_string(),
+            version: "3.0.0".to_string(),
+            protocol: 1,
+            capabilities: vec!["binary".to_string()],
+            leds: None,
+            identified: true,
+        };
+        handle.attach(Box::new(mock.clone()), firmware).await;
+        handle
+    }
+
+    #[tokio::test]
+    async fn test_batches_are_split_into_frames() {
+        let mock = MockTransport::new();
+        mock.push_reply(append_checksum("#1 OK: 32 LEDs"))
+            .push_reply(append_checksum("#2 OK: 8 LEDs"));
+        let handle = binary_board(&mock).await;
+        assert!(handle.stats().binary);
+
+        let response = handle.send_leds(leds(40)).await.unwrap();
+        assert_eq!(response, Response::Ok("8 LEDs".to_string()));
+
+        let sent = mock.sent();
+        assert_eq!(sent.len(), 2);
+        assert!(sent[0].starts_with("bin #1 LEDS(0:1:0080FF:2, 1:1:0080FF:2,"));
+        assert!(sent[1].starts_with("bin #2 LEDS(32:1:0080FF:2,"));
+        assert!(sent[1].ends_with("39:1:0080FF:2)"));
+    }
+
+    #[tokio::test]
+    async fn test_commands_use_binary_frames_once_negotiated() {
+        let mock = MockTransport::new();
+        mock.push_reply(append_checksum("#1 OK: Stopped"));
+        let handle = binary_board(&mock).await;
+
+        handle
+            .send(crate::protocol::Command::Stop { eeprom: None })
+            .await
+            .unwrap();
+        assert_eq!(mock.sent(), vec!["bin #1 STOP()"]);
+    }
+
+    #[tokio::test]
+    async fn test_first_error_ends_the_batch() {
+        let mock = MockTransport::new();
+        mock.push_reply(append_checksum("#1 ERROR: unknown LED 5"));
+        let handle = binary_board(&mock).await;
+
+        let result = handle.send_leds(leds(40)).await;
+        assert!(matches!(result, Err(AppError::Device { .. })));
+        assert_eq!(mock.sent().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_text_fallback_without_binary_support() {
+        let mock = MockTransport::new();
+        mock.push_reply("OK: LED").push_reply("OK: LED");
+        let handle = SerialHandle::with_link(
+            Some(Box::new(mock.clone())),
+            LinkConfig {
+                binary: true,
+                ..LinkConfig::default()
+            },
+        );
+
+        handle.send_leds(leds(2)).await.unwrap();
+        assert!(!handle.stats().binary);
+        assert_eq!(
+            mock.sent(),
+            vec![
+                "<LED(0, 1, 0, 128, 255, 2)>\n",
+                "<LED(1, 1, 0, 128, 255, 2)>\n"
+            ]
+        );
+    }
+}
@@ -0,0 +1,316 @@
+use crate::error::AppError;
+use std::future::Future;
+use std::pin::Pin;
+use std::time::Duration;
+
+/// Future returned by [`Transport`] methods.
+pub type TransportFuture<'a, T> = Pin<Box<dyn Future<Output = Result<T, AppError>> + Send + 'a>>;
+
+/// How long the device has to stay silent before `reset` considers the input drained.
+const DRAIN_QUIET_PERIOD: Duration = Duration::from_millis(100);
+
+/// A byte-level link to the Arduino.
+///
+/// Handlers and `serial::communication` only talk to the board through this
+/// trait, so the physical serial port can be swapped for an in-memory backend
+/// (see [`crate::serial::mock::MockTransport`]).
+///
+/// `receive_line` has no timeout of its own; callers race it against their
+/// deadline. Implementations must therefore be cancel safe and keep partially
+/// received lines buffered for the next call.
+pub trait Transport: Send {
+    /// Writes one complete, already framed command to the device.
+    fn send_frame<'a>(&'a mut self, frame: &'a [u8]) -> TransportFuture<'a, ()>;
+
+    /// Reads the next non-empty line sent by the device, without the line ending.
+    fn receive_line(&mut self) -> TransportFuture<'_, String>;
+
+    /// Reads the next line while no command is in flight, i.e. something the
+    /// device sent on its own. Same cancel-safety rules as `receive_line`.
+    ///
+    /// Scripted backends override this so idle reads do not consume replies
+    /// meant for the next command.
+    fn receive_unsolicited(&mut self) -> TransportFuture<'_, String> {
+        self.receive_line()
+    }
+
+    /// Discards everything the device has sent but nobody has read yet.
+    fn reset(&mut self) -> TransportFuture<'_, ()>;
+
+    /// Human readable description of the backend, e.g. the port name.
+    fn describe(&self) -> String;
+}
+
+/// Splits a byte stream into trimmed, non-empty text lines.
+#[derive(Default)]
+pub struct LineBuffer {
+    bytes: Vec<u8>,
+}
+
+impl LineBuffer {
+    pub fn extend(&mut self, data: &[u8]) {
+        self.bytes.extend_from_slice(data);
+    }
+
+    /// Removes and returns the next complete line, if one has been received.
+    pub fn next_line(&mut self) -> Option<String> {
+        while let Some(end) = self.bytes.iter().position(|&b| b == b'\n') {
+            let line: Vec<u8> = self.bytes.drain(..=end).collect();
+            let line = String::from_utf8_lossy(&line).trim().to_string();
+            if !line.is_empty() {
+                return Some(line);
+            }
+        }
+        None
+    }
+
+    pub fn clear(&mut self) {
+        self.bytes.clear();
+    }
+}
+
+#[cfg(unix)]
+pub use self::unix::SerialTransport;
+
+#[cfg(not(unix))]
+pub use self::blocking::SerialTransport;
+
+#[cfg(unix)]
+mod unix {
+    use super::{LineBuffer, Transport, TransportFuture, DRAIN_QUIET_PERIOD};
+    use crate::error::AppError;
+    use std::io;
+    use std::os::unix::io::{AsRawFd, RawFd};
+    use tokio::io::unix::AsyncFd;
+
+    /// [`Transport`] over a tty, driven by the tokio reactor.
+    ///
+    /// The port is opened and configured through `serialport`, then switched
+    /// to non-blocking mode and registered with [`AsyncFd`], so waiting for
+    /// the board never occupies a runtime thread.
+    pub struct SerialTransport {
+        port: AsyncFd<serialport::TTYPort>,
+        name: String,
+        lines: LineBuffer,
+    }
+
+    impl SerialTransport {
+        pub fn open(port_name: &str, baud_rate: u32) -> Result<Self, AppError> {
+            let port = serialport::new(port_name, baud_rate)
+                .open_native()
+                .map_err(|e| AppError::Io(format!("Error while opening {}: {}", port_name, e)))?;
+            set_nonblocking(port.as_raw_fd())
+                // SAFETY: the descriptor belongs to `port`, which the `AsyncFd`
+                // owns and never closes or swaps until it is dropped.
+                .and_then(|_| unsafe { AsyncFd::register(port) }.map_err(io::Error::from))
+                .map(|port| Self {
+                    port,
+                    name: port_name.to_string(),
+                    lines: LineBuffer::default(),
+                })
+                .map_err(|e| AppError::Io(format!("Error while registering {}: {}", port_name, e)))
+        }
+
+        async fn read_chunk(&mut self) -> io::Result<usize> {
+            let mut buffer = [0u8; 256];
+            loop {
+                let mut guard = self.port.readable_mut().await?;
+                let fd = guard.get_inner().as_raw_fd();
+                match guard.try_io(|_| read_fd(fd, &mut buffer)) {
+                    Ok(Ok(0)) => {
+                        return Err(io::Error::new(
+                            io::ErrorKind::UnexpectedEof,
+                            "device disconnected",
+                        ))
+                    }
+                    Ok(Ok(n)) => {
+                        self.lines.extend(&buffer[..n]);
+                        return Ok(n);
+                    }
+                    Ok(Err(e)) => return Err(e),
+                    Err(_would_block) => continue,
+                }
+            }
+        }
+    }
+
+    impl Transport for SerialTransport {
+        fn send_frame<'a>(&'a mut self, frame: &'a [u8]) -> TransportFuture<'a, ()> {
+            Box::pin(async move {
+                let mut written = 0;
+                while written < frame.len() {
+                    let mut guard = self
+                        .port
+                        .writable_mut()
+                        .await
+                        .map_err(|e| AppError::Io(format!("Error while sending: {}", e)))?;
+                    let fd = guard.get_inner().as_raw_fd();
+                    match guard.try_io(|_| write_fd(fd, &frame[written..])) {
+                        Ok(Ok(n)) => written += n,
+                        Ok(Err(e)) => {
+                            return Err(AppError::Io(format!("Error while sending: {}", e)))
+                        }
+                        Err(_would_block) => continue,
+                    }
+                }
+                Ok(())
+            })
+        }
+
+        fn receive_line(&mut self) -> TransportFuture<'_, String> {
+            Box::pin(async move {
+                loop {
+                    if let Some(line) = self.lines.next_line() {
+                        return Ok(line);
+                    }
+                    self.read_chunk()
+                        .await
+                        .map_err(|e| AppError::Io(format!("Error while reading: {}", e)))?;
+                }
+            })
+        }
+
+        fn reset(&mut self) -> TransportFuture<'_, ()> {
+            Box::pin(async move {
+                while let Ok(result) =
+                    tokio::time::timeout(DRAIN_QUIET_PERIOD, self.read_chunk()).await
+                {
+                    result.map_err(|e| AppError::Io(format!("Error while reading: {}", e)))?;
+                }
+                self.lines.clear();
+                Ok(())
+            })
+        }
+
+        fn describe(&self) -> String {
+            format!("serial port {}", self.name)
+        }
+    }
+
+    fn set_nonblocking(fd: RawFd) -> io::Result<()> {
+        // SAFETY: `fd` is a valid descriptor owned by the open `TTYPort`.
+        unsafe {
+            let flags = libc::fcntl(fd, libc::F_GETFL);
+            if flags < 0 || libc::fcntl(fd, libc::F_SETFL, flags | libc::O_NONBLOCK) < 0 {
+                return Err(io::Error::last_os_error());
+            }
+        }
+        Ok(())
+    }
+
+    // `TTYPort`'s own `Read`/`Write` impls poll with their timeout first, which
+    // would block inside the reactor; talk to the descriptor directly instead.
+    fn read_fd(fd: RawFd, buffer: &mut [u8]) -> io::Result<usize> {
+        // SAFETY: the pointer and length come from a valid mutable slice.
+        let n = unsafe { libc::read(fd, buffer.as_mut_ptr().cast(), buffer.len()) };
+        if n < 0 {
+            Err(io::Error::last_os_error())
+        } else {
+            Ok(n as usize)
+        }
+    }
+
+    fn write_fd(fd: RawFd, data: &[u8]) -> io::Result<usize> {
+        // SAFETY: the pointer and length come from a valid slice.
+        let n = unsafe { libc::write(fd, data.as_ptr().cast(), data.len()) };
+        if n < 0 {
+            Err(io::Error::last_os_error())
+        } else {
+            Ok(n as usize)
+        }
+    }
+}
+
+#[cfg(not(unix))]
+mod blocking {
+    use super::{LineBuffer, Transport, TransportFuture, DRAIN_QUIET_PERIOD};
+    use crate::error::AppError;
+    use std::io::{Read, Write};
+    use std::sync::{Arc, Mutex};
+
+    struct Port {
+        port: Box<dyn serialport::SerialPort>,
+        lines: LineBuffer,
+    }
+
+    /// [`Transport`] over a serial port without readiness notifications.
+    ///
+    /// Reads run in tokio's blocking pool with a short port timeout, so a
+    /// cancelled `receive_line` only leaves a short read behind, whose bytes
+    /// still end up in the shared line buffer.
+    pub struct SerialTransport {
+        inner: Arc<Mutex<Port>>,
+        name: String,
+    }
+
+    impl SerialTransport {
+        pub fn open(port_name: &str, baud_rate: u32) -> Result<Self, AppError> {
+            let port = serialport::new(port_name, baud_rate)
+                .timeout(DRAIN_QUIET_PERIOD)
+                .open()
+                .map_err(|e| AppError::Io(format!("Error while opening {}: {}", port_name, e)))?;
+            Ok(Self {
+                inner: Arc::new(Mutex::new(Port {
+                    port,
+                    lines: LineBuffer::default(),
+                })),
+                name: port_name.to_string(),
+            })
+        }
+
+        /// Reads whatever arrives within the port timeout; `Ok(0)` means silence.
+        async fn read_chunk(&self) -> Result<usize, AppError> {
+            let inner = Arc::clone(&self.inner);
+            tokio::task::spawn_blocking(move || {
+                let mut inner = inner.lock().unwrap();
+                let mut buffer = [0u8; 256];
+                match inner.port.read(&mut buffer) {
+                    Ok(n) => {
+                        inner.lines.extend(&buffer[..n]);
+                        Ok(n)
+                    }
+                    Err(ref e) if e.kind() == std::io::ErrorKind::TimedOut => Ok(0),
+                    Err(e) => Err(AppError::Io(format!("Error while reading: {}", e))),
+                }
+            })
+            .await
+            .map_err(|e| AppError::Io(format!("Error while reading: {}", e)))?
+        }
+    }
+
+    impl Transport for SerialTransport {
+        fn send_frame<'a>(&'a mut self, frame: &'a [u8]) -> TransportFuture<'a, ()> {
+            let inner = Arc::clone(&self.inner);
+            let frame = frame.to_vec();
+            Box::pin(async move {
+                tokio::task::spawn_blocking(move || inner.lock().unwrap().port.write_all(&frame))
+                    .await
+                    .map_err(|e| AppError::Io(format!("Error while sending: {}", e)))?
+                    .map_err(|e| AppError::Io(format!("Error while sending: {}", e)))
+            })
+        }
+
+        fn receive_line(&mut self) -> TransportFuture<'_, String> {
+            Box::pin(async move {
+                loop {
+                    if let Some(line) = self.inner.lock().unwrap().lines.next_line() {
+                        return Ok(line);
+                    }
+                    self.read_chunk().await?;
+                }
+            })
+        }
+
+        fn reset(&mut self) -> TransportFuture<'_, ()> {
+            Box::pin(async move {
+                while self.read_chunk().await? > 0 {}
+                self.inner.lock().unwrap().lines.clear();
+                Ok(())
+            })
+        }
+
+        fn describe(&self) -> String {
+            format!("serial port {}", self.name)
+        }
+    }
+}
@@ -1,8 +1,14 @@
+use crate::protocol::Command;
+
 pub fn make_update_string(power: i32, charge: i32, time: i32, eeprom: i32, active: i32) -> String {
-    format!(
-        "UPDATE({}, {}, {}, {}, {})",
-        eeprom, power, charge, time, active
-    )
+    Command::Update {
+        eeprom,
+        power,
+        charge,
+        time,
+        active,
+    }
+    .encode()
 }
 
 pub fn make_led_string(
@@ -11,10 +17,13 @@ pub fn make_led_string(
     forward: bool,
     pulse_frequenz: i32,
 ) -> String {
-    format!(
-        "LED({}, {}, {}, {}, {}, {})",
-        led_id, forward as i32, color.0, color.1, color.2, pulse_frequenz
-    )
+    Command::Led {
+        led_id,
+        forward,
+        color,
+        pulse_frequenz,
+    }
+    .encode()
 }
 
 pub fn format_response(response: &str) -> Result<serde_json::Value, String> {
@@ -38,7 +38,10 @@ mod converter_tests {
     fn test_hex_to_rgb_invalid_length() {
         let result = hex_to_rgb("#FFF");
         assert!(result.is_err());
-        assert_eq!(result.unwrap_err(), "Hex color must be 6 digits long");
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "Hex color must be 6 digits long"
+        );
     }
 
     #[test]
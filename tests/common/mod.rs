@@ -1,24 +1,52 @@
 //! Common test utilities
 
 use axum::{
-    routing::{get, post},
+    routing::{get, post, put},
     Router,
 };
-use std::sync::Arc;
-use tokio::sync::Mutex;
 use tower_http::cors::CorsLayer;
+use webserver::serial::Boards;
 
 pub fn create_test_app() -> Router {
-    let state = webserver::models::AppState {
-        arduino: Arc::new(Mutex::new(None)),
-        scan_cache: Arc::new(Mutex::new(None)),
-    };
+    let state = webserver::models::AppState::new(Boards::default());
+
+    let admin = Router::new()
+        .route(
+            "/api/admin/calibration",
+            get(webserver::handlers::calibration)
+                .put(webserver::handlers::replace_calibration)
+                .delete(webserver::handlers::reset_calibration),
+        )
+        .route(
+            "/api/admin/calibration/slots/:label",
+            put(webserver::handlers::calibrate_slot),
+        )
+        .route(
+            "/api/admin/calibration/lines/:key",
+            put(webserver::handlers::calibrate_line),
+        )
+        .route_layer(axum::middleware::from_fn_with_state(
+            state.clone(),
+            webserver::handlers::require_admin,
+        ));
 
     Router::new()
         .route("/api/update", post(webserver::handlers::update))
         .route("/api/stop", post(webserver::handlers::stop))
         .route("/api/led", post(webserver::handlers::led))
+        .route("/api/leds", post(webserver::handlers::leds))
         .route("/api/scan", get(webserver::handlers::scan))
+        .route("/api/modules", get(webserver::handlers::modules))
+        .route("/api/layout", get(webserver::handlers::layout))
+        .route("/api/events", get(webserver::handlers::events))
+        .route("/api/ws", get(webserver::handlers::ws))
+        .route("/api/serial/stats", get(webserver::handlers::serial_stats))
+        .route("/api/boards", get(webserver::handlers::boards))
+        .route("/api/ports", get(webserver::handlers::ports))
+        .route("/api/firmware", get(webserver::handlers::firmware))
+        .route("/api/connection", get(webserver::handlers::connection))
         .layer(CorsLayer::permissive())
+        .merge(admin)
+        .layer(axum::middleware::from_fn(webserver::error::request_id))
         .with_state(state)
 }
@@ -5,32 +5,77 @@ use axum::{
     http::{header, Request, StatusCode},
     Router,
 };
+use futures::StreamExt;
 use serde_json::json;
 use std::sync::Arc;
-use tokio::sync::Mutex;
+use std::time::Duration;
 use tower::ServiceExt;
+use webserver::inventory::{poll_topology, ModuleInfo, ModuleType};
+use webserver::protocol::{FirmwareInfo, Framing};
+use webserver::serial::{
+    Boards, Connection, DiscoveryConfig, DiscoveryRule, LinkConfig, MockTransport, SerialHandle,
+};
 
 // Mock application state for testing
 fn create_test_state() -> webserver::models::AppState {
-    webserver::models::AppState {
-        arduino: Arc::new(Mutex::new(None)), // No actual Arduino connection
-        scan_cache: Arc::new(Mutex::new(None)),
-    }
+    webserver::models::AppState::new(Boards::default())
+}
+
+// Application state with a scripted transport standing in for the Arduino
+fn create_mock_state(mock: &MockTransport) -> webserver::models::AppState {
+    webserver::models::AppState::new(Boards::single(
+        "mock",
+        SerialHandle::spawn(Some(Box::new(mock.clone()))),
+    ))
 }
 
 // Helper to create test router
 fn create_test_router() -> Router {
-    use axum::routing::{get, post};
+    create_test_router_with_state(create_test_state())
+}
+
+fn create_test_router_with_state(state: webserver::models::AppState) -> Router {
+    use axum::routing::{get, post, put};
     use tower_http::cors::CorsLayer;
 
-    let state = create_test_state();
+    let admin = Router::new()
+        .route(
+            "/api/admin/calibration",
+            get(webserver::handlers::calibration)
+                .put(webserver::handlers::replace_calibration)
+                .delete(webserver::handlers::reset_calibration),
+        )
+        .route(
+            "/api/admin/calibration/slots/:label",
+            put(webserver::handlers::calibrate_slot),
+        )
+        .route(
+            "/api/admin/calibration/lines/:key",
+            put(webserver::handlers::calibrate_line),
+        )
+        .route_layer(axum::middleware::from_fn_with_state(
+            state.clone(),
+            webserver::handlers::require_admin,
+        ));
 
     Router::new()
         .route("/api/update", post(webserver::handlers::update))
         .route("/api/stop", post(webserver::handlers::stop))
         .route("/api/led", post(webserver::handlers::led))
+        .route("/api/leds", post(webserver::handlers::leds))
         .route("/api/scan", get(webserver::handlers::scan))
+        .route("/api/modules", get(webserver::handlers::modules))
+        .route("/api/layout", get(webserver::handlers::layout))
+        .route("/api/events", get(webserver::handlers::events))
+        .route("/api/ws", get(webserver::handlers::ws))
+        .route("/api/serial/stats", get(webserver::handlers::serial_stats))
+        .route("/api/boards", get(webserver::handlers::boards))
+        .route("/api/ports", get(webserver::handlers::ports))
+        .route("/api/firmware", get(webserver::handlers::firmware))
+        .route("/api/connection", get(webserver::handlers::connection))
         .layer(CorsLayer::permissive())
+        .merge(admin)
+        .layer(axum::middleware::from_fn(webserver::error::request_id))
         .with_state(state)
 }
 
@@ -293,3 +338,1519 @@ async fn test_missing_content_type() {
         response.status().is_client_error() || response.status() == StatusCode::SERVICE_UNAVAILABLE
     );
 }
+
+async fn json_body(response: axum::response::Response) -> serde_json::Value {
+    let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    serde_json::from_slice(&bytes).unwrap()
+}
+
+fn post_json(uri: &str, body: serde_json::Value) -> Request<Body> {
+    Request::builder()
+        .method("POST")
+        .uri(uri)
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(Body::from(body.to_string()))
+        .unwrap()
+}
+
+#[tokio::test]
+async fn test_update_endpoint_with_mock_arduino() {
+    let mock = MockTransport::new();
+    mock.push_reply("OK: Updated");
+    let app = create_test_router_with_state(create_mock_state(&mock));
+
+    let response = app
+        .oneshot(post_json(
+            "/api/update",
+            json!({"power": 100, "charge": 50, "time": 30, "eeprom": 1}),
+        ))
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = json_body(response).await;
+    assert_eq!(body["sent"], "UPDATE(1, 100, 50, 30, 1)");
+    assert_eq!(body["arduino_response"], "OK: Updated");
+    assert_eq!(mock.sent(), vec!["<UPDATE(1, 100, 50, 30, 1)>\n"]);
+}
+
+#[tokio::test]
+async fn test_events_endpoint_streams_command_outcomes() {
+    let mock = MockTransport::new();
+    mock.push_reply("OK: Updated");
+    let app = create_test_router_with_state(create_mock_state(&mock));
+
+    let response = app
+        .clone()
+        .oneshot(
+            Request::builder()
+                .uri("/api/events")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        response.headers()[header::CONTENT_TYPE],
+        "text/event-stream"
+    );
+    let mut stream = response.into_body().into_data_stream();
+
+    let update = app
+        .oneshot(post_json(
+            "/api/update",
+            json!({"power": 100, "charge": 50, "time": 30, "eeprom": 1}),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(update.status(), StatusCode::OK);
+
+    let chunk = tokio::time::timeout(Duration::from_secs(1), stream.next())
+        .await
+        .unwrap()
+        .unwrap()
+        .unwrap();
+    let text = String::from_utf8(chunk.to_vec()).unwrap();
+    let data = text
+        .lines()
+        .find_map(|line| line.strip_prefix("data: "))
+        .unwrap();
+    assert!(text.starts_with("event: command\n"), "{}", text);
+    let outcome: serde_json::Value = serde_json::from_str(data).unwrap();
+    assert_eq!(outcome["command"], "UPDATE(1, 100, 50, 30, 1)");
+    assert_eq!(outcome["ok"], true);
+    assert_eq!(outcome["response"], "OK: Updated");
+}
+
+#[tokio::test]
+async fn test_update_endpoint_arduino_error_keeps_connection() {
+    let mock = MockTransport::new();
+    mock.push_reply("ERROR: unknown EEPROM 99")
+        .push_reply("OK: Updated");
+    let state = create_mock_state(&mock);
+    let app = create_test_router_with_state(state.clone());
+
+    let response = app
+        .clone()
+        .oneshot(post_json(
+            "/api/update",
+            json!({"power": 100, "charge": 50, "time": 30, "eeprom": 99}),
+        ))
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    let body = json_body(response).await;
+    assert_eq!(body["code"], "DEVICE_ERROR");
+    assert_eq!(body["message"], "unknown EEPROM 99");
+    assert!(state.arduino.is_connected());
+
+    let response = app
+        .oneshot(post_json(
+            "/api/update",
+            json!({"power": 100, "charge": 50, "time": 30, "eeprom": 1}),
+        ))
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::OK);
+}
+
+#[tokio::test]
+async fn test_update_endpoint_timeout() {
+    let mock = MockTransport::new();
+    mock.push_timeout();
+    let state = create_mock_state(&mock);
+    let app = create_test_router_with_state(state.clone());
+
+    let response = app
+        .oneshot(post_json(
+            "/api/update",
+            json!({"power": 100, "charge": 50, "time": 30, "eeprom": 1}),
+        ))
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::GATEWAY_TIMEOUT);
+    let body = json_body(response).await;
+    assert_eq!(body["code"], "SERIAL_TIMEOUT");
+    assert_eq!(body["details"]["timeout_ms"], 1000);
+    assert!(!state.arduino.is_connected());
+}
+
+#[tokio::test]
+async fn test_led_endpoint_with_mock_arduino() {
+    let mock = MockTransport::new();
+    mock.push_reply("OK: LED");
+    let app = create_test_router_with_state(create_mock_state(&mock));
+
+    let response = app
+        .oneshot(post_json(
+            "/api/led",
+            json!({"ledID": 4, "color": "#FF5733", "forward": false, "pulseFrequenz": 2}),
+        ))
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = json_body(response).await;
+    assert_eq!(body["parameters"]["color"], json!([255, 87, 51]));
+    assert_eq!(mock.sent(), vec!["<LED(4, 0, 255, 87, 51, 2)>\n"]);
+}
+
+#[tokio::test]
+async fn test_led_endpoint_invalid_color_with_mock_arduino() {
+    let mock = MockTransport::new();
+    let app = create_test_router_with_state(create_mock_state(&mock));
+
+    let response = app
+        .oneshot(post_json(
+            "/api/led",
+            json!({"ledID": 1, "color": "invalid", "forward": true, "pulseFrequenz": 5}),
+        ))
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    assert!(mock.sent().is_empty());
+}
+
+#[tokio::test]
+async fn test_leds_endpoint_with_mock_arduino() {
+    let mock = MockTransport::new();
+    mock.push_reply("OK: LED").push_reply("OK: LED");
+    let app = create_test_router_with_state(create_mock_state(&mock));
+
+    let response = app
+        .oneshot(post_json(
+            "/api/leds",
+            json!({"leds": [
+                {"ledID": 4, "color": "#FF5733", "forward": false, "pulseFrequenz": 2},
+                {"ledID": 5, "color": "#00FF00", "forward": true, "pulseFrequenz": 1}
+            ]}),
+        ))
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = json_body(response).await;
+    assert_eq!(body["message"], "2 LEDs set");
+    assert_eq!(
+        mock.sent(),
+        vec![
+            "<LED(4, 0, 255, 87, 51, 2)>\n",
+            "<LED(5, 1, 0, 255, 0, 1)>\n"
+        ]
+    );
+}
+
+#[tokio::test]
+async fn test_leds_endpoint_rejects_invalid_batches() {
+    let mock = MockTransport::new();
+    let app = create_test_router_with_state(create_mock_state(&mock));
+
+    for body in [
+        json!({"leds": []}),
+        json!({"leds": [{"ledID": 300, "color": "#FF5733", "forward": true, "pulseFrequenz": 1}]}),
+        json!({"leds": [{"ledID": 1, "color": "invalid", "forward": true, "pulseFrequenz": 1}]}),
+    ] {
+        let response = app
+            .clone()
+            .oneshot(post_json("/api/leds", body))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+    assert!(mock.sent().is_empty());
+}
+
+#[tokio::test]
+async fn test_stop_endpoint_with_mock_arduino() {
+    let mock = MockTransport::new();
+    mock.push_reply("OK: Stopped");
+    let app = create_test_router_with_state(create_mock_state(&mock));
+
+    let response = app
+        .oneshot(post_json("/api/stop", json!({"eeprom": 3})))
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = json_body(response).await;
+    assert_eq!(body["arduino_response"], "OK: Stopped");
+    assert_eq!(mock.sent(), vec!["<STOP(3)>\n"]);
+}
+
+#[tokio::test]
+async fn test_event_before_reply_is_not_the_answer() {
+    let mock = MockTransport::new();
+    mock.push_reply(r#"EVENT BUTTON: {"EEPROM":3,"BUTTON":1}"#)
+        .push_reply("OK: Stopped");
+    let state = create_mock_state(&mock);
+    let mut events = state.arduino.subscribe();
+    let app = create_test_router_with_state(state);
+
+    let response = app
+        .oneshot(post_json("/api/stop", json!({"eeprom": 3})))
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = json_body(response).await;
+    assert_eq!(body["arduino_response"], "OK: Stopped");
+    let event = events.try_recv().unwrap();
+    assert_eq!(event.port, "mock");
+    assert_eq!(
+        serde_json::to_value(&event).unwrap()["event"],
+        "button_pressed"
+    );
+}
+
+#[tokio::test]
+async fn test_stop_endpoint_read_failure() {
+    let mock = MockTransport::new();
+    mock.push_failure("Error while reading: broken pipe");
+    let state = create_mock_state(&mock);
+    let app = create_test_router_with_state(state.clone());
+
+    let response = app
+        .oneshot(post_json("/api/stop", json!({})))
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::BAD_GATEWAY);
+    let body = json_body(response).await;
+    assert_eq!(body["code"], "SERIAL_IO");
+    assert_eq!(body["message"], "Error while reading: broken pipe");
+    assert_eq!(mock.sent(), vec!["<STOP()>\n"]);
+    assert!(!state.arduino.is_connected());
+}
+
+#[tokio::test]
+async fn test_scan_endpoint_with_mock_arduino() {
+    let scan = r#"SCAN: [{"EEPROM":1,"I2C":32,"TYPE":2}]"#;
+    let mock = MockTransport::new();
+    mock.push_reply(scan).push_reply(scan);
+    let state = create_mock_state(&mock);
+    let app = create_test_router_with_state(state.clone());
+
+    let response = app
+        .oneshot(
+            Request::builder()
+                .method("GET")
+                .uri("/api/scan")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = json_body(response).await;
+    assert_eq!(body["data"][0]["TYPE"], 2);
+    assert_eq!(body["consensus"]["agreeing_samples"], 2);
+    assert_eq!(mock.sent().len(), 2);
+    assert!(state.scan_cache.get().is_some());
+}
+
+#[tokio::test]
+async fn test_scan_endpoint_inconsistent_uses_cache() {
+    let mock = MockTransport::new();
+    mock.push_reply(r#"SCAN: [{"EEPROM":1,"I2C":32,"TYPE":1}]"#)
+        .push_reply(r#"SCAN: [{"EEPROM":2,"I2C":33,"TYPE":2}]"#)
+        .push_reply(r#"SCAN: [{"EEPROM":3,"I2C":34,"TYPE":3}]"#);
+    let state = create_mock_state(&mock);
+    state.scan_cache.update(
+        vec![ModuleInfo {
+            eeprom: 7,
+            i2c: 40,
+            module_type: ModuleType::Household,
+        }],
+        None,
+    );
+    let app = create_test_router_with_state(state);
+
+    let response = app
+        .oneshot(
+            Request::builder()
+                .method("GET")
+                .uri("/api/scan")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = json_body(response).await;
+    assert_eq!(body["arduino_response"], "No consensus among 3 samples");
+    assert_eq!(body["data"][0]["EEPROM"], 7);
+    assert_eq!(body["consensus"]["agreeing_samples"], 0);
+    assert_eq!(mock.sent().len(), 3);
+}
+
+#[tokio::test]
+async fn test_scan_endpoint_drops_flaky_module() {
+    let mock = MockTransport::new();
+    mock.push_reply(r#"SCAN: [{"EEPROM":1,"I2C":32,"TYPE":1},{"EEPROM":2,"I2C":33,"TYPE":2}]"#)
+        .push_reply(r#"SCAN: [{"EEPROM":1,"I2C":32,"TYPE":1}]"#)
+        .push_reply(r#"SCAN: [{"EEPROM":1,"I2C":32,"TYPE":1},{"EEPROM":3,"I2C":34,"TYPE":3}]"#);
+    let state = create_mock_state(&mock);
+    let app = create_test_router_with_state(state.clone());
+
+    let response = app
+        .oneshot(
+            Request::builder()
+                .uri("/api/scan")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = json_body(response).await;
+    assert_eq!(body["data"], json!([{"EEPROM": 1, "I2C": 32, "TYPE": 1}]));
+    assert_eq!(body["consensus"]["samples"], 3);
+    assert_eq!(body["consensus"]["required_votes"], 2);
+    assert_eq!(body["consensus"]["disputed"][0]["module"]["EEPROM"], 2);
+    assert_eq!(body["consensus"]["disputed"][0]["votes"], 1);
+    assert_eq!(body["consensus"]["disputed"][0]["accepted"], false);
+    assert_eq!(
+        state.scan_cache.get().map(|snapshot| snapshot.modules),
+        Some(vec![ModuleInfo {
+            eeprom: 1,
+            i2c: 32,
+            module_type: ModuleType::Solar,
+        }])
+    );
+}
+
+#[tokio::test]
+async fn test_scan_endpoint_answers_from_poller_cache() {
+    let mock = MockTransport::new();
+    let state = webserver::models::AppState {
+        scan_poll: Some(Duration::from_secs(2)),
+        ..create_mock_state(&mock)
+    };
+    state.scan_cache.update(
+        vec![ModuleInfo {
+            eeprom: 3,
+            i2c: 34,
+            module_type: ModuleType::Battery,
+        }],
+        None,
+    );
+    let scanned_at_ms = state.scan_cache.get().unwrap().scanned_at_ms;
+    let app = create_test_router_with_state(state);
+
+    let response = app
+        .oneshot(
+            Request::builder()
+                .uri("/api/scan")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = json_body(response).await;
+    assert_eq!(body["data"], json!([{"EEPROM": 3, "I2C": 34, "TYPE": 3}]));
+    assert_eq!(body["scanned_at_ms"], scanned_at_ms);
+    assert!(mock.sent().is_empty());
+}
+
+#[tokio::test]
+async fn test_poller_tracks_topology_changes() {
+    let before = r#"SCAN: [{"EEPROM":1,"I2C":32,"TYPE":1},{"EEPROM":2,"I2C":33,"TYPE":2}]"#;
+    let after = r#"SCAN: [{"EEPROM":1,"I2C":35,"TYPE":1}]"#;
+    let mock = MockTransport::new();
+    mock.push_reply(before)
+        .push_reply(before)
+        .push_reply(after)
+        .push_reply(after);
+    let state = create_mock_state(&mock);
+    let poller = tokio::spawn(poll_topology(state.clone(), Duration::from_millis(10)));
+
+    let expected = vec![ModuleInfo {
+        eeprom: 1,
+        i2c: 35,
+        module_type: ModuleType::Solar,
+    }];
+    let deadline = tokio::time::Instant::now() + Duration::from_secs(2);
+    while state.scan_cache.get().map(|snapshot| snapshot.modules) != Some(expected.clone()) {
+        assert!(
+            tokio::time::Instant::now() < deadline,
+            "poller never saw the change"
+        );
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    poller.abort();
+
+    assert_eq!(mock.sent().len(), 4);
+    let diagnostics = state.scan_cache.get().unwrap().diagnostics.unwrap();
+    assert_eq!(diagnostics.agreeing_samples, 2);
+}
+
+#[tokio::test]
+async fn test_modules_endpoint_scans_and_names_types() {
+    let scan = r#"SCAN: [{"EEPROM":1,"I2C":32,"TYPE":5},{"EEPROM":4,"I2C":35,"TYPE":9}]"#;
+    let mock = MockTransport::new();
+    mock.push_reply(scan).push_reply(scan);
+    let state = create_mock_state(&mock);
+    let app = create_test_router_with_state(state.clone());
+
+    let response = app
+        .oneshot(
+            Request::builder()
+                .uri("/api/modules")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = json_body(response).await;
+    assert_eq!(
+        body["data"],
+        json!([
+            {"eeprom": 1, "i2c": 32, "slot": 1, "type": "charging_station", "type_code": 5},
+            {"eeprom": 4, "i2c": 35, "slot": 4, "type": "unknown", "type_code": 9},
+        ])
+    );
+    assert_eq!(body["message"], "Unknown module types: 4 (9)");
+    assert_eq!(mock.sent().len(), 2);
+    assert_eq!(
+        state
+            .scan_cache
+            .get()
+            .map(|snapshot| snapshot.modules.len()),
+        Some(2)
+    );
+}
+
+#[tokio::test]
+async fn test_modules_endpoint_answers_from_cache() {
+    let mock = MockTransport::new();
+    let state = create_mock_state(&mock);
+    state.scan_cache.update(
+        vec![ModuleInfo {
+            eeprom: 2,
+            i2c: 33,
+            module_type: ModuleType::Wind,
+        }],
+        None,
+    );
+    let app = create_test_router_with_state(state);
+
+    let response = app
+        .oneshot(
+            Request::builder()
+                .uri("/api/modules")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = json_body(response).await;
+    assert_eq!(body["data"][0]["type"], "wind");
+    assert!(body.get("message").is_none());
+    assert!(mock.sent().is_empty());
+}
+
+#[tokio::test]
+async fn test_layout_endpoint_places_modules_in_slots() {
+    let mock = MockTransport::new();
+    let state = create_mock_state(&mock);
+    state.scan_cache.update(
+        vec![
+            ModuleInfo {
+                eeprom: 9,
+                i2c: 32,
+                module_type: ModuleType::Solar,
+            },
+            ModuleInfo {
+                eeprom: 20,
+                i2c: 80,
+                module_type: ModuleType::Wind,
+            },
+        ],
+        None,
+    );
+    let app = create_test_router_with_state(state);
+
+    let response = app
+        .oneshot(
+            Request::builder()
+                .uri("/api/layout")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = json_body(response).await;
+    let slots = body["data"]["slots"].as_array().unwrap();
+    assert_eq!(slots.len(), 16);
+    assert_eq!(slots[0]["shape"], "square");
+    assert_eq!(slots[0]["i2c"], 31);
+    assert!(slots[0]["module"].is_null());
+
+    let slot = &slots[1];
+    assert_eq!(slot["label"], 1);
+    assert_eq!(slot["shape"], "hexagon");
+    assert_eq!(slot["hex"], json!({"q": 0, "r": 2}));
+    assert_eq!(
+        (slot["row"].clone(), slot["col"].clone()),
+        (json!(2), json!(0))
+    );
+    assert_eq!(slot["neighbours"], json!([4]));
+    assert_eq!(slot["lines"], json!([0, 1]));
+    assert_eq!(slot["i2c"], 32);
+    assert_eq!(slot["module"]["eeprom"], 9);
+    assert_eq!(slot["module"]["type"], "solar");
+
+    let lines = body["data"]["lines"].as_array().unwrap();
+    assert_eq!(lines.len(), 30);
+    assert_eq!(lines[0]["key"], "line_2_0_1_-1");
+    assert_eq!(lines[0]["slots"], json!([1, null]));
+    assert_eq!(lines[1]["slots"], json!([4, 1]));
+    assert_eq!(body["message"], "Modules outside the layout: 20 (I2C 80)");
+    assert!(body["scanned_at_ms"].is_u64());
+    assert!(mock.sent().is_empty());
+}
+
+#[tokio::test]
+async fn test_scan_endpoint_arduino_error_keeps_connection() {
+    let mock = MockTransport::new();
+    mock.push_reply("ERROR 3: I2C bus busy");
+    let state = create_mock_state(&mock);
+    let app = create_test_router_with_state(state.clone());
+
+    let response = app
+        .oneshot(
+            Request::builder()
+                .uri("/api/scan")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    let body = json_body(response).await;
+    assert_eq!(body["message"], "I2C bus busy");
+    assert_eq!(body["details"]["firmware_code"], 3);
+    assert!(state.arduino.is_connected());
+}
+
+#[tokio::test]
+async fn test_error_body_format() {
+    let app = create_test_router();
+
+    let response = app
+        .oneshot(
+            Request::builder()
+                .uri("/api/scan")
+                .header("x-request-id", "client-42")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(response.headers()["x-request-id"], "client-42");
+    let body = json_body(response).await;
+    assert_eq!(body["status"], "error");
+    assert_eq!(body["code"], "ARDUINO_NOT_CONNECTED");
+    assert_eq!(body["message"], "Arduino not connected - no port available");
+    assert_eq!(body["request_id"], "client-42");
+}
+
+#[tokio::test]
+async fn test_invalid_json_uses_error_format() {
+    let app = create_test_router();
+
+    let response = app
+        .oneshot(post_json("/api/led", json!({"ledID": 1})))
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    let request_id = response.headers()["x-request-id"]
+        .to_str()
+        .unwrap()
+        .to_string();
+    let body = json_body(response).await;
+    assert_eq!(body["code"], "INVALID_REQUEST");
+    assert_eq!(body["request_id"], request_id.as_str());
+}
+
+#[tokio::test]
+async fn test_update_endpoint_with_sequenced_framing() {
+    let mock = MockTransport::new();
+    mock.push_reply("Booting...").push_reply("#1 OK: Updated");
+    let state = webserver::models::AppState::new(Boards::single(
+        "mock",
+        SerialHandle::with_link(
+            Some(Box::new(mock.clone())),
+            LinkConfig {
+                framing: Framing::Sequenced,
+                ..LinkConfig::default()
+            },
+        ),
+    ));
+    let app = create_test_router_with_state(state);
+
+    let response = app
+        .oneshot(post_json(
+            "/api/update",
+            json!({"power": 100, "charge": 50, "time": 30, "eeprom": 1}),
+        ))
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = json_body(response).await;
+    assert_eq!(body["arduino_response"], "OK: Updated");
+    assert_eq!(mock.sent(), vec!["<#1 UPDATE(1, 100, 50, 30, 1)>\n"]);
+}
+
+#[tokio::test]
+async fn test_serial_stats_endpoint() {
+    let mock = MockTransport::new();
+    mock.push_reply("OK: Stopped");
+    let app = create_test_router_with_state(create_mock_state(&mock));
+
+    let response = app
+        .clone()
+        .oneshot(post_json("/api/stop", json!({})))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let response = app
+        .oneshot(
+            Request::builder()
+                .uri("/api/serial/stats")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = json_body(response).await;
+    let link = &body["data"][0]["link"];
+    assert_eq!(body["data"][0]["port"], "mock");
+    assert_eq!(link["framing"], "legacy");
+    assert_eq!(link["checksum"], false);
+    assert_eq!(link["frames_sent"], 1);
+    assert_eq!(link["replies_received"], 1);
+    assert_eq!(link["retransmissions"], 0);
+}
+
+#[tokio::test]
+async fn test_two_boards_are_routed_by_module() {
+    let left = MockTransport::new();
+    let right = MockTransport::new();
+    left.push_reply(r#"SCAN: [{"EEPROM":1,"I2C":32,"TYPE":1}]"#)
+        .push_reply(r#"SCAN: [{"EEPROM":1,"I2C":32,"TYPE":1}]"#);
+    right
+        .push_reply(r#"SCAN: [{"EEPROM":7,"I2C":32,"TYPE":4}]"#)
+        .push_reply(r#"SCAN: [{"EEPROM":7,"I2C":32,"TYPE":4}]"#)
+        .push_reply("OK: Updated");
+
+    let arduino = Boards::single("left", SerialHandle::spawn(Some(Box::new(left.clone()))));
+    arduino.insert("right", SerialHandle::spawn(Some(Box::new(right.clone()))));
+    let state = webserver::models::AppState::new(arduino);
+    let app = create_test_router_with_state(state);
+
+    let response = app
+        .clone()
+        .oneshot(
+            Request::builder()
+                .uri("/api/scan")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = json_body(response).await;
+    assert_eq!(
+        body["data"],
+        json!([
+            {"EEPROM": 1, "I2C": 32, "TYPE": 1},
+            {"EEPROM": 7, "I2C": 32, "TYPE": 4}
+        ])
+    );
+
+    let response = app
+        .clone()
+        .oneshot(post_json(
+            "/api/update",
+            json!({"power": 100, "charge": 50, "time": 30, "eeprom": 7}),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(left.sent().len(), 2);
+    assert_eq!(
+        right.sent().last().unwrap(),
+        "<UPDATE(7, 100, 50, 30, 1)>\n"
+    );
+
+    let response = app
+        .oneshot(
+            Request::builder()
+                .uri("/api/boards")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    let body = json_body(response).await;
+    assert_eq!(body["data"][0]["eeproms"], json!([1]));
+    assert_eq!(body["data"][1]["eeproms"], json!([7]));
+}
+
+#[tokio::test]
+async fn test_ports_endpoint_lists_configured_path() {
+    let mock = MockTransport::new();
+    let state = webserver::models::AppState {
+        discovery: Arc::new(DiscoveryConfig {
+            rules: vec![DiscoveryRule {
+                name: Some("bench".to_string()),
+                path: Some("mock".to_string()),
+                ..DiscoveryRule::default()
+            }],
+            ..DiscoveryConfig::default()
+        }),
+        ..create_mock_state(&mock)
+    };
+    let app = create_test_router_with_state(state);
+
+    let response = app
+        .oneshot(
+            Request::builder()
+                .uri("/api/ports")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = json_body(response).await;
+    let ports = body["data"].as_array().unwrap();
+    let bench = ports.iter().find(|port| port["port"] == "mock").unwrap();
+    assert_eq!(bench["kind"], "configured");
+    assert_eq!(bench["matched_rule"], "bench");
+    assert_eq!(bench["connected"], true);
+}
+
+#[tokio::test]
+async fn test_identified_board_uses_announced_features() {
+    let mock = MockTransport::new();
+    mock.push_reply("#1 OK: LED");
+    let arduino = Boards::default();
+    arduino
+        .attach(Connection {
+            port: "mock".to_string(),
+            transport: Box::new(mock.clone()),
+            firmware: FirmwareInfo {
+                name: "energy-table".to_string(),
+                version: "2.1.0".to_string(),
+                protocol: 1,
+                capabilities: vec!["sequenced".to_string()],
+                leds: None,
+                identified: true,
+            },
+            baud_rate: Some(115200),
+        })
+        .await;
+    let app = create_test_router_with_state(webserver::models::AppState::new(arduino));
+
+    let response = app
+        .clone()
+        .oneshot(post_json(
+            "/api/led",
+            json!({"ledID": 4, "forward": true, "color": "#0000ff", "pulseFrequenz": 2}),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(mock.sent(), vec!["<#1 LED(4, 1, 0, 0, 255, 2)>\n"]);
+
+    let response = app
+        .clone()
+        .oneshot(
+            Request::builder()
+                .uri("/api/connection")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    let body = json_body(response).await;
+    assert_eq!(body["data"]["boards"][0]["baud_rate"], 115200);
+
+    let response = app
+        .oneshot(
+            Request::builder()
+                .uri("/api/firmware")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = json_body(response).await;
+    assert_eq!(body["data"][0]["port"], "mock");
+    assert_eq!(body["data"][0]["connected"], true);
+    assert_eq!(body["data"][0]["firmware"]["name"], "energy-table");
+    assert_eq!(
+        body["data"][0]["firmware"]["capabilities"],
+        json!(["sequenced"])
+    );
+}
+
+#[tokio::test]
+async fn test_firmware_endpoint_without_handshake() {
+    let mock = MockTransport::new();
+    let app = create_test_router_with_state(create_mock_state(&mock));
+
+    let response = app
+        .oneshot(
+            Request::builder()
+                .uri("/api/firmware")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    let body = json_body(response).await;
+    assert_eq!(body["data"][0]["firmware"], serde_json::Value::Null);
+}
+
+#[tokio::test]
+async fn test_connection_endpoint_reports_lost_board() {
+    let mock = MockTransport::new();
+    mock.push_failure("device unplugged");
+    let app = create_test_router_with_state(create_mock_state(&mock));
+
+    let response = app
+        .clone()
+        .oneshot(post_json("/api/stop", json!({})))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_GATEWAY);
+
+    let response = app
+        .oneshot(
+            Request::builder()
+                .uri("/api/connection")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = json_body(response).await;
+    assert_eq!(body["data"]["state"], "failed");
+    assert_eq!(body["data"]["boards"][0]["port"], "mock");
+    assert_eq!(body["data"]["boards"][0]["state"], "failed");
+    assert_eq!(body["data"]["boards"][0]["last_error"], "device unplugged");
+    assert_eq!(body["data"]["boards"][0]["reconnects"], 0);
+}
+
+#[tokio::test]
+async fn test_connection_endpoint_without_boards_is_searching() {
+    let app = create_test_router();
+
+    let response = app
+        .oneshot(
+            Request::builder()
+                .uri("/api/connection")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    let body = json_body(response).await;
+    assert_eq!(body["data"]["state"], "searching");
+    assert_eq!(body["data"]["boards"], json!([]));
+}
+
+#[tokio::test]
+async fn test_concurrent_commands_are_queued() {
+    use futures::future::join_all;
+
+    let mock = MockTransport::new();
+    for _ in 0..5 {
+        mock.push_reply("OK: Stopped");
+    }
+    let state = create_mock_state(&mock);
+
+    let requests = (1..=5).map(|eeprom| {
+        let app = create_test_router_with_state(state.clone());
+        async move {
+            app.oneshot(post_json("/api/stop", json!({ "eeprom": eeprom })))
+                .await
+                .unwrap()
+        }
+    });
+    let responses = join_all(requests).await;
+
+    for response in responses {
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+    let mut sent = mock.sent();
+    sent.sort();
+    assert_eq!(
+        sent,
+        (1..=5)
+            .map(|eeprom| format!("<STOP({})>\n", eeprom))
+            .collect::<Vec<_>>()
+    );
+    assert!(state.arduino.is_connected());
+}
+
+// Serves the router on a local port and opens a WebSocket to /api/ws
+async fn connect_ws(
+    state: webserver::models::AppState,
+) -> tokio_tungstenite::WebSocketStream<tokio_tungstenite::MaybeTlsStream<tokio::net::TcpStream>> {
+    connect_ws_as(state, None).await
+}
+
+// Same as connect_ws, sending `request_id` as X-Request-Id with the upgrade
+async fn connect_ws_as(
+    state: webserver::models::AppState,
+    request_id: Option<&str>,
+) -> tokio_tungstenite::WebSocketStream<tokio_tungstenite::MaybeTlsStream<tokio::net::TcpStream>> {
+    use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let app = create_test_router_with_state(state);
+    tokio::spawn(async move {
+        axum::serve(listener, app).await.unwrap();
+    });
+    let mut request = format!("ws://{}/api/ws", addr)
+        .into_client_request()
+        .unwrap();
+    if let Some(request_id) = request_id {
+        request
+            .headers_mut()
+            .insert("x-request-id", request_id.parse().unwrap());
+    }
+    let (socket, _) = tokio_tungstenite::connect_async(request).await.unwrap();
+    socket
+}
+
+// Reads WebSocket messages until `count` results arrived, returning results and events
+async fn read_ws_results(
+    socket: &mut tokio_tungstenite::WebSocketStream<
+        tokio_tungstenite::MaybeTlsStream<tokio::net::TcpStream>,
+    >,
+    count: usize,
+) -> (Vec<serde_json::Value>, Vec<serde_json::Value>) {
+    let (mut results, mut events) = (Vec::new(), Vec::new());
+    while results.len() < count {
+        let message = tokio::time::timeout(Duration::from_secs(10), socket.next())
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap();
+        let value: serde_json::Value = serde_json::from_str(message.to_text().unwrap()).unwrap();
+        match value["type"].as_str() {
+            Some("result") => results.push(value),
+            Some("event") => events.push(value),
+            other => panic!("unexpected message type {:?}", other),
+        }
+    }
+    (results, events)
+}
+
+#[tokio::test]
+async fn test_ws_command_gets_correlated_result_and_event() {
+    use futures::SinkExt;
+    use tokio_tungstenite::tungstenite::Message;
+
+    let mock = MockTransport::new();
+    mock.push_reply("OK: Updated");
+    let mut socket = connect_ws(create_mock_state(&mock)).await;
+
+    let update = json!({
+        "id": "slider-1",
+        "type": "update",
+        "power": 100,
+        "charge": 50,
+        "time": 30,
+        "eeprom": 1
+    });
+    socket
+        .send(Message::Text(update.to_string()))
+        .await
+        .unwrap();
+
+    let (results, mut events) = read_ws_results(&mut socket, 1).await;
+    assert_eq!(results[0]["id"], "slider-1");
+    assert_eq!(results[0]["status"], "success");
+    assert_eq!(results[0]["arduino_response"], "OK: Updated");
+
+    if events.is_empty() {
+        let message = tokio::time::timeout(Duration::from_secs(1), socket.next())
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap();
+        events.push(serde_json::from_str(message.to_text().unwrap()).unwrap());
+    }
+    assert_eq!(events[0]["event"], "command");
+    assert_eq!(events[0]["data"]["command"], "UPDATE(1, 100, 50, 30, 1)");
+    assert_eq!(mock.sent(), vec!["<UPDATE(1, 100, 50, 30, 1)>\n"]);
+}
+
+#[tokio::test]
+async fn test_ws_coalesces_queued_updates_for_the_same_module() {
+    use futures::SinkExt;
+    use tokio_tungstenite::tungstenite::Message;
+
+    let mock = MockTransport::new();
+    // The first command needs a retry, so the burst below queues up behind it
+    mock.push_timeout()
+        .push_reply("OK: Updated")
+        .push_reply("OK: Updated")
+        .push_reply("OK: Updated");
+    let state = webserver::models::AppState::new(Boards::single(
+        "mock",
+        SerialHandle::with_link(
+            Some(Box::new(mock.clone())),
+            LinkConfig {
+                retries: 1,
+                ..LinkConfig::default()
+            },
+        ),
+    ));
+    let mut socket = connect_ws(state).await;
+
+    let update = |id: u32, eeprom: i32, power: i32| {
+        Message::Text(
+            json!({"id": id, "type": "update", "power": power, "charge": 0, "time": 0, "eeprom": eeprom})
+                .to_string(),
+        )
+    };
+    socket.send(update(1, 1, 10)).await.unwrap();
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    for (id, power) in [(2, 20), (3, 30), (4, 40)] {
+        socket.send(update(id, 1, power)).await.unwrap();
+    }
+    socket.send(update(5, 2, 50)).await.unwrap();
+
+    let (results, _) = read_ws_results(&mut socket, 5).await;
+    let statuses: Vec<_> = results
+        .iter()
+        .map(|result| (result["id"].as_u64().unwrap(), result["status"].clone()))
+        .collect();
+    assert_eq!(
+        statuses,
+        vec![
+            (1, json!("success")),
+            (2, json!("superseded")),
+            (3, json!("superseded")),
+            (4, json!("success")),
+            (5, json!("success")),
+        ]
+    );
+    assert_eq!(
+        mock.sent(),
+        vec![
+            "<UPDATE(1, 10, 0, 0, 1)>\n",
+            "<UPDATE(1, 10, 0, 0, 1)>\n",
+            "<UPDATE(1, 40, 0, 0, 1)>\n",
+            "<UPDATE(2, 50, 0, 0, 1)>\n",
+        ]
+    );
+}
+
+#[tokio::test]
+async fn test_ws_queue_is_bounded() {
+    use futures::SinkExt;
+    use tokio_tungstenite::tungstenite::Message;
+    use webserver::config::WS_QUEUE_SIZE;
+
+    let mock = MockTransport::new();
+    // Keeps the first command busy while the flood arrives
+    mock.push_timeout().push_reply("OK: Updated");
+    let state = webserver::models::AppState::new(Boards::single(
+        "mock",
+        SerialHandle::with_link(
+            Some(Box::new(mock.clone())),
+            LinkConfig {
+                retries: 1,
+                ..LinkConfig::default()
+            },
+        ),
+    ));
+    let mut socket = connect_ws(state).await;
+
+    socket
+        .send(Message::Text(
+            json!({"id": 0, "type": "update", "power": 1, "charge": 0, "time": 0, "eeprom": 1})
+                .to_string(),
+        ))
+        .await
+        .unwrap();
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    // A line each, so none replaces another; the bad color fails without
+    // reaching the board.
+    let flood = WS_QUEUE_SIZE + 2;
+    for led in 1..=flood {
+        socket
+            .send(Message::Text(
+                json!({"id": led, "type": "led", "ledID": led, "forward": true, "color": "bad", "pulseFrequenz": 1})
+                    .to_string(),
+            ))
+            .await
+            .unwrap();
+    }
+
+    let (results, _) = read_ws_results(&mut socket, flood + 1).await;
+    let code_of = |id: usize| {
+        results
+            .iter()
+            .find(|result| result["id"] == id)
+            .map(|result| result["code"].clone())
+            .unwrap()
+    };
+    assert_eq!(code_of(WS_QUEUE_SIZE), "INVALID_COLOR");
+    assert_eq!(code_of(WS_QUEUE_SIZE + 1), "QUEUE_FULL");
+    assert_eq!(code_of(WS_QUEUE_SIZE + 2), "QUEUE_FULL");
+    // Rejections are answered right away, ahead of the queued commands.
+    assert_eq!(results[0]["id"], 0);
+    assert_eq!(results[1]["id"], WS_QUEUE_SIZE + 1);
+    assert_eq!(mock.sent().len(), 2);
+}
+
+#[tokio::test]
+async fn test_ws_invalid_message_gets_error_result() {
+    use futures::SinkExt;
+    use tokio_tungstenite::tungstenite::Message;
+
+    let mock = MockTransport::new();
+    let mut socket = connect_ws(create_mock_state(&mock)).await;
+
+    socket
+        .send(Message::Text(json!({"id": 9, "type": "dance"}).to_string()))
+        .await
+        .unwrap();
+    socket
+        .send(Message::Text("not json".to_string()))
+        .await
+        .unwrap();
+
+    let (results, _) = read_ws_results(&mut socket, 2).await;
+    assert_eq!(results[0]["id"], 9);
+    assert_eq!(results[0]["status"], "error");
+    assert_eq!(results[0]["code"], "INVALID_REQUEST");
+    assert!(results[1]["id"].is_null());
+    assert_eq!(results[1]["code"], "INVALID_REQUEST");
+    assert!(mock.sent().is_empty());
+}
+
+#[tokio::test]
+async fn test_ws_errors_carry_the_upgrade_request_id() {
+    use futures::SinkExt;
+    use tokio_tungstenite::tungstenite::Message;
+
+    let state = create_test_state();
+    let mut socket = connect_ws_as(state, Some("ws-client-7")).await;
+
+    socket
+        .send(Message::Text(json!({"id": 1, "type": "dance"}).to_string()))
+        .await
+        .unwrap();
+    socket
+        .send(Message::Text(json!({"id": 2, "type": "scan"}).to_string()))
+        .await
+        .unwrap();
+
+    let (results, _) = read_ws_results(&mut socket, 2).await;
+    assert_eq!(results[0]["code"], "INVALID_REQUEST");
+    assert_eq!(results[0]["request_id"], "ws-client-7");
+    assert_eq!(results[1]["code"], "ARDUINO_NOT_CONNECTED");
+    assert_eq!(results[1]["request_id"], "ws-client-7");
+}
+
+fn put_json(uri: &str, body: serde_json::Value) -> Request<Body> {
+    Request::builder()
+        .method("PUT")
+        .uri(uri)
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(Body::from(body.to_string()))
+        .unwrap()
+}
+
+#[tokio::test]
+async fn test_commands_reach_the_module_the_scan_reports() {
+    let mock = MockTransport::new();
+    mock.push_reply(r#"SCAN: [{"EEPROM":9,"I2C":40,"TYPE":1}]"#)
+        .push_reply(r#"SCAN: [{"EEPROM":9,"I2C":40,"TYPE":1}]"#)
+        .push_reply("OK: Updated")
+        .push_reply("OK: LED");
+    let state = create_mock_state(&mock);
+    // Slots 6 and 9 swapped, and the LED ids of two lines
+    state
+        .layout
+        .calibrate(webserver::layout::Calibration {
+            slots: [(40, 6), (37, 9)].into(),
+            lines: [
+                ("line_1_0_0_0".to_string(), 23),
+                ("line_1_1_0_0".to_string(), 22),
+            ]
+            .into(),
+        })
+        .await
+        .unwrap();
+    let app = create_test_router_with_state(state);
+    let get = |uri: &str| Request::builder().uri(uri).body(Body::empty()).unwrap();
+
+    let response = app.clone().oneshot(get("/api/modules")).await.unwrap();
+    let module = json_body(response).await["data"][0].clone();
+    assert_eq!(module["eeprom"], 9);
+    assert_eq!(module["slot"], 6);
+
+    let response = app.clone().oneshot(get("/api/layout")).await.unwrap();
+    let layout = json_body(response).await;
+    assert_eq!(layout["data"]["slots"][6]["module"]["eeprom"], 9);
+    let line = layout["data"]["lines"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|line| line["key"] == "line_1_0_0_0")
+        .unwrap()
+        .clone();
+
+    let response = app
+        .clone()
+        .oneshot(post_json(
+            "/api/update",
+            json!({"eeprom": module["eeprom"], "power": 100, "charge": 50, "time": 30}),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let response = app
+        .oneshot(post_json(
+            "/api/led",
+            json!({"ledID": line["id"], "color": "#FF0000", "forward": true, "pulseFrequenz": 1}),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    // Ids go to the board as the server reported them
+    assert_eq!(
+        mock.sent()[2..],
+        [
+            "<UPDATE(9, 100, 50, 30, 1)>\n",
+            "<LED(23, 1, 255, 0, 0, 1)>\n",
+        ]
+    );
+}
+
+#[tokio::test]
+async fn test_calibration_endpoints_edit_the_layout() {
+    let state = webserver::models::AppState {
+        admin_token: Some("secret".into()),
+        ..create_test_state()
+    };
+    state.scan_cache.update(
+        vec![ModuleInfo {
+            eeprom: 9,
+            i2c: 60,
+            module_type: ModuleType::Solar,
+        }],
+        None,
+    );
+    let app = create_test_router_with_state(state.clone());
+
+    // The second address replaces the first
+    for i2c in [59, 60] {
+        let response = app
+            .clone()
+            .oneshot(as_admin(put_json(
+                "/api/admin/calibration/slots/1",
+                json!({ "i2c": i2c }),
+            )))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+    let response = app
+        .clone()
+        .oneshot(as_admin(
+            Request::builder()
+                .uri("/api/admin/calibration")
+                .body(Body::empty())
+                .unwrap(),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = json_body(response).await;
+    assert_eq!(body["data"], json!({"slots": {"60": 1}, "lines": {}}));
+
+    let response = app
+        .clone()
+        .oneshot(as_admin(put_json(
+            "/api/admin/calibration/lines/line_2_1_2_0",
+            json!({"ledID": 40}),
+        )))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let response = app
+        .clone()
+        .oneshot(
+            Request::builder()
+                .uri("/api/layout")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    let body = json_body(response).await;
+    let slot = &body["data"]["slots"][1];
+    assert_eq!(slot["i2c"], 60);
+    assert_eq!(slot["module"]["eeprom"], 9);
+    assert_eq!(slot["lines"], json!([0, 40]));
+    assert!(body.get("message").is_none());
+
+    // Slot 2 is already at I2C 33
+    let response = app
+        .clone()
+        .oneshot(as_admin(put_json(
+            "/api/admin/calibration",
+            json!({"slots": {"33": 1}}),
+        )))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    let body = json_body(response).await;
+    assert_eq!(body["code"], "INVALID_REQUEST");
+    assert_eq!(body["message"], "Slots 1 and 2 are both at I2C 33");
+    assert_eq!(state.layout.calibration().slots.get(&60), Some(&1));
+
+    let response = app
+        .clone()
+        .oneshot(as_admin(
+            Request::builder()
+                .method("DELETE")
+                .uri("/api/admin/calibration")
+                .body(Body::empty())
+                .unwrap(),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(state.layout.layout().slot(1).unwrap().i2c, 32);
+
+    let response = app
+        .oneshot(as_admin(
+            Request::builder()
+                .uri("/api/admin/calibration")
+                .body(Body::empty())
+                .unwrap(),
+        ))
+        .await
+        .unwrap();
+    let body = json_body(response).await;
+    assert_eq!(body["data"], json!({"slots": {}, "lines": {}}));
+}
+
+fn as_admin(mut request: Request<Body>) -> Request<Body> {
+    request.headers_mut().insert(
+        header::AUTHORIZATION,
+        header::HeaderValue::from_static("Bearer secret"),
+    );
+    request
+}
+
+#[tokio::test]
+async fn test_admin_routes_need_the_token() {
+    let state = create_test_state();
+    let disabled = create_test_router_with_state(state.clone());
+    let response = disabled
+        .oneshot(as_admin(put_json(
+            "/api/admin/calibration",
+            json!({"slots": {"33": 1, "32": 2}}),
+        )))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    let body = json_body(response).await;
+    assert_eq!(body["code"], "UNAUTHORIZED");
+    assert!(body["request_id"].is_string());
+
+    let app = create_test_router_with_state(webserver::models::AppState {
+        admin_token: Some("secret".into()),
+        ..state.clone()
+    });
+    for authorization in [None, Some("Bearer guess"), Some("secret")] {
+        let mut request = put_json(
+            "/api/admin/calibration",
+            json!({"slots": {"33": 1, "32": 2}}),
+        );
+        if let Some(value) = authorization {
+            request
+                .headers_mut()
+                .insert(header::AUTHORIZATION, value.parse().unwrap());
+        }
+        let response = app.clone().oneshot(request).await.unwrap();
+        assert_eq!(
+            response.status(),
+            StatusCode::UNAUTHORIZED,
+            "{:?}",
+            authorization
+        );
+    }
+    assert!(state.layout.calibration().slots.is_empty());
+
+    // Other origins get no CORS headers for admin routes, unlike the others.
+    let preflight = |uri: &str| {
+        Request::builder()
+            .method("OPTIONS")
+            .uri(uri)
+            .header(header::ORIGIN, "http://elsewhere.example")
+            .header(header::ACCESS_CONTROL_REQUEST_METHOD, "PUT")
+            .body(Body::empty())
+            .unwrap()
+    };
+    let response = app
+        .clone()
+        .oneshot(preflight("/api/admin/calibration"))
+        .await
+        .unwrap();
+    assert!(response
+        .headers()
+        .get(header::ACCESS_CONTROL_ALLOW_ORIGIN)
+        .is_none());
+    let response = app.oneshot(preflight("/api/layout")).await.unwrap();
+    assert!(response
+        .headers()
+        .get(header::ACCESS_CONTROL_ALLOW_ORIGIN)
+        .is_some());
+}